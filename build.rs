@@ -0,0 +1,17 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Link settings for the Node.js addon
+    #[cfg(feature = "napi")]
+    napi_build::setup();
+
+    // gRPC service code, compiled without needing protoc installed
+    #[cfg(feature = "server")]
+    {
+        println!("cargo:rerun-if-changed=proto/ingest.proto");
+        let descriptors = protox::compile(["proto/ingest.proto"], ["proto"])?;
+        tonic_build::configure()
+            .build_client(false)
+            .compile_fds(descriptors)?;
+    }
+
+    Ok(())
+}
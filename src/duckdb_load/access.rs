@@ -0,0 +1,51 @@
+use std::error::Error;
+
+use super::{naming, DuckDBFileProcessor};
+
+impl DuckDBFileProcessor {
+    // Hand the loaded table to its owner role and let the read roles select from it
+    pub(super) fn apply_table_access(&self) -> Result<(), Box<dyn Error>> {
+        if self.options.table_owner.is_none() && self.options.grant_select.is_empty() {
+            return Ok(());
+        }
+
+        let mut relations = vec![naming::qualified_name(&self.schema_name, &self.table_name)];
+        // The view of a versioned table reads its version with the owner's privileges
+        if let Some(version) = &self.report.borrow().version_table {
+            relations.push(naming::qualified_name(&self.schema_name, version));
+        }
+
+        let mut access_sql = String::new();
+        for relation in &relations {
+            if let Some(owner) = &self.options.table_owner {
+                access_sql.push_str(&format!(
+                    "ALTER TABLE {} OWNER TO {};\n",
+                    relation,
+                    role_name(owner)
+                ));
+            }
+            if !self.options.grant_select.is_empty() {
+                let roles: Vec<String> = self
+                    .options
+                    .grant_select
+                    .iter()
+                    .map(|role| role_name(role))
+                    .collect();
+                access_sql.push_str(&format!(
+                    "GRANT SELECT ON {} TO {};\n",
+                    relation,
+                    roles.join(", ")
+                ));
+            }
+        }
+        self.postgres_execute("set table owner and grants", &access_sql)
+    }
+}
+
+fn role_name(role: &str) -> String {
+    if role.eq_ignore_ascii_case("public") {
+        "PUBLIC".to_string()
+    } else {
+        naming::quote_identifier(role)
+    }
+}
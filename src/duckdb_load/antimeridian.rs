@@ -0,0 +1,28 @@
+use super::{naming, Antimeridian, DuckDBFileProcessor};
+
+impl DuckDBFileProcessor {
+    // SQL fixing features that cross the antimeridian, run on the staging table in PostGIS
+    // After reprojection such features wrap the long way round, so they span over 180 degrees
+    pub(super) fn antimeridian_sql(&self, table: &str, geom_column: &str) -> Option<String> {
+        let column = naming::quote_identifier(geom_column);
+        let fixed = match self.options.antimeridian {
+            Antimeridian::Keep => return None,
+            // Cut at 180 in 0-360 longitudes, then move the eastern parts back by -360
+            Antimeridian::Split => format!(
+                "ST_CollectionHomogenize(ST_WrapX(ST_Split(ST_ShiftLongitude({geom}),
+                    ST_SetSRID(ST_MakeLine(ST_MakePoint(180, -90), ST_MakePoint(180, 90)), ST_SRID({geom}))),
+                    180, -360))",
+                geom = column
+            ),
+            Antimeridian::Shift => format!("ST_ShiftLongitude({})", column),
+        };
+        Some(format!(
+            "UPDATE {table} SET {geom} = {fixed}
+            WHERE ST_XMax({geom}) - ST_XMin({geom}) > 180
+                AND GeometryType({geom}) NOT IN ('POINT', 'MULTIPOINT');",
+            table = table,
+            geom = column,
+            fixed = fixed
+        ))
+    }
+}
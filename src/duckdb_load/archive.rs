@@ -0,0 +1,505 @@
+use flate2::read::DeflateDecoder;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use super::logging::{log_info, log_warn};
+
+// Data files a zip may carry - Office files are zips too, and are left to Excel detection
+const DATA_EXTENSIONS: [&str; 13] = [
+    "shp", "gpkg", "geojson", "json", "csv", "tsv", "parquet", "dxf", "tif", "tiff", "xlsx", "ods",
+    "pbf",
+];
+const ODS_MIMETYPE: &[u8] = b"application/vnd.oasis.opendocument.spreadsheet";
+// Sidecars extracted alongside a shapefile
+const SHAPEFILE_EXTENSIONS: [&str; 5] = ["shp", "shx", "dbf", "prj", "cpg"];
+// Limits on what an archive may hold, so a zip bomb fails rather than filling the disk. Sizes
+// are counted as the data is decompressed, as the sizes a zip declares can't be trusted
+const MAX_ENTRIES: usize = 10_000;
+const MAX_EXTRACTED_BYTES: u64 = 16 * 1024 * 1024 * 1024;
+// Largest single entry read into memory, such as an Excel workbook's XML
+const MAX_ENTRY_READ_BYTES: u64 = 256 * 1024 * 1024;
+
+// The data file extracted from a zip archive, in a temporary directory removed on drop
+#[derive(Debug)]
+pub(super) struct ZipExtract {
+    pub archive: String,
+    pub directory: PathBuf,
+    pub path: PathBuf,
+}
+
+impl ZipExtract {
+    // Extract the single data file in a zip, or None when the path isn't a zip of data files
+    pub(super) fn open(path: &str) -> Result<Option<Self>, Box<dyn Error>> {
+        let mut file = match File::open(path) {
+            Ok(file) if Path::new(path).is_file() => file,
+            _ => return Ok(None),
+        };
+        let mut signature = [0u8; 4];
+        if file.read(&mut signature)? < 4 || signature != [0x50, 0x4B, 0x03, 0x04] {
+            return Ok(None);
+        }
+        let entries = read_central_directory(&mut file)?;
+        if is_document(&entries) {
+            return Ok(None);
+        }
+        if entries.len() > MAX_ENTRIES {
+            return Err(format!(
+                "Zip archive {} holds {} entries, more than the {} allowed",
+                path,
+                entries.len(),
+                MAX_ENTRIES
+            )
+            .into());
+        }
+        let data_entries = entries
+            .iter()
+            .filter(|entry| !entry.name.ends_with('/') && !entry.name.starts_with("__MACOSX/"))
+            .filter(|entry| {
+                entry_extension(&entry.name)
+                    .is_some_and(|extension| DATA_EXTENSIONS.contains(&extension.as_str()))
+            })
+            .collect::<Vec<_>>();
+        let main_entry = match data_entries.as_slice() {
+            [] => return Err(format!("Zip archive {} holds no supported data file", path).into()),
+            [entry] => *entry,
+            several => {
+                let names = several
+                    .iter()
+                    .map(|entry| entry.name.as_str())
+                    .collect::<Vec<_>>();
+                return Err(format!(
+                    "Zip archive {} holds several data files, expected one: {:?}",
+                    path, names
+                )
+                .into());
+            }
+        };
+
+        // A shapefile needs its sidecars, which share its name and folder in the archive
+        let stem = main_entry
+            .name
+            .rsplit_once('.')
+            .map_or("", |(stem, _)| stem);
+        let is_shapefile = entry_extension(&main_entry.name).as_deref() == Some("shp");
+        let to_extract = entries
+            .iter()
+            .filter(|entry| {
+                entry.name == main_entry.name
+                    || (is_shapefile
+                        && entry
+                            .name
+                            .rsplit_once('.')
+                            .is_some_and(|(other_stem, _)| other_stem == stem)
+                        && entry_extension(&entry.name).is_some_and(|extension| {
+                            SHAPEFILE_EXTENSIONS.contains(&extension.as_str())
+                        }))
+            })
+            .collect::<Vec<_>>();
+
+        // Unique per load, so loads of the same table never share or remove each other's files
+        let directory = tempfile::Builder::new()
+            .prefix("duckdb_postgis_")
+            .suffix("_zip")
+            .tempdir()?
+            .into_path();
+        let extract = Self {
+            archive: path.to_string(),
+            path: directory.join(entry_file_name(&main_entry.name)),
+            directory,
+        };
+        // On error the directory is removed when extract is dropped
+        let mut remaining = MAX_EXTRACTED_BYTES;
+        for entry in to_extract {
+            let written = extract_entry(&mut file, entry, &extract.directory, remaining)?;
+            remaining -= written;
+        }
+        log_info!("Extracted {} from {}", main_entry.name, path);
+        Ok(Some(extract))
+    }
+}
+
+impl Drop for ZipExtract {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.directory) {
+            log_warn!("Failed to remove {}: {}", self.directory.display(), e);
+        }
+    }
+}
+
+// Whether a file is a zip archive of data files, which is loaded by extracting it, rather than
+// a document such as an Excel workbook that is also a zip
+pub(super) fn is_data_archive(path: &str) -> Result<bool, Box<dyn Error>> {
+    let mut file = match File::open(path) {
+        Ok(file) if Path::new(path).is_file() => file,
+        _ => return Ok(false),
+    };
+    let mut signature = [0u8; 4];
+    if file.read(&mut signature)? < 4 || signature != [0x50, 0x4B, 0x03, 0x04] {
+        return Ok(false);
+    }
+    Ok(!is_document(&read_central_directory(&mut file)?))
+}
+
+// Office Open XML files list their content types, and OpenDocument files their mimetype
+fn is_document(entries: &[ZipEntry]) -> bool {
+    entries
+        .iter()
+        .any(|entry| entry.name == "[Content_Types].xml" || entry.name == "mimetype")
+}
+
+struct ZipEntry {
+    name: String,
+    method: u16,
+    flags: u16,
+    compressed_size: u64,
+    local_header_offset: u64,
+}
+
+// Entries listed in the central directory at the end of the archive
+fn read_central_directory(file: &mut File) -> Result<Vec<ZipEntry>, Box<dyn Error>> {
+    // The end of central directory record is 22 bytes plus a comment of up to 64KB
+    let length = file.seek(SeekFrom::End(0))?;
+    let tail_length = length.min(22 + 65535);
+    let mut tail = vec![0u8; tail_length as usize];
+    file.seek(SeekFrom::Start(length - tail_length))?;
+    file.read_exact(&mut tail)?;
+    let end = (0..tail.len().saturating_sub(21))
+        .rev()
+        .find(|&index| tail[index..index + 4] == [0x50, 0x4B, 0x05, 0x06])
+        .ok_or("Zip archive has no central directory")?;
+    let entry_count = u16_at(&tail, end + 10);
+    let directory_size = u32_at(&tail, end + 12);
+    let directory_offset = u32_at(&tail, end + 16);
+    if entry_count == u16::MAX || directory_offset == u32::MAX {
+        return Err("ZIP64 archives are not supported".into());
+    }
+    // Checked before the directory is allocated, so a corrupt size can't claim gigabytes
+    if directory_offset as u64 + directory_size as u64 > length {
+        return Err("Zip archive central directory runs past the end of the file".into());
+    }
+
+    let mut directory = vec![0u8; directory_size as usize];
+    file.seek(SeekFrom::Start(directory_offset as u64))?;
+    file.read_exact(&mut directory)?;
+    let mut entries = Vec::with_capacity(entry_count as usize);
+    let mut offset = 0;
+    for _ in 0..entry_count {
+        if directory.len() < offset + 46
+            || directory[offset..offset + 4] != [0x50, 0x4B, 0x01, 0x02]
+        {
+            return Err("Zip archive central directory is corrupt".into());
+        }
+        let name_length = u16_at(&directory, offset + 28) as usize;
+        let extra_length = u16_at(&directory, offset + 30) as usize;
+        let comment_length = u16_at(&directory, offset + 32) as usize;
+        let name = directory
+            .get(offset + 46..offset + 46 + name_length)
+            .ok_or("Zip archive central directory is corrupt")?;
+        entries.push(ZipEntry {
+            name: String::from_utf8_lossy(name).into_owned(),
+            flags: u16_at(&directory, offset + 8),
+            method: u16_at(&directory, offset + 10),
+            compressed_size: u32_at(&directory, offset + 20) as u64,
+            local_header_offset: u32_at(&directory, offset + 42) as u64,
+        });
+        offset += 46 + name_length + extra_length + comment_length;
+    }
+    Ok(entries)
+}
+
+// Whether the start of a file is a zip whose first entry is the mimetype of an OpenDocument
+// spreadsheet, which the format stores uncompressed so files can be recognised like this
+pub(super) fn is_opendocument_spreadsheet(header: &[u8]) -> bool {
+    if header.len() < 30 || u16_at(header, 8) != 0 {
+        return false;
+    }
+    let name_length = u16_at(header, 26) as usize;
+    let contents = 30 + name_length + u16_at(header, 28) as usize;
+    header.get(30..30 + name_length) == Some(b"mimetype".as_slice())
+        && header
+            .get(contents..)
+            .is_some_and(|contents| contents.starts_with(ODS_MIMETYPE))
+}
+
+// Read one entry of a zip, such as xl/workbook.xml in an Excel file, into memory, or None when
+// the archive has no entry of that name
+pub(super) fn read_entry(path: &str, name: &str) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+    let mut file = File::open(path)?;
+    let entries = read_central_directory(&mut file)?;
+    let Some(entry) = entries.iter().find(|entry| entry.name == name) else {
+        return Ok(None);
+    };
+    let mut contents = Vec::new();
+    entry_reader(&mut file, entry)?
+        .take(MAX_ENTRY_READ_BYTES + 1)
+        .read_to_end(&mut contents)?;
+    if contents.len() as u64 > MAX_ENTRY_READ_BYTES {
+        return Err(format!(
+            "Zip entry {} expands to more than {} bytes",
+            name, MAX_ENTRY_READ_BYTES
+        )
+        .into());
+    }
+    Ok(Some(contents))
+}
+
+// Extract an entry into the directory, failing once it passes the bytes the archive has left.
+// Returns the bytes written
+fn extract_entry(
+    file: &mut File,
+    entry: &ZipEntry,
+    directory: &Path,
+    remaining: u64,
+) -> Result<u64, Box<dyn Error>> {
+    let mut data = entry_reader(file, entry)?.take(remaining + 1);
+    // Only the file name is used, so entries can't be written outside the directory
+    let mut output = File::create(directory.join(entry_file_name(&entry.name)))?;
+    let written = io::copy(&mut data, &mut output)?;
+    if written > remaining {
+        return Err(format!(
+            "Zip archive expands to more than {} bytes, so it is not extracted",
+            MAX_EXTRACTED_BYTES
+        )
+        .into());
+    }
+    Ok(written)
+}
+
+// The decompressed contents of an entry
+fn entry_reader<'a>(
+    file: &'a mut File,
+    entry: &ZipEntry,
+) -> Result<Box<dyn Read + 'a>, Box<dyn Error>> {
+    if entry.flags & 1 != 0 {
+        return Err(format!("Zip entry {} is encrypted", entry.name).into());
+    }
+    // The local header repeats the name and may have a different extra field length
+    let mut header = [0u8; 30];
+    file.seek(SeekFrom::Start(entry.local_header_offset))?;
+    file.read_exact(&mut header)?;
+    let data_offset =
+        entry.local_header_offset + 30 + u16_at(&header, 26) as u64 + u16_at(&header, 28) as u64;
+    file.seek(SeekFrom::Start(data_offset))?;
+    let data = Read::by_ref(file).take(entry.compressed_size);
+    match entry.method {
+        0 => Ok(Box::new(data)),
+        8 => Ok(Box::new(DeflateDecoder::new(data))),
+        method => Err(format!(
+            "Zip entry {} uses unsupported compression method {}",
+            entry.name, method
+        )
+        .into()),
+    }
+}
+
+fn entry_file_name(name: &str) -> &str {
+    name.rsplit('/').next().unwrap_or(name)
+}
+
+fn entry_extension(name: &str) -> Option<String> {
+    entry_file_name(name)
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_lowercase())
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::DeflateEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    // Zip of (name, method, data) entries, with data already compressed by the method. CRCs are
+    // left as zero, as they aren't checked
+    fn zip(entries: &[(&str, u16, Vec<u8>)]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut directory = Vec::new();
+        for (name, method, data) in entries {
+            let offset = bytes.len() as u32;
+            bytes.extend([0x50, 0x4B, 0x03, 0x04, 20, 0, 0, 0]);
+            bytes.extend(method.to_le_bytes());
+            bytes.extend([0u8; 8]);
+            bytes.extend((data.len() as u32).to_le_bytes());
+            bytes.extend([0u8; 4]);
+            bytes.extend((name.len() as u16).to_le_bytes());
+            bytes.extend([0u8; 2]);
+            bytes.extend(name.as_bytes());
+            bytes.extend(data);
+
+            directory.extend([0x50, 0x4B, 0x01, 0x02, 20, 0, 20, 0, 0, 0]);
+            directory.extend(method.to_le_bytes());
+            directory.extend([0u8; 8]);
+            directory.extend((data.len() as u32).to_le_bytes());
+            directory.extend([0u8; 4]);
+            directory.extend((name.len() as u16).to_le_bytes());
+            directory.extend([0u8; 12]);
+            directory.extend(offset.to_le_bytes());
+            directory.extend(name.as_bytes());
+        }
+        let directory_offset = bytes.len() as u32;
+        bytes.extend(&directory);
+        bytes.extend([0x50, 0x4B, 0x05, 0x06, 0, 0, 0, 0]);
+        bytes.extend((entries.len() as u16).to_le_bytes());
+        bytes.extend((entries.len() as u16).to_le_bytes());
+        bytes.extend((directory.len() as u32).to_le_bytes());
+        bytes.extend(directory_offset.to_le_bytes());
+        bytes.extend([0u8; 2]);
+        bytes
+    }
+
+    fn stored(name: &'static str, data: &str) -> (&'static str, u16, Vec<u8>) {
+        (name, 0, data.as_bytes().to_vec())
+    }
+
+    fn write_zip(dir: &Path, bytes: &[u8]) -> String {
+        let path = dir.join("upload.zip");
+        fs::write(&path, bytes).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn extracts_a_shapefile_with_its_sidecars() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_zip(
+            dir.path(),
+            &zip(&[
+                stored("data/", ""),
+                stored("data/parcels.shp", "shp"),
+                stored("data/parcels.DBF", "dbf"),
+                stored("data/parcels.shx", "shx"),
+                stored("data/other.prj", "prj"),
+                stored("__MACOSX/data/._parcels.shp", "resource fork"),
+            ]),
+        );
+        assert!(is_data_archive(&path).unwrap());
+
+        let extract = ZipExtract::open(&path).unwrap().unwrap();
+        assert_eq!(extract.path, extract.directory.join("parcels.shp"));
+        assert_eq!(fs::read_to_string(&extract.path).unwrap(), "shp");
+        let mut names = fs::read_dir(&extract.directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["parcels.DBF", "parcels.shp", "parcels.shx"]);
+
+        let directory = extract.directory.clone();
+        drop(extract);
+        assert!(!directory.exists());
+    }
+
+    #[test]
+    fn extracts_entries_by_file_name_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_zip(dir.path(), &zip(&[stored("../../escape.csv", "a,b")]));
+        let extract = ZipExtract::open(&path).unwrap().unwrap();
+        assert_eq!(extract.path, extract.directory.join("escape.csv"));
+        assert!(extract.path.is_file());
+    }
+
+    #[test]
+    fn reads_deflated_entries() {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"<workbook/>").unwrap();
+        let deflated = encoder.finish().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_zip(
+            dir.path(),
+            &zip(&[
+                stored("[Content_Types].xml", "<Types/>"),
+                ("xl/workbook.xml", 8, deflated),
+            ]),
+        );
+        assert!(!is_data_archive(&path).unwrap());
+        assert!(ZipExtract::open(&path).unwrap().is_none());
+        assert_eq!(
+            read_entry(&path, "xl/workbook.xml").unwrap().unwrap(),
+            b"<workbook/>"
+        );
+        assert!(read_entry(&path, "xl/missing.xml").unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_archives_without_one_data_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_zip(dir.path(), &zip(&[stored("readme.txt", "hello")]));
+        let error = ZipExtract::open(&path).unwrap_err();
+        assert!(error.to_string().contains("no supported data file"));
+
+        let path = write_zip(
+            dir.path(),
+            &zip(&[stored("a.csv", "a"), stored("b.geojson", "{}")]),
+        );
+        let error = ZipExtract::open(&path).unwrap_err();
+        assert!(error.to_string().contains("several data files"));
+    }
+
+    #[test]
+    fn rejects_malformed_archives() {
+        let dir = tempfile::tempdir().unwrap();
+        let valid = zip(&[stored("roads.csv", "a,b")]);
+        let end = valid.len() - 22;
+
+        // A directory size far past the end of the file is refused before it's allocated
+        let mut oversized = valid.clone();
+        oversized[end + 12..end + 16].copy_from_slice(&u32::MAX.to_le_bytes());
+        let path = write_zip(dir.path(), &oversized);
+        let error = ZipExtract::open(&path).unwrap_err();
+        assert!(error.to_string().contains("runs past the end"));
+
+        // A directory offset pointing at the local header instead
+        let mut misplaced = valid.clone();
+        misplaced[end + 16..end + 20].copy_from_slice(&0u32.to_le_bytes());
+        let path = write_zip(dir.path(), &misplaced);
+        let error = ZipExtract::open(&path).unwrap_err();
+        assert!(error.to_string().contains("corrupt"));
+
+        let mut miscounted = valid.clone();
+        miscounted[end + 10] = 2;
+        let path = write_zip(dir.path(), &miscounted);
+        let error = ZipExtract::open(&path).unwrap_err();
+        assert!(error.to_string().contains("corrupt"));
+
+        let path = write_zip(dir.path(), &valid[..end]);
+        let error = ZipExtract::open(&path).unwrap_err();
+        assert!(error.to_string().contains("no central directory"));
+
+        // Not a zip at all
+        let path = write_zip(dir.path(), b"id,name\n1,a\n");
+        assert!(ZipExtract::open(&path).unwrap().is_none());
+        assert!(!is_data_archive(&path).unwrap());
+    }
+
+    #[test]
+    fn rejects_encrypted_and_unknown_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut bytes = zip(&[stored("roads.csv", "a,b")]);
+        let end = bytes.len() - 22;
+        let directory = u32_at(&bytes, end + 16) as usize;
+        bytes[directory + 8] = 1;
+        let path = write_zip(dir.path(), &bytes);
+        let error = ZipExtract::open(&path).unwrap_err();
+        assert!(error.to_string().contains("encrypted"));
+
+        let path = write_zip(dir.path(), &zip(&[("roads.csv", 12, b"BZh".to_vec())]));
+        let error = ZipExtract::open(&path).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("unsupported compression method 12"));
+    }
+}
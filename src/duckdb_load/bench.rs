@@ -0,0 +1,128 @@
+use std::error::Error;
+use std::io;
+use std::process;
+use std::time::Instant;
+
+use super::logging::log_info;
+use super::{
+    credentials, launch_process_file_with_options, naming, open_connection, sql,
+    unique_attach_alias, LoadStage, ProcessorOptions,
+};
+
+// One load of a benchmark
+#[derive(Debug, Clone)]
+pub struct BenchRun {
+    // Wall time of the whole load, in seconds
+    pub seconds: f64,
+    pub rows_loaded: u64,
+    // Time spent in each load stage, as in LoadReport::stage_seconds
+    pub stage_seconds: Vec<(String, f64)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BenchReport {
+    // Schema the loads wrote to
+    pub schema: String,
+    pub runs: Vec<BenchRun>,
+}
+
+impl BenchReport {
+    // Mean seconds per run of each stage, in load order, with the rows per second through it
+    pub fn stage_throughput(&self) -> Vec<(String, f64, f64)> {
+        let runs = self.runs.len().max(1) as f64;
+        let rows = self.runs.iter().map(|run| run.rows_loaded).sum::<u64>() as f64 / runs;
+        [LoadStage::Detection, LoadStage::Transform, LoadStage::Write]
+            .iter()
+            .map(|stage| {
+                let seconds = self
+                    .runs
+                    .iter()
+                    .flat_map(|run| &run.stage_seconds)
+                    .filter(|(name, _)| name == stage.name())
+                    .map(|(_, seconds)| seconds)
+                    .sum::<f64>()
+                    / runs;
+                let throughput = if seconds > 0.0 { rows / seconds } else { 0.0 };
+                (stage.name().to_string(), seconds, throughput)
+            })
+            .collect()
+    }
+}
+
+// Load file_path end to end `runs` times with the same options and time each load
+// Without a schema the loads go to a throwaway schema, dropped with its tables afterwards
+pub fn run_bench(
+    file_path: &str,
+    table_name: &str,
+    postgis_uri: &str,
+    schema_name: Option<&str>,
+    runs: usize,
+    options: &ProcessorOptions,
+) -> Result<BenchReport, io::Error> {
+    let schema = match schema_name {
+        Some(schema) => credentials::resolve_schema(schema),
+        None => format!("duckdb_postgis_bench_{}", process::id()),
+    };
+
+    let mut results = Vec::with_capacity(runs);
+    let mut outcome = Ok(());
+    for run in 1..=runs {
+        log_info!("Benchmark run {} of {}", run, runs);
+        let started = Instant::now();
+        match launch_process_file_with_options(
+            file_path,
+            table_name,
+            postgis_uri,
+            &schema,
+            options.clone(),
+        ) {
+            Ok(report) => results.push(BenchRun {
+                seconds: started.elapsed().as_secs_f64(),
+                rows_loaded: report.rows_loaded,
+                stage_seconds: report.stage_seconds,
+            }),
+            Err(e) => {
+                outcome = Err(e);
+                break;
+            }
+        }
+    }
+
+    if schema_name.is_none() {
+        let connection = credentials::resolve_target_connection(postgis_uri);
+        if let Err(e) = drop_schema(&connection, &schema) {
+            let e = io::Error::other(format!(
+                "Error dropping benchmark schema '{}': {}",
+                schema,
+                credentials::redact(&e.to_string(), &connection)
+            ));
+            // A failed load is the more useful error to return
+            outcome = outcome.and(Err(e));
+        }
+    }
+    outcome?;
+    Ok(BenchReport {
+        schema,
+        runs: results,
+    })
+}
+
+fn drop_schema(postgis_uri: &str, schema_name: &str) -> Result<(), Box<dyn Error>> {
+    let conn = open_connection()?;
+    let attach_alias = unique_attach_alias();
+    conn.execute(
+        &format!(
+            "ATTACH '{}' AS {} (TYPE POSTGRES)",
+            postgis_uri.replace('\'', "''"),
+            attach_alias
+        ),
+        [],
+    )?;
+    let drop_sql = format!(
+        "DROP SCHEMA IF EXISTS {} CASCADE;",
+        naming::quote_identifier(schema_name)
+    );
+    conn.execute(&sql::postgres_execute(&attach_alias, &drop_sql), [])?;
+    log_info!("Dropped benchmark schema {}", schema_name);
+    Ok(())
+}
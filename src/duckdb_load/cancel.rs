@@ -0,0 +1,228 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use super::logging::{log_debug, log_warn};
+use super::{credentials, sql, timeouts, DuckDBFileProcessor};
+
+// How often the watchdog checks for cancellation and timeouts
+const WATCH_INTERVAL: Duration = Duration::from_millis(100);
+
+// Shared flag for cancelling a load from another thread, set with ProcessorOptions::cancellation.
+// The load's running Postgres statements are cancelled straight away, and the load stops with a
+// LoadCancelled error after dropping its staging table. The duckdb crate has no way to interrupt
+// a connection, so a DuckDB statement that is already running, such as reading the source file,
+// finishes first and the load stops at its next checkpoint
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+// Error a cancelled load returns, inside the io::Error - tell it from other failures with
+// LoadCancelled::is_cancelled
+#[derive(Debug)]
+pub struct LoadCancelled {
+    file_path: String,
+}
+
+impl LoadCancelled {
+    pub(super) fn error(file_path: &str) -> io::Error {
+        io::Error::other(Self {
+            file_path: file_path.to_string(),
+        })
+    }
+
+    // Whether a load failed because it was cancelled
+    pub fn is_cancelled(error: &io::Error) -> bool {
+        error.get_ref().is_some_and(|e| e.is::<Self>())
+    }
+}
+
+impl fmt::Display for LoadCancelled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Load of '{}' cancelled", self.file_path)
+    }
+}
+
+impl Error for LoadCancelled {}
+
+// Application name the load's Postgres sessions are tagged with, so the watchdog can find them.
+// Loads sharing an attachment run one at a time, so the alias and process id tell them apart
+pub(super) fn application_name(attach_alias: &str) -> String {
+    format!("duckdb_postgis_{}_{}", process::id(), attach_alias)
+}
+
+// Connection string with the application name set, unless the caller set their own - the
+// watchdog then can't tell the load's sessions apart, so only stops the load at checkpoints
+pub(super) fn with_application_name(connection: &str, name: &str) -> String {
+    if connection.is_empty() || connection.contains("application_name") {
+        return connection.to_string();
+    }
+    if timeouts::is_uri(connection) {
+        let separator = if connection.contains('?') { '&' } else { '?' };
+        format!("{}{}application_name={}", connection, separator, name)
+    } else {
+        format!("{} application_name={}", connection, name)
+    }
+}
+
+// Thread cancelling the load's running Postgres statements once it's cancelled or out of time,
+// rather than leaving them to finish before the next checkpoint. Stopped when dropped
+pub(super) struct Watchdog {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl DuckDBFileProcessor {
+    pub(super) fn is_cancelled(&self) -> bool {
+        self.options
+            .cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+    }
+
+    pub(super) fn check_cancelled(&self) -> Result<(), Box<dyn Error>> {
+        if self.is_cancelled() {
+            return Err("Load cancelled".into());
+        }
+        Ok(())
+    }
+
+    // Watch the load from another DuckDB connection, which sees the same attachment. None when
+    // the load can't be cancelled and has no timeouts
+    pub(super) fn start_watchdog(&self) -> Option<Watchdog> {
+        let timeouts = &self.options.timeouts;
+        let limited = timeouts.detection.is_some()
+            || timeouts.transform.is_some()
+            || timeouts.write.is_some()
+            || timeouts.total.is_some();
+        if self.options.cancellation.is_none() && !limited {
+            return None;
+        }
+        let conn = match self.conn.try_clone() {
+            Ok(conn) => conn,
+            Err(e) => {
+                log_warn!("Could not start the load watchdog: {}", e);
+                return None;
+            }
+        };
+
+        let cancellation = self.options.cancellation.clone();
+        let deadline = Arc::clone(&self.deadline);
+        let stop = Arc::new(AtomicBool::new(false));
+        let attach_alias = self.attach_alias.clone();
+        let postgis_uri = self.postgis_uri.clone();
+        let cancel_sql = sql::postgres_execute(
+            &self.attach_alias,
+            &format!(
+                "SELECT pg_cancel_backend(pid) FROM pg_stat_activity
+                WHERE application_name = {} AND pid <> pg_backend_pid();",
+                sql::literal(&application_name(&self.attach_alias))
+            ),
+        );
+        let thread = thread::spawn({
+            let stop = Arc::clone(&stop);
+            move || {
+                while !stop.load(Ordering::SeqCst) {
+                    let cancelled = cancellation
+                        .as_ref()
+                        .is_some_and(CancellationToken::is_cancelled);
+                    let expired = deadline
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .is_some_and(|deadline| Instant::now() > deadline);
+                    if !cancelled && !expired {
+                        thread::sleep(WATCH_INTERVAL);
+                        continue;
+                    }
+                    // Nothing runs in Postgres before the target is attached
+                    let attached = conn.query_row(
+                        "SELECT count(*) > 0 FROM duckdb_databases() WHERE database_name = ?;",
+                        [&attach_alias],
+                        |row| row.get::<_, bool>(0),
+                    );
+                    // Fires once, so the cleanup of the stopped load isn't cancelled too
+                    match attached.and_then(|attached| match attached {
+                        true => conn.execute_batch(&cancel_sql),
+                        false => Ok(()),
+                    }) {
+                        Ok(()) => log_debug!("Cancelled the load's running Postgres statements"),
+                        Err(e) => log_warn!(
+                            "Could not cancel the load's running Postgres statements: {}",
+                            credentials::redact(&e.to_string(), &postgis_uri)
+                        ),
+                    }
+                    return;
+                }
+            }
+        });
+        Some(Watchdog {
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancelled_loads_are_told_apart() {
+        let cancelled = LoadCancelled::error("roads.gpkg");
+        assert!(LoadCancelled::is_cancelled(&cancelled));
+        assert_eq!(cancelled.to_string(), "Load of 'roads.gpkg' cancelled");
+        assert_ne!(cancelled.kind(), io::ErrorKind::Interrupted);
+        let failed = io::Error::other("Load failed");
+        let interrupted = io::Error::from(io::ErrorKind::Interrupted);
+        assert!(!LoadCancelled::is_cancelled(&failed));
+        assert!(!LoadCancelled::is_cancelled(&interrupted));
+    }
+
+    #[test]
+    fn tags_connections_with_the_application_name() {
+        assert_eq!(
+            with_application_name("postgresql://localhost/gis", "load_1"),
+            "postgresql://localhost/gis?application_name=load_1"
+        );
+        assert_eq!(
+            with_application_name("postgresql://localhost/gis?sslmode=require", "load_1"),
+            "postgresql://localhost/gis?sslmode=require&application_name=load_1"
+        );
+        assert_eq!(
+            with_application_name("host=localhost dbname=gis", "load_1"),
+            "host=localhost dbname=gis application_name=load_1"
+        );
+        // The caller's own name is kept
+        let named = "postgresql://localhost/gis?application_name=app";
+        assert_eq!(with_application_name(named, "load_1"), named);
+    }
+}
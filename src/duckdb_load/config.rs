@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use super::credentials::{SCHEMA_VARIABLE, URI_VARIABLE};
+use super::extensions::{
+    pin_extension, set_extension_directory, set_extension_repository, ExtensionPin,
+    EXTENSION_DIRECTORY_VARIABLE,
+};
+use super::{ExcelDateSystem, ProcessorOptions, WriteMethod};
+
+// Config file read when no path is given and DUCKDB_POSTGIS_CONFIG is unset, if it exists
+pub const DEFAULT_CONFIG_FILE: &str = "duckdb-postgis.toml";
+
+// Defaults for the CLI and daemon modes, read from a TOML file such as
+//
+//   uri = "postgresql://admin@localhost:5432/gridwalk"
+//   schema = "uploads"
+//   target_crs = "27700"
+//   write_method = "copy"
+//   extension_directory = "/opt/duckdb/extensions"
+//   extension_repository = "https://extensions.example.com/duckdb"
+//
+//   [duckdb_extensions.spatial]
+//   version = "v1.1.1"
+//   sha256 = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+//
+//   [extensions.csv]
+//   encoding = "latin1"
+//   assumed_crs = "27700"
+//
+// Environment variables override the file: DUCKDB_POSTGIS_URI, DUCKDB_POSTGIS_SCHEMA,
+// DUCKDB_POSTGIS_TARGET_CRS, DUCKDB_POSTGIS_WRITE_METHOD, DUCKDB_POSTGIS_EXTENSION_DIR and
+// DUCKDB_POSTGIS_EXTENSION_REPOSITORY. Command line arguments override both
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoadConfig {
+    pub uri: Option<String>,
+    pub schema: Option<String>,
+    // EPSG code geometry is transformed to
+    pub target_crs: Option<String>,
+    pub write_method: Option<WriteMethod>,
+    // Local directory DuckDB extensions are loaded from, see set_extension_directory
+    pub extension_directory: Option<String>,
+    // Repository DuckDB extensions are installed from, see set_extension_repository
+    pub extension_repository: Option<String>,
+    // Pinned versions and checksums of DuckDB extensions, keyed by extension name
+    pub duckdb_extensions: HashMap<String, ExtensionPin>,
+    // Options for files with a given extension, keyed by lowercase extension without the dot
+    pub extensions: HashMap<String, ExtensionConfig>,
+}
+
+// Options for one file extension, taking precedence over the top-level settings
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExtensionConfig {
+    // Named option profile, see register_profile
+    pub profile: Option<String>,
+    pub assumed_crs: Option<String>,
+    pub encoding: Option<String>,
+    pub target_crs: Option<String>,
+    pub write_method: Option<WriteMethod>,
+    pub csv_max_rejected_rows: Option<u64>,
+    // Type detection for delimited text files, as in CsvOptions
+    pub sample_size: Option<i64>,
+    pub all_varchar: Option<bool>,
+    pub dateformat: Option<String>,
+    pub decimal_separator: Option<char>,
+    // Rows of a spreadsheet above its table, and where its column names are, as in ExcelOptions
+    // has_header applies to delimited text files too
+    pub skip_rows: Option<usize>,
+    pub header_row: Option<usize>,
+    pub has_header: Option<bool>,
+    // Cells of a spreadsheet holding its table, as in ExcelOptions
+    pub range: Option<String>,
+    // Spreadsheet date columns and how their values are read, as in ExcelOptions
+    pub date_columns: Option<Vec<String>>,
+    pub date_system: Option<ExcelDateSystem>,
+    pub date_format: Option<String>,
+}
+
+impl LoadConfig {
+    // Read the config at path, or at DUCKDB_POSTGIS_CONFIG, or duckdb-postgis.toml in the
+    // working directory when that exists, then apply environment overrides
+    pub fn load(path: Option<&str>) -> Result<Self, io::Error> {
+        let path = path
+            .map(str::to_string)
+            .or_else(|| env::var("DUCKDB_POSTGIS_CONFIG").ok())
+            .or_else(|| {
+                Path::new(DEFAULT_CONFIG_FILE)
+                    .is_file()
+                    .then(|| DEFAULT_CONFIG_FILE.to_string())
+            });
+        let mut config = match path {
+            Some(path) => Self::from_file(&path)?,
+            None => Self::default(),
+        };
+        config.apply_env()?;
+        Ok(config)
+    }
+
+    // Read a config file, without environment overrides
+    pub fn from_file(path: &str) -> Result<Self, io::Error> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("Error reading '{}': {}", path, e)))?;
+        toml::from_str(&text).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Error in config file '{}': {}", path, e),
+            )
+        })
+    }
+
+    fn apply_env(&mut self) -> Result<(), io::Error> {
+        if let Ok(uri) = env::var(URI_VARIABLE) {
+            self.uri = Some(uri);
+        }
+        if let Ok(schema) = env::var(SCHEMA_VARIABLE) {
+            self.schema = Some(schema);
+        }
+        if let Ok(target_crs) = env::var("DUCKDB_POSTGIS_TARGET_CRS") {
+            self.target_crs = Some(target_crs);
+        }
+        if let Ok(extension_directory) = env::var(EXTENSION_DIRECTORY_VARIABLE) {
+            self.extension_directory = Some(extension_directory);
+        }
+        if let Ok(extension_repository) = env::var("DUCKDB_POSTGIS_EXTENSION_REPOSITORY") {
+            self.extension_repository = Some(extension_repository);
+        }
+        if let Ok(write_method) = env::var("DUCKDB_POSTGIS_WRITE_METHOD") {
+            self.write_method = Some(match write_method.to_lowercase().as_str() {
+                "attach" => WriteMethod::Attach,
+                "copy" => WriteMethod::Copy,
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!(
+                            "DUCKDB_POSTGIS_WRITE_METHOD must be attach or copy, not '{}'",
+                            write_method
+                        ),
+                    ))
+                }
+            });
+        }
+        Ok(())
+    }
+
+    // Use the extension directory, repository and pins for every load in the process
+    pub fn apply_extension_settings(&self) {
+        if let Some(directory) = &self.extension_directory {
+            set_extension_directory(Some(directory.into()));
+        }
+        if self.extension_repository.is_some() {
+            set_extension_repository(self.extension_repository.clone());
+        }
+        for (name, pin) in &self.duckdb_extensions {
+            pin_extension(name, pin.clone());
+        }
+    }
+
+    // URI to use when none is given on the command line - empty falls back to PG* variables
+    pub fn uri(&self) -> &str {
+        self.uri.as_deref().unwrap_or_default()
+    }
+
+    // Schema to use when none is given on the command line
+    pub fn schema(&self) -> &str {
+        self.schema.as_deref().unwrap_or("public")
+    }
+
+    // Options for loading file_path, from the top-level settings and its extension's section
+    pub fn options_for(&self, file_path: &str) -> ProcessorOptions {
+        let mut options = ProcessorOptions {
+            target_crs: self.target_crs.clone(),
+            write_method: self.write_method.unwrap_or_default(),
+            ..ProcessorOptions::default()
+        };
+        let extension = Path::new(file_path)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        let Some(section) = extension.and_then(|extension| self.extensions.get(&extension)) else {
+            return options;
+        };
+        options.profile = section.profile.clone();
+        options.assumed_crs = section.assumed_crs.clone();
+        options.encoding = section.encoding.clone();
+        if section.target_crs.is_some() {
+            options.target_crs = section.target_crs.clone();
+        }
+        if let Some(write_method) = section.write_method {
+            options.write_method = write_method;
+        }
+        options.csv_max_rejected_rows = section.csv_max_rejected_rows;
+        options.csv.sample_size = section.sample_size;
+        if let Some(all_varchar) = section.all_varchar {
+            options.csv.all_varchar = all_varchar;
+        }
+        options.csv.dateformat = section.dateformat.clone();
+        options.csv.decimal_separator = section.decimal_separator;
+        if let Some(skip_rows) = section.skip_rows {
+            options.excel.skip_rows = skip_rows;
+        }
+        if let Some(header_row) = section.header_row {
+            options.excel.header_row = header_row;
+        }
+        if let Some(has_header) = section.has_header {
+            options.excel.has_header = has_header;
+            options.csv.has_header = Some(has_header);
+        }
+        if section.range.is_some() {
+            options.excel.range = section.range.clone();
+        }
+        if let Some(date_columns) = &section.date_columns {
+            options.excel.date_columns = date_columns.clone();
+        }
+        if let Some(date_system) = section.date_system {
+            options.excel.date_system = date_system;
+        }
+        if section.date_format.is_some() {
+            options.excel.date_format = section.date_format.clone();
+        }
+        options
+    }
+}
@@ -0,0 +1,87 @@
+use postgres::{Client, NoTls};
+use std::error::Error;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use super::logging::{log_info, log_warn};
+use super::{sql, DuckDBFileProcessor};
+
+impl DuckDBFileProcessor {
+    // Export the table from DuckDB as CSV and stream it into Postgres with COPY FROM STDIN
+    pub(super) fn write_table_with_copy(
+        &self,
+        source_table: &str,
+        schema_qualified_table: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        // Let DuckDB create the empty target so column types match the attach writer
+        self.execute_postgis(
+            "create table",
+            &format!(
+                "CREATE TABLE {}.{} AS SELECT * FROM {} LIMIT 0;",
+                self.attach_alias, schema_qualified_table, source_table
+            ),
+        )?;
+
+        // The direct connection has no TLS and takes only the options the postgres crate
+        // knows, so servers it can't reach are written through the attach instead
+        let mut client = match Client::connect(&self.postgis_uri, NoTls) {
+            Ok(client) => client,
+            Err(e) => {
+                log_warn!(
+                    "COPY write method could not connect to Postgres directly, so rows are \
+                    written through the attach instead (TLS connections are not supported \
+                    for COPY): {}",
+                    self.redact(&e.to_string())
+                );
+                return self.execute_postgis(
+                    "write table",
+                    &format!(
+                        "INSERT INTO {}.{} SELECT * FROM {};",
+                        self.attach_alias, schema_qualified_table, source_table
+                    ),
+                );
+            }
+        };
+
+        // Unique per load, so loads running side by side never share an export file
+        let export_path = tempfile::Builder::new()
+            .prefix("duckdb_postgis_")
+            .suffix(".csv")
+            .tempfile()?
+            .into_temp_path();
+        self.copy_via_file(
+            &mut client,
+            source_table,
+            schema_qualified_table,
+            &export_path,
+        )
+    }
+
+    fn copy_via_file(
+        &self,
+        client: &mut Client,
+        source_table: &str,
+        schema_qualified_table: &str,
+        export_path: &Path,
+    ) -> Result<(), Box<dyn Error>> {
+        // \N marks NULL so empty strings survive the round trip
+        self.execute_profiled(
+            "export rows",
+            &format!(
+                "COPY {} TO {} (FORMAT csv, HEADER false, NULLSTR '\\N');",
+                source_table,
+                sql::string_literal(&export_path.to_string_lossy())
+            ),
+        )?;
+
+        let mut writer = client.copy_in(&format!(
+            "COPY {} FROM STDIN (FORMAT csv, NULL '\\N');",
+            schema_qualified_table
+        ))?;
+        io::copy(&mut File::open(export_path)?, &mut writer)?;
+        let rows = writer.finish()?;
+        log_info!("Copied {} rows into {}", rows, schema_qualified_table);
+        Ok(())
+    }
+}
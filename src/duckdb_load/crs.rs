@@ -0,0 +1,347 @@
+use std::error::Error;
+
+use super::{extensions, sql, DuckDBFileProcessor};
+
+impl DuckDBFileProcessor {
+    // CRS of a GeoPackage layer from the file's own gpkg_spatial_ref_sys table
+    // st_read_meta can fail to resolve custom SRS definitions held there
+    pub(super) fn geopackage_crs(&self) -> Result<Option<String>, Box<dyn Error>> {
+        extensions::load_extension(&self.conn, "sqlite")?;
+        // Layer st_read loads, or the first geometry table when GDAL can't read the metadata
+        let layer_filter = match self.get_layer_name() {
+            Ok(layer) => format!("WHERE columns.table_name = {}", sql::string_literal(&layer)),
+            Err(_) => String::new(),
+        };
+        let query = format!(
+            "SELECT srs.organization, srs.organization_coordsys_id, srs.definition
+            FROM sqlite_scan({file}, 'gpkg_geometry_columns') AS columns
+            JOIN sqlite_scan({file}, 'gpkg_spatial_ref_sys') AS srs ON columns.srs_id = srs.srs_id
+            {filter}
+            LIMIT 1;",
+            file = sql::string_literal(&self.file_path),
+            filter = layer_filter,
+        );
+        let mut stmt = self.conn.prepare(&query)?;
+        let mut rows = stmt.query([])?;
+        let Some(row) = rows.next()? else {
+            return Ok(None);
+        };
+        let organization: Option<String> = row.get(0)?;
+        let code: Option<i64> = row.get(1)?;
+        let definition: Option<String> = row.get(2)?;
+        match (organization, code) {
+            (Some(organization), Some(code))
+                if organization.eq_ignore_ascii_case("EPSG") && code > 0 =>
+            {
+                Ok(Some(code.to_string()))
+            }
+            // Custom SRS - the WKT definition may still name an EPSG CRS
+            _ => Ok(definition.as_deref().and_then(epsg_from_wkt)),
+        }
+    }
+}
+
+// Resolve the EPSG code of a WKT CRS, such as a shapefile .prj
+// OGC WKT1 and WKT2 carry an AUTHORITY or ID, ESRI WKT only names the CRS
+pub(super) fn epsg_from_wkt(wkt: &str) -> Option<String> {
+    crs_code(&parse_wkt(wkt)?)
+}
+
+// Horizontal and vertical parts of a compound CRS, e.g. EPSG:27700 + ODN height (EPSG:5701)
+pub(super) struct CompoundCrs {
+    pub horizontal: Option<String>,
+    // EPSG code of the vertical CRS, or its name when it has none
+    pub vertical: String,
+}
+
+pub(super) fn compound_crs(wkt: &str) -> Option<CompoundCrs> {
+    let crs = parse_wkt(wkt)?;
+    if crs.keyword != "COMPD_CS" && crs.keyword != "COMPOUNDCRS" {
+        return None;
+    }
+    let horizontal = crs
+        .nodes()
+        .find(|node| HORIZONTAL_KEYWORDS.contains(&node.keyword.as_str()))
+        .and_then(crs_code);
+    let vertical = crs
+        .nodes()
+        .find(|node| node.keyword == "VERT_CS" || node.keyword == "VERTCRS")?;
+    let vertical = vertical
+        .authority_code()
+        .or_else(|| vertical.name().map(str::to_string))?;
+    Some(CompoundCrs {
+        horizontal,
+        vertical,
+    })
+}
+
+const HORIZONTAL_KEYWORDS: [&str; 6] = [
+    "PROJCS",
+    "GEOGCS",
+    "PROJCRS",
+    "GEOGCRS",
+    "GEODCRS",
+    "BASEGEOGCRS",
+];
+
+fn parse_wkt(wkt: &str) -> Option<WktNode> {
+    let mut tokens = tokenize(wkt.trim_start_matches('\u{feff}'))
+        .into_iter()
+        .peekable();
+    let crs = parse_node(&mut tokens)?;
+    // A bound CRS wraps the source CRS with a transformation to WGS84
+    if crs.keyword == "BOUNDCRS" {
+        return crs.child("SOURCECRS")?.nodes().next().cloned();
+    }
+    Some(crs)
+}
+
+fn crs_code(crs: &WktNode) -> Option<String> {
+    if let Some(code) = crs.authority_code() {
+        return Some(code);
+    }
+    esri_name_code(crs.name()?).map(|code| code.to_string())
+}
+
+#[derive(Debug, Clone)]
+struct WktNode {
+    keyword: String,
+    values: Vec<WktValue>,
+}
+
+#[derive(Debug, Clone)]
+enum WktValue {
+    Text(String),
+    Number(String),
+    Node(WktNode),
+}
+
+impl WktNode {
+    fn name(&self) -> Option<&str> {
+        match self.values.first() {
+            Some(WktValue::Text(name)) => Some(name),
+            _ => None,
+        }
+    }
+
+    fn nodes(&self) -> impl Iterator<Item = &WktNode> {
+        self.values.iter().filter_map(|value| match value {
+            WktValue::Node(node) => Some(node),
+            _ => None,
+        })
+    }
+
+    fn child(&self, keyword: &str) -> Option<&WktNode> {
+        self.nodes().find(|node| node.keyword == keyword)
+    }
+
+    // AUTHORITY["EPSG","27700"] in WKT1, ID["EPSG",27700] in WKT2 - only the CRS's own, not its parts'
+    fn authority_code(&self) -> Option<String> {
+        let authority = self.child("AUTHORITY").or_else(|| self.child("ID"))?;
+        match authority.values.as_slice() {
+            [WktValue::Text(name), WktValue::Text(code) | WktValue::Number(code), ..]
+                if name.eq_ignore_ascii_case("EPSG") =>
+            {
+                Some(code.clone())
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Text(String),
+    Open,
+    Close,
+    Comma,
+}
+
+fn tokenize(wkt: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = wkt.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '[' | '(' => tokens.push(Token::Open),
+            ']' | ')' => tokens.push(Token::Close),
+            ',' => tokens.push(Token::Comma),
+            '"' => {
+                let mut text = String::new();
+                while let Some(c) = chars.next() {
+                    // A doubled quote is an escaped quote
+                    if c == '"' {
+                        if chars.peek() == Some(&'"') {
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                    text.push(c);
+                }
+                tokens.push(Token::Text(text));
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut word = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if "[](),\"".contains(next) || next.is_whitespace() {
+                        break;
+                    }
+                    word.push(next);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    tokens
+}
+
+type Tokens = std::iter::Peekable<std::vec::IntoIter<Token>>;
+
+fn parse_node(tokens: &mut Tokens) -> Option<WktNode> {
+    match tokens.next()? {
+        Token::Word(keyword) => parse_node_body(keyword, tokens),
+        _ => None,
+    }
+}
+
+fn parse_node_body(keyword: String, tokens: &mut Tokens) -> Option<WktNode> {
+    if tokens.next()? != Token::Open {
+        return None;
+    }
+    let mut node = WktNode {
+        keyword: keyword.to_uppercase(),
+        values: Vec::new(),
+    };
+    loop {
+        match tokens.next()? {
+            Token::Close => return Some(node),
+            Token::Comma => {}
+            Token::Text(text) => node.values.push(WktValue::Text(text)),
+            // A word followed by a bracket is a nested node, otherwise a number or enum
+            Token::Word(word) if tokens.peek() == Some(&Token::Open) => {
+                let child = parse_node_body(word, tokens)?;
+                node.values.push(WktValue::Node(child));
+            }
+            Token::Word(word) => node.values.push(WktValue::Number(word)),
+            Token::Open => return None,
+        }
+    }
+}
+
+// EPSG codes for the CRS names ESRI writes into .prj files without an authority
+fn esri_name_code(name: &str) -> Option<u32> {
+    let code = match name {
+        "GCS_WGS_1984" => 4326,
+        "GCS_North_American_1983" => 4269,
+        "GCS_North_American_1927" => 4267,
+        "GCS_ETRS_1989" => 4258,
+        "GCS_OSGB_1936" => 4277,
+        "GCS_GDA_1994" => 4283,
+        "GCS_GDA2020" => 7844,
+        "British_National_Grid" | "OSGB_1936_British_National_Grid" => 27700,
+        "WGS_1984_Web_Mercator_Auxiliary_Sphere" | "WGS_1984_Web_Mercator" => 3857,
+        "IRENET95_Irish_Transverse_Mercator" | "Irish_Transverse_Mercator" => 2157,
+        "TM65_Irish_Grid" => 29902,
+        "RD_New" | "Amersfoort_RD_New" => 28992,
+        "ETRS_1989_LAEA" => 3035,
+        "ETRS_1989_LCC" => 3034,
+        "RGF_1993_Lambert_93" => 2154,
+        "NAD_1983_Contiguous_USA_Albers"
+        | "USA_Contiguous_Albers_Equal_Area_Conic_USGS_version" => 5070,
+        _ => return zoned_code(name),
+    };
+    Some(code)
+}
+
+// UTM and MGA zone names, e.g. WGS_1984_UTM_Zone_33N
+fn zoned_code(name: &str) -> Option<u32> {
+    let zoned = [
+        ("WGS_1984_UTM_Zone_", 'N', 32600, 1..=60),
+        ("WGS_1984_UTM_Zone_", 'S', 32700, 1..=60),
+        ("NAD_1983_UTM_Zone_", 'N', 26900, 1..=23),
+        ("NAD_1927_UTM_Zone_", 'N', 26700, 1..=22),
+        ("ETRS_1989_UTM_Zone_", 'N', 25800, 28..=38),
+        ("GDA_1994_MGA_Zone_", ' ', 28300, 48..=58),
+        ("GDA2020_MGA_Zone_", ' ', 7800, 46..=59),
+    ];
+    zoned
+        .into_iter()
+        .find_map(|(prefix, hemisphere, base, zones)| {
+            let zone = name.strip_prefix(prefix)?;
+            let zone = match hemisphere {
+                ' ' => zone,
+                hemisphere => zone.strip_suffix(hemisphere)?,
+            };
+            let zone = zone.parse::<u32>().ok()?;
+            zones.contains(&zone).then_some(base + zone)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_wkt1_authority_codes() {
+        let wkt = r#"PROJCS["OSGB 1936 / British National Grid",
+            GEOGCS["OSGB 1936",DATUM["OSGB_1936",AUTHORITY["EPSG","6277"]],
+                AUTHORITY["EPSG","4277"]],
+            PROJECTION["Transverse_Mercator"],
+            AUTHORITY["EPSG","27700"]]"#;
+        assert_eq!(epsg_from_wkt(wkt), Some("27700".to_string()));
+    }
+
+    #[test]
+    fn reads_wkt2_ids_and_bound_crs() {
+        let wkt = r#"GEOGCRS["WGS 84",DATUM["World Geodetic System 1984"],ID["EPSG",4326]]"#;
+        assert_eq!(epsg_from_wkt(wkt), Some("4326".to_string()));
+        let bound = r#"BOUNDCRS[SOURCECRS[PROJCRS["RD New",ID["EPSG",28992]]],
+            TARGETCRS[GEOGCRS["WGS 84",ID["EPSG",4326]]]]"#;
+        assert_eq!(epsg_from_wkt(bound), Some("28992".to_string()));
+    }
+
+    #[test]
+    fn resolves_esri_names_without_an_authority() {
+        let wkt = "\u{feff}PROJCS[\"British_National_Grid\",GEOGCS[\"GCS_OSGB_1936\"]]";
+        assert_eq!(epsg_from_wkt(wkt), Some("27700".to_string()));
+        assert_eq!(
+            epsg_from_wkt(r#"PROJCS["WGS_1984_UTM_Zone_33S",GEOGCS["GCS_WGS_1984"]]"#),
+            Some("32733".to_string())
+        );
+        assert_eq!(zoned_code("GDA2020_MGA_Zone_56"), Some(7856));
+        assert_eq!(zoned_code("WGS_1984_UTM_Zone_61N"), None);
+        assert_eq!(epsg_from_wkt(r#"PROJCS["Local grid"]"#), None);
+    }
+
+    #[test]
+    fn splits_compound_crs() {
+        let wkt = r#"COMPD_CS["OSGB 1936 / British National Grid + ODN height",
+            PROJCS["OSGB 1936 / British National Grid",AUTHORITY["EPSG","27700"]],
+            VERT_CS["ODN height",VERT_DATUM["Ordnance Datum Newlyn",2005],
+                AUTHORITY["EPSG","5701"]]]"#;
+        let compound = compound_crs(wkt).unwrap();
+        assert_eq!(compound.horizontal, Some("27700".to_string()));
+        assert_eq!(compound.vertical, "5701");
+        assert!(compound_crs(r#"GEOGCS["WGS 84",AUTHORITY["EPSG","4326"]]"#).is_none());
+    }
+
+    #[test]
+    fn tokenizes_doubled_quotes_and_rejects_malformed_wkt() {
+        assert_eq!(
+            tokenize(r#"A["say ""hi""",1]"#),
+            vec![
+                Token::Word("A".to_string()),
+                Token::Open,
+                Token::Text("say \"hi\"".to_string()),
+                Token::Comma,
+                Token::Word("1".to_string()),
+                Token::Close
+            ]
+        );
+        assert!(parse_wkt("PROJCS[\"unclosed\"").is_none());
+        assert!(parse_wkt("not wkt").is_none());
+    }
+}
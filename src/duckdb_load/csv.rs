@@ -0,0 +1,215 @@
+use std::error::Error;
+
+use super::logging::{log_debug, log_warn};
+use super::{sql, DuckDBFileProcessor, FileType};
+
+// Rows read to judge whether the first row of a CSV is a header
+const HEADER_SAMPLE_ROWS: usize = 20;
+// Values a column needs below the first row to count as a column of numbers
+const MIN_NUMBER_VALUES: usize = 3;
+// In DuckDB's error when a value doesn't fit the type detected from the sample
+const DETECTED_TYPE_ERROR: &str = "This type was auto-detected from the CSV file";
+
+impl DuckDBFileProcessor {
+    pub(super) fn csv_select(&self, all_varchar: bool) -> Result<String, Box<dyn Error>> {
+        let mut options = self.csv_delimiter_option()?;
+        options.push_str(&self.csv_header_option()?);
+        options.push_str(self.csv_rejects_option());
+        let csv = &self.options.csv;
+        if let Some(sample_size) = csv.sample_size {
+            options.push_str(&format!(", sample_size = {}", sample_size));
+        }
+        if all_varchar {
+            options.push_str(", all_varchar = true");
+        }
+        if let Some(dateformat) = &csv.dateformat {
+            options.push_str(&format!(
+                ", dateformat = {}",
+                sql::string_literal(dateformat)
+            ));
+        }
+        if let Some(separator) = csv.decimal_separator {
+            options.push_str(&format!(", decimal_separator = '{}'", separator));
+        }
+        if !self.options.column_types.is_empty() {
+            // Apply overrides while reading so inference can't drop leading zeros
+            options.push_str(&format!(", types = {}", self.column_types_struct()));
+        }
+        if let Some(encoding) = &self.options.encoding {
+            options.push_str(&format!(", encoding = '{}'", encoding));
+        }
+        Ok(format!(
+            "SELECT * FROM read_csv({}{})",
+            sql::string_literal(&self.file_path),
+            options
+        ))
+    }
+
+    // Whether a failed read is a CSV with a value further in than the sample that doesn't fit
+    // the type detected for its column
+    pub(super) fn csv_types_failed(&self, error: &duckdb::Error) -> bool {
+        self.file_type == FileType::Csv
+            && !self.options.csv.all_varchar
+            && error.to_string().contains(DETECTED_TYPE_ERROR)
+    }
+
+    // Read the CSV again with every column as text, after detected types failed part way
+    // through it. column_types overrides still apply
+    pub(super) fn read_csv_as_text(&self, error: &duckdb::Error) -> Result<(), Box<dyn Error>> {
+        let message = error.to_string();
+        log_warn!(
+            "CSV types detected from a sample don't fit the whole file, so every column is \
+            read as text - set sample_size = -1 or column_types to keep types: {}",
+            message.lines().take(3).collect::<Vec<_>>().join(" ")
+        );
+        // The first read's duplicate column renames are found again
+        self.report.borrow_mut().duplicate_column_renames.clear();
+        let select = self.resolve_duplicate_columns(self.csv_select(true)?)?;
+        self.execute_profiled("read source", &format!("CREATE TABLE data AS {};", select))?;
+        self.report.borrow_mut().csv_all_varchar = true;
+        Ok(())
+    }
+
+    // read_csv options naming the columns column_0, column_1, ... when the file has no header
+    pub(super) fn csv_header_option(&self) -> Result<String, Box<dyn Error>> {
+        if self.csv_has_header()? {
+            return Ok(String::new());
+        }
+        let columns: i64 = self.conn.query_row(
+            &format!(
+                "SELECT len(Columns) FROM sniff_csv({}, header = false{});",
+                sql::string_literal(&self.file_path),
+                self.csv_sniff_options()?
+            ),
+            [],
+            |row| row.get(0),
+        )?;
+        let names = (0..columns)
+            .map(|index| format!("'column_{}'", index))
+            .collect::<Vec<_>>();
+        Ok(format!(", header = false, names = [{}]", names.join(", ")))
+    }
+
+    // Whether the first row of a CSV holds column names - as set in the options, or as DuckDB
+    // detects. DuckDB tells a header by its types, so it takes the first row of an all-text
+    // file as one, and is overruled when that row looks like the rows under it
+    pub(super) fn csv_has_header(&self) -> Result<bool, Box<dyn Error>> {
+        if let Some(has_header) = self.options.csv.has_header {
+            return Ok(has_header);
+        }
+        let options = self.csv_sniff_options()?;
+        let sniffed: bool = self.conn.query_row(
+            &format!(
+                "SELECT HasHeader FROM sniff_csv({}{});",
+                sql::string_literal(&self.file_path),
+                options
+            ),
+            [],
+            |row| row.get(0),
+        )?;
+        if !sniffed {
+            return Ok(false);
+        }
+
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT * FROM read_csv({}, header = false, all_varchar = true{}) LIMIT {};",
+            sql::string_literal(&self.file_path),
+            options,
+            HEADER_SAMPLE_ROWS
+        ))?;
+        let columns = stmt.column_count();
+        let rows = stmt
+            .query_map([], |row| {
+                (0..columns)
+                    .map(|index| row.get::<_, Option<String>>(index))
+                    .collect::<Result<Vec<_>, _>>()
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        let votes = header_votes(&rows);
+        log_debug!("CSV header detected, with {} votes from its values", votes);
+        Ok(votes >= 0)
+    }
+
+    // Options sniff_csv and read_csv need to split the file as the load does
+    pub(super) fn csv_sniff_options(&self) -> Result<String, Box<dyn Error>> {
+        let mut options = self.csv_delimiter_option()?;
+        if let Some(encoding) = &self.options.encoding {
+            options.push_str(&format!(", encoding = '{}'", encoding));
+        }
+        Ok(options)
+    }
+}
+
+// Votes for the first row being a header, one from each column. A first value repeated
+// below it is data and votes against, and in a column of numbers a first value that is a
+// number votes against while one that isn't votes for
+fn header_votes(rows: &[Vec<Option<String>>]) -> i32 {
+    let Some((first, rest)) = rows.split_first() else {
+        return 0;
+    };
+    let mut votes = 0;
+    for (index, header) in first.iter().enumerate() {
+        let Some(header) = header.as_deref().map(str::trim).filter(|h| !h.is_empty()) else {
+            continue;
+        };
+        let values = rest
+            .iter()
+            .filter_map(|row| row.get(index)?.as_deref().map(str::trim))
+            .filter(|value| !value.is_empty())
+            .collect::<Vec<_>>();
+        if values.contains(&header) {
+            votes -= 1;
+        } else if values.len() >= MIN_NUMBER_VALUES && values.iter().all(|v| is_number(v)) {
+            votes += if is_number(header) { -1 } else { 1 };
+        }
+    }
+    votes
+}
+
+fn is_number(value: &str) -> bool {
+    value.chars().any(|c| c.is_ascii_digit()) && value.parse::<f64>().is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(rows: &[&[&str]]) -> Vec<Vec<Option<String>>> {
+        rows.iter()
+            .map(|row| {
+                row.iter()
+                    .map(|value| Some(value.to_string()).filter(|v| !v.is_empty()))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn names_over_numbers_vote_for_a_header() {
+        let sample = rows(&[
+            &["easting", "northing"],
+            &["530000", "180000"],
+            &["530100", "180100"],
+            &["530200", "180200"],
+        ]);
+        assert_eq!(header_votes(&sample), 2);
+    }
+
+    #[test]
+    fn numbers_and_repeated_values_vote_against() {
+        let sample = rows(&[
+            &["1", "open"],
+            &["2", "open"],
+            &["3", "closed"],
+            &["4", "open"],
+        ]);
+        assert_eq!(header_votes(&sample), -2);
+    }
+
+    #[test]
+    fn text_columns_and_blanks_abstain() {
+        let sample = rows(&[&["name", ""], &["Leeds", "x"], &["York", "y"]]);
+        assert_eq!(header_votes(&sample), 0);
+        assert_eq!(header_votes(&[]), 0);
+    }
+}
@@ -0,0 +1,78 @@
+use std::error::Error;
+
+use super::logging::{log_debug, log_warn};
+use super::{DuckDBFileProcessor, FileType};
+
+// Rejected rows listed in the load report
+const SAMPLE_ROWS: usize = 5;
+
+impl DuckDBFileProcessor {
+    // read_csv option storing malformed rows in reject_errors instead of failing the read
+    pub(super) fn csv_rejects_option(&self) -> &'static str {
+        match self.options.csv_max_rejected_rows {
+            Some(_) => ", store_rejects = true",
+            None => "",
+        }
+    }
+
+    // Report the rows read_csv skipped, failing when there are more than allowed
+    pub(super) fn check_csv_rejects(&self) -> Result<(), Box<dyn Error>> {
+        let Some(max_rejected_rows) = self.options.csv_max_rejected_rows else {
+            return Ok(());
+        };
+        if self.file_type != FileType::Csv {
+            return Ok(());
+        }
+
+        // A row can have several errors, so group them by line
+        let mut stmt = self.conn.prepare(
+            "SELECT line, first(csv_line ORDER BY column_idx),
+                string_agg(error_message, '; ' ORDER BY column_idx)
+            FROM (SELECT DISTINCT line, column_idx, csv_line, error_message FROM reject_errors)
+            GROUP BY line ORDER BY line;",
+        )?;
+        let rejects = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        if rejects.is_empty() {
+            return Ok(());
+        }
+
+        let samples = rejects
+            .iter()
+            .take(SAMPLE_ROWS)
+            .map(|(line, csv_line, message)| {
+                format!(
+                    "line {}: {} ({})",
+                    line,
+                    csv_line.as_deref().unwrap_or_default(),
+                    message
+                )
+            })
+            .collect::<Vec<_>>();
+        for sample in &samples {
+            log_debug!("Rejected CSV row {}", sample);
+        }
+        let rejected_rows = rejects.len() as u64;
+        {
+            let mut report = self.report.borrow_mut();
+            report.rejected_rows = rejected_rows;
+            report.rejected_row_samples = samples;
+        }
+        if rejected_rows > max_rejected_rows {
+            return Err(format!(
+                "{} malformed CSV rows exceeds the limit of {}",
+                rejected_rows, max_rejected_rows
+            )
+            .into());
+        }
+        log_warn!("Skipped {} malformed CSV rows", rejected_rows);
+        Ok(())
+    }
+}
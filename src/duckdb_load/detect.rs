@@ -0,0 +1,83 @@
+use std::error::Error;
+use std::io;
+use std::path::Path;
+use std::sync::{OnceLock, PoisonError, RwLock};
+
+use super::{sql, DuckDBFileProcessor, FileType};
+
+// Detector for a format the built-in detection doesn't know, registered with
+// register_file_detector. Files it matches are reported as FileType::Custom(name)
+#[derive(Debug, Clone)]
+pub struct FileDetector {
+    pub name: String,
+    // Whether a file is in this format, given its path and first bytes (up to 100)
+    pub matches: fn(&Path, &[u8]) -> bool,
+    // DuckDB query reading the file, with {path} replaced by the quoted file path,
+    // e.g. "SELECT * FROM st_read({path}, open_options = ['FLATTEN_NESTED_ATTRIBUTES=YES'])"
+    pub read_query: String,
+}
+
+fn registry() -> &'static RwLock<Vec<FileDetector>> {
+    static DETECTORS: OnceLock<RwLock<Vec<FileDetector>>> = OnceLock::new();
+    DETECTORS.get_or_init(|| RwLock::new(Vec::new()))
+}
+
+// Register a detector, replacing any existing detector with the same name. Registered detectors
+// are tried in order before the built-in detection
+pub fn register_file_detector(detector: FileDetector) {
+    let mut detectors = registry().write().unwrap_or_else(PoisonError::into_inner);
+    detectors.retain(|existing| existing.name != detector.name);
+    detectors.push(detector);
+}
+
+pub(super) fn custom_detector(name: &str) -> Option<FileDetector> {
+    registry()
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .find(|detector| detector.name == name)
+        .cloned()
+}
+
+pub(super) fn detect_custom_type(path: &Path, header: &[u8]) -> Option<FileType> {
+    registry()
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .find(|detector| (detector.matches)(path, header))
+        .map(|detector| FileType::Custom(detector.name.clone()))
+}
+
+// Detect the type of a local file from its content, as the loader does
+pub fn determine_file_type(file_path: &str) -> Result<FileType, io::Error> {
+    DuckDBFileProcessor::determine_file_type(file_path)
+        .map_err(|e| io::Error::other(format!("Error detecting type of '{}': {}", file_path, e)))
+}
+
+// Detect the type of an in-memory file, such as an upload, from its content. Registered
+// detectors are given an empty path
+pub fn determine_file_type_from_bytes(bytes: &[u8]) -> Result<FileType, io::Error> {
+    DuckDBFileProcessor::file_type_from_bytes(bytes)
+        .map_err(|e| io::Error::other(format!("Error detecting file type: {}", e)))
+}
+
+impl DuckDBFileProcessor {
+    pub(super) fn file_type_from_bytes(bytes: &[u8]) -> Result<FileType, Box<dyn Error>> {
+        let header = &bytes[..bytes.len().min(100)];
+        if let Some(file_type) = detect_custom_type(Path::new(""), header) {
+            return Ok(file_type);
+        }
+        if let Some(file_type) = Self::match_magic_numbers(header) {
+            return Ok(file_type);
+        }
+        Self::detect_content_based_type(bytes)
+    }
+
+    pub(super) fn custom_select(&self, name: &str) -> Result<String, Box<dyn Error>> {
+        let detector = custom_detector(name)
+            .ok_or_else(|| format!("No file detector registered for {}", name))?;
+        Ok(detector
+            .read_query
+            .replace("{path}", &sql::string_literal(&self.file_path)))
+    }
+}
@@ -0,0 +1,109 @@
+use std::collections::HashSet;
+use std::error::Error;
+
+use super::logging::log_debug;
+use super::{naming, sql, DuckDBFileProcessor, DuplicateColumns, FileType};
+
+impl DuckDBFileProcessor {
+    // Resolve duplicate source column names before DuckDB silently renames them
+    pub(super) fn resolve_duplicate_columns(
+        &self,
+        select: String,
+    ) -> Result<String, Box<dyn Error>> {
+        let columns = self.source_column_names(&select)?;
+        let (renamed, renames) = deduplicate_names(&columns);
+        if renames.is_empty() {
+            return Ok(select);
+        }
+
+        if self.options.duplicate_columns == DuplicateColumns::Fail {
+            let duplicates = renames
+                .iter()
+                .map(|(original, _)| original.as_str())
+                .collect::<Vec<_>>();
+            return Err(format!("Source has duplicate column names: {:?}", duplicates).into());
+        }
+
+        for (original, new_name) in &renames {
+            log_debug!("Duplicate column {} renamed to {}", original, new_name);
+        }
+        self.report
+            .borrow_mut()
+            .duplicate_column_renames
+            .extend(renames);
+
+        // Alias the columns by position so the duplicates never meet in a table
+        let aliases = renamed
+            .iter()
+            .map(|name| naming::quote_identifier(name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        Ok(format!("SELECT * FROM ({}) AS source({})", select, aliases))
+    }
+
+    fn source_column_names(&self, select: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut stmt = self.conn.prepare(&format!("DESCRIBE {}", select))?;
+        let columns = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        if self.file_type != FileType::Csv {
+            return Ok(columns);
+        }
+
+        // read_csv has already made the header unique, so read the raw header row instead
+        if !self.csv_has_header()? {
+            return Ok(columns);
+        }
+        let options = self.csv_sniff_options()?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT * FROM read_csv({}, header = false, all_varchar = true{}) LIMIT 1;",
+            sql::string_literal(&self.file_path),
+            options
+        ))?;
+        let mut rows = stmt.query([])?;
+        let Some(row) = rows.next()? else {
+            return Ok(columns);
+        };
+        let header = columns
+            .into_iter()
+            .enumerate()
+            .map(
+                |(index, generated)| match row.get::<_, Option<String>>(index) {
+                    Ok(Some(name)) => name,
+                    // Blank or missing header cells keep the name read_csv generated
+                    _ => generated,
+                },
+            )
+            .collect();
+        Ok(header)
+    }
+}
+
+// Suffix repeated names with _2, _3, ... skipping any suffix already in use
+fn deduplicate_names(columns: &[String]) -> (Vec<String>, Vec<(String, String)>) {
+    let mut taken = columns
+        .iter()
+        .map(|c| c.to_lowercase())
+        .collect::<HashSet<_>>();
+    let mut seen = HashSet::new();
+    let mut renamed = Vec::with_capacity(columns.len());
+    let mut renames = Vec::new();
+    for column in columns {
+        if seen.insert(column.to_lowercase()) {
+            renamed.push(column.clone());
+            continue;
+        }
+        let mut suffix = 2;
+        let new_name = loop {
+            let candidate = format!("{}_{}", column, suffix);
+            if taken.insert(candidate.to_lowercase()) {
+                break candidate;
+            }
+            suffix += 1;
+        };
+        seen.insert(new_name.to_lowercase());
+        renames.push((column.clone(), new_name.clone()));
+        renamed.push(new_name);
+    }
+    (renamed, renames)
+}
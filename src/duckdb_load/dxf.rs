@@ -0,0 +1,45 @@
+use std::error::Error;
+
+use super::{DuckDBFileProcessor, DxfGeometry};
+
+impl DuckDBFileProcessor {
+    // DXF entities come back as one mixed layer, so filter by CAD layer and geometry family
+    pub(super) fn dxf_select(&self) -> Result<String, Box<dyn Error>> {
+        let dxf = &self.options.dxf;
+        let mut filters = vec![
+            "geom IS NOT NULL".to_string(),
+            "NOT ST_IsEmpty(geom)".to_string(),
+        ];
+        if !dxf.layers.is_empty() {
+            let layers = dxf
+                .layers
+                .iter()
+                .map(|layer| format!("'{}'", layer.replace('\'', "''")))
+                .collect::<Vec<_>>()
+                .join(", ");
+            filters.push(format!("\"Layer\" IN ({})", layers));
+        }
+        let geometry_types = match dxf.geometry {
+            DxfGeometry::Mixed => None,
+            DxfGeometry::Points => Some("'POINT', 'MULTIPOINT'"),
+            DxfGeometry::Lines => Some("'LINESTRING', 'MULTILINESTRING'"),
+            DxfGeometry::Polygons => Some("'POLYGON', 'MULTIPOLYGON'"),
+        };
+        if let Some(geometry_types) = geometry_types {
+            filters.push(format!(
+                "CAST(ST_GeometryType(geom) AS VARCHAR) IN ({})",
+                geometry_types
+            ));
+        }
+
+        // Drawings are often 3D with every Z at 0, and a column of geometry types keeps
+        // mixed layers easy to split in PostGIS
+        Ok(format!(
+            "SELECT * REPLACE (ST_Force2D(geom) AS geom),
+                CAST(ST_GeometryType(geom) AS VARCHAR) AS geometry_type
+            FROM {} WHERE {}",
+            self.st_read_source(),
+            filters.join(" AND ")
+        ))
+    }
+}
@@ -0,0 +1,115 @@
+use std::error::Error;
+
+use super::logging::log_info;
+use super::{naming, DuckDBFileProcessor, EmptyGeometry};
+
+// DuckDB table holding rows routed away by EmptyGeometry::SideTable
+const SIDE_TABLE: &str = "empty_geometry_rows";
+
+impl DuckDBFileProcessor {
+    // Count rows with NULL or EMPTY geometry and keep, drop or set them aside per the policy
+    pub(super) fn apply_empty_geometry_policy(&self) -> Result<(), Box<dyn Error>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT column_name FROM information_schema.columns
+            WHERE table_name = 'data' AND data_type = 'GEOMETRY'",
+        )?;
+        let geom_columns = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        if geom_columns.is_empty() {
+            return Ok(());
+        }
+
+        let any_null = geom_columns
+            .iter()
+            .map(|column| format!("{} IS NULL", naming::quote_identifier(column)))
+            .collect::<Vec<_>>()
+            .join(" OR ");
+        let any_empty = geom_columns
+            .iter()
+            .map(|column| format!("ST_IsEmpty({})", naming::quote_identifier(column)))
+            .collect::<Vec<_>>()
+            .join(" OR ");
+        // NULL wins, so a row is only counted once
+        let (null_count, empty_count): (i64, i64) = self.conn.query_row(
+            &format!(
+                "SELECT count(*) FILTER (WHERE {null}),
+                    count(*) FILTER (WHERE NOT ({null}) AND ({empty}))
+                FROM data;",
+                null = any_null,
+                empty = any_empty
+            ),
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        {
+            let mut report = self.report.borrow_mut();
+            report.null_geometries = null_count as u64;
+            report.empty_geometries = empty_count as u64;
+        }
+        if null_count + empty_count == 0 {
+            return Ok(());
+        }
+        log_info!(
+            "{} rows with NULL geometry and {} with EMPTY geometry",
+            null_count,
+            empty_count
+        );
+
+        let condition = format!("({}) OR ({})", any_null, any_empty);
+        match self.options.empty_geometry {
+            EmptyGeometry::Keep => return Ok(()),
+            EmptyGeometry::Drop => {}
+            // Geometry is kept as WKT text, so EMPTY values survive the trip to Postgres
+            EmptyGeometry::SideTable => {
+                let replace = geom_columns
+                    .iter()
+                    .map(|column| {
+                        let column = naming::quote_identifier(column);
+                        format!("ST_AsText({0}) AS {0}", column)
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                self.conn.execute(
+                    &format!(
+                        "CREATE TABLE {} AS SELECT * REPLACE ({}) FROM data WHERE {};",
+                        SIDE_TABLE, replace, condition
+                    ),
+                    [],
+                )?;
+            }
+        }
+        self.conn
+            .execute(&format!("DELETE FROM data WHERE {};", condition), [])?;
+        log_info!(
+            "Removed {} rows without geometry from the load",
+            null_count + empty_count
+        );
+        Ok(())
+    }
+
+    // Write the rows set aside by EmptyGeometry::SideTable to <table>_empty_geometry
+    pub(super) fn write_empty_geometry_table(&self) -> Result<(), Box<dyn Error>> {
+        if self.options.empty_geometry != EmptyGeometry::SideTable
+            || !self
+                .get_column_names(SIDE_TABLE)
+                .is_ok_and(|columns| !columns.is_empty())
+        {
+            return Ok(());
+        }
+        let side_table = naming::qualified_name(
+            &self.schema_name,
+            &naming::derived_name(&self.table_name, "_empty_geometry"),
+        );
+        self.postgres_execute(
+            "drop empty geometry table",
+            &format!("DROP TABLE IF EXISTS {};", side_table),
+        )?;
+        // Writing sets rows_loaded, which should keep counting the main table
+        let rows_loaded = self.report.borrow().rows_loaded;
+        self.write_table_to_postgis(SIDE_TABLE, &side_table)?;
+        self.report.borrow_mut().rows_loaded = rows_loaded;
+        log_info!("Rows without geometry written to {}", side_table);
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+use duckdb::Connection;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, Read};
+
+use super::postgres_source::PostgresSource;
+use super::remote::RemoteSource;
+use super::{archive, extensions, inspect, shapefile, sql, DuckDBFileProcessor, FileType};
+
+// Bytes of a CSV read to estimate its average line length
+const CSV_SAMPLE_BYTES: usize = 1 << 20;
+
+// Approximate row count of a file, from its metadata where it has any
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RowEstimate {
+    pub rows: u64,
+    // True when the count comes from file metadata rather than a sample
+    pub exact: bool,
+}
+
+// Estimate the rows of a file cheaply, for sizing and batching before a load: Parquet footer
+// metadata, GDAL feature counts (GeoPackage keeps them in gpkg_ogr_contents) and a sample of
+// CSV lines. Only the file's header and metadata are read. None when there's no cheap way to
+// tell, e.g. for remote and Postgres sources and zip archives
+pub fn estimate_rows(file_path: &str) -> Result<Option<RowEstimate>, io::Error> {
+    read_estimate(file_path)
+        .map_err(|e| io::Error::other(format!("Error estimating rows of '{}': {}", file_path, e)))
+}
+
+fn read_estimate(file_path: &str) -> Result<Option<RowEstimate>, Box<dyn Error>> {
+    // Fetching, querying or extracting the source would cost as much as loading it
+    if RemoteSource::parse(file_path).is_some() || PostgresSource::parse(file_path).is_some() {
+        return Ok(None);
+    }
+    let file_path = shapefile::resolve_shapefile_path(file_path)?;
+    if archive::is_data_archive(&file_path)? {
+        return Ok(None);
+    }
+
+    let file_type = DuckDBFileProcessor::determine_file_type(&file_path)?;
+    match file_type {
+        FileType::Parquet => {
+            let rows: i64 = Connection::open_in_memory()?.query_row(
+                &format!(
+                    "SELECT sum(num_rows)::BIGINT FROM parquet_file_metadata({});",
+                    sql::string_literal(&file_path)
+                ),
+                [],
+                |row| row.get(0),
+            )?;
+            Ok(Some(RowEstimate {
+                rows: rows as u64,
+                exact: true,
+            }))
+        }
+        FileType::Csv => estimate_csv_rows(&file_path).map(Some),
+        // A raster loads as a single footprint row
+        FileType::GeoTiff => Ok(Some(RowEstimate {
+            rows: 1,
+            exact: true,
+        })),
+        _ if inspect::is_gdal_format(&file_type, &file_path) => {
+            let conn = Connection::open_in_memory()?;
+            extensions::load_extension(&conn, "spatial")?;
+            // GDAL reports -1 when a count would need a full scan
+            let count: Option<i64> = conn.query_row(
+                &format!(
+                    "SELECT layers[1].feature_count FROM st_read_meta({});",
+                    sql::string_literal(&file_path)
+                ),
+                [],
+                |row| row.get(0),
+            )?;
+            Ok(count.filter(|count| *count >= 0).map(|count| RowEstimate {
+                rows: count as u64,
+                exact: true,
+            }))
+        }
+        _ => Ok(None),
+    }
+}
+
+// Count the lines of a small CSV, or scale the average line length of a sample to the file size
+fn estimate_csv_rows(file_path: &str) -> Result<RowEstimate, Box<dyn Error>> {
+    let file = File::open(file_path)?;
+    let file_size = file.metadata()?.len();
+    let mut sample = Vec::with_capacity(CSV_SAMPLE_BYTES);
+    file.take(CSV_SAMPLE_BYTES as u64)
+        .read_to_end(&mut sample)?;
+
+    let mut lines = sample.iter().filter(|byte| **byte == b'\n').count() as u64;
+    if sample.last().is_some_and(|byte| *byte != b'\n') {
+        lines += 1;
+    }
+    // The header line isn't a row
+    let header_length = sample
+        .iter()
+        .position(|byte| *byte == b'\n')
+        .map_or(sample.len(), |position| position + 1) as u64;
+    let rows = lines.saturating_sub(1);
+
+    if sample.len() as u64 >= file_size {
+        return Ok(RowEstimate { rows, exact: true });
+    }
+    // A header longer than the sample leaves nothing to measure
+    if rows == 0 {
+        return Ok(RowEstimate { rows, exact: false });
+    }
+    let average_line = (sample.len() as u64 - header_length) as f64 / rows as f64;
+    Ok(RowEstimate {
+        rows: ((file_size - header_length) as f64 / average_line).round() as u64,
+        exact: false,
+    })
+}
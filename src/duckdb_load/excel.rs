@@ -0,0 +1,592 @@
+use std::error::Error;
+use std::iter;
+
+use duckdb::appender_params_from_iter;
+use duckdb::types::Value;
+
+use super::xls::{self, XlsWorkbook};
+use super::{archive, naming, DuckDBFileProcessor, ExcelDateSystem};
+
+// Largest column (XFD) and row of an Excel sheet
+const MAX_COLUMN: usize = 16384;
+const MAX_ROW: usize = 1048576;
+
+// Types tried for each column of a sheet read as text, narrowest first
+const INFERRED_TYPES: [(&str, &str); 5] = [
+    (
+        "BIGINT",
+        "regexp_full_match({v}, '[-+]?[0-9]+') AND TRY_CAST({v} AS BIGINT) IS NOT NULL",
+    ),
+    ("DOUBLE", "TRY_CAST({v} AS DOUBLE) IS NOT NULL"),
+    ("BOOLEAN", "lower({v}) IN ('true', 'false')"),
+    ("DATE", "TRY_CAST({v} AS DATE) IS NOT NULL"),
+    ("TIMESTAMP", "TRY_CAST({v} AS TIMESTAMP) IS NOT NULL"),
+];
+
+impl DuckDBFileProcessor {
+    // Read the table from a workbook - the cells of the configured range, or the first sheet -
+    // skipping rows above it and taking the column names from the configured header row.
+    // Legacy .xls workbooks are always read this way, as GDAL can't open them
+    pub(super) fn excel_select(&self) -> Result<String, Box<dyn Error>> {
+        let excel = &self.options.excel;
+        let xls = match xls::is_xls(&self.file_path) {
+            true => Some(XlsWorkbook::open(&self.file_path)?),
+            false => None,
+        };
+        if excel.is_default() && xls.is_none() {
+            return Ok(format!("SELECT * FROM {}", self.st_read_source()));
+        }
+        let range = match &excel.range {
+            Some(range) => Some(self.excel_range(range)?),
+            None => None,
+        };
+        let sheet = range.as_ref().and_then(|range| range.sheet.as_deref());
+
+        match &xls {
+            Some(workbook) => self.load_xls_rows(workbook, sheet)?,
+            // GDAL types a column from every cell in it, title rows included, so read the
+            // sheet as text and type the columns from the table's own rows
+            None => {
+                let layer = match sheet {
+                    Some(sheet) => format!(", layer = '{}'", sheet.replace('\'', "''")),
+                    None => String::new(),
+                };
+                self.conn.execute(
+                    &format!(
+                        "CREATE TEMP TABLE excel_rows AS
+                        SELECT row_number() OVER () AS excel_row, *
+                        FROM st_read(
+                            '{}'{}, open_options = ['HEADERS=DISABLE', 'FIELD_TYPES=STRING']
+                        );",
+                        self.file_path, layer
+                    ),
+                    [],
+                )?;
+            }
+        }
+        let mut stmt = self.conn.prepare(
+            "SELECT column_name FROM information_schema.columns
+            WHERE table_name = 'excel_rows' AND column_name <> 'excel_row' AND data_type = 'VARCHAR'
+            ORDER BY ordinal_position",
+        )?;
+        let fields = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .filter(|field| range.as_ref().is_none_or(|range| range.has_field(field)))
+            .collect::<Vec<_>>();
+        if fields.is_empty() {
+            return Err("The Excel sheet has no columns".into());
+        }
+
+        // Rows are counted from the top of the range, and rows below it aren't read
+        let top = range.as_ref().map_or(1, |range| range.first_row);
+        let (names, first_row) = if excel.has_header {
+            let header_row = top - 1 + excel.skip_rows + excel.header_row;
+            if let Some(range) = range.as_ref().filter(|range| header_row > range.last_row) {
+                return Err(format!(
+                    "Excel header row {} is below the last row of the range, {}",
+                    header_row, range.last_row
+                )
+                .into());
+            }
+            (self.excel_header(&fields, header_row)?, header_row + 1)
+        } else {
+            (fields.clone(), top + excel.skip_rows)
+        };
+        let last_row = range
+            .as_ref()
+            .map(|range| format!(" AND excel_row <= {}", range.last_row))
+            .unwrap_or_default();
+        let quoted = fields
+            .iter()
+            .map(|field| naming::quote_identifier(field))
+            .collect::<Vec<_>>();
+        let mut values = quoted
+            .iter()
+            .map(|field| format!("NULLIF(trim({}), '')", field))
+            .collect::<Vec<_>>();
+        // Blank rows, such as one between the header and the data, aren't part of the table
+        let rows = format!(
+            "FROM excel_rows WHERE excel_row >= {}{} AND coalesce({}) IS NOT NULL",
+            first_row,
+            last_row,
+            values.join(", ")
+        );
+
+        let mut types = self.excel_column_types(&quoted, &rows)?;
+        if !excel.date_columns.is_empty() {
+            let date_1904 = self.excel_uses_1904_dates(xls.as_ref());
+            for column in &excel.date_columns {
+                let index = names
+                    .iter()
+                    .position(|name| name == column)
+                    .ok_or_else(|| format!("Excel date column '{}' isn't in the sheet", column))?;
+                let date = date_expression(&values[index], date_1904, excel.date_format.as_deref());
+                types[index] = self.excel_date_type(column, &values[index], &date, &rows)?;
+                values[index] = date;
+            }
+        }
+        let select_list = quoted
+            .iter()
+            .zip(&values)
+            .zip(names.iter().zip(&types))
+            .map(
+                |((field, value), (name, data_type))| match data_type.as_str() {
+                    "VARCHAR" => format!("{} AS {}", field, naming::quote_identifier(name)),
+                    _ => format!(
+                        "CAST({} AS {}) AS {}",
+                        value,
+                        data_type,
+                        naming::quote_identifier(name)
+                    ),
+                },
+            )
+            .collect::<Vec<_>>()
+            .join(", ");
+        Ok(format!(
+            "SELECT {} {} ORDER BY excel_row",
+            select_list, rows
+        ))
+    }
+
+    // Cells of an .xls worksheet as the text table GDAL reads .xlsx sheets into, with a
+    // FieldN column for each column of the sheet and a row for every row down to the last
+    fn load_xls_rows(
+        &self,
+        workbook: &XlsWorkbook,
+        sheet: Option<&str>,
+    ) -> Result<(), Box<dyn Error>> {
+        let rows = workbook.sheet_rows(sheet)?;
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let fields = (1..=columns)
+            .map(|index| format!("\"Field{}\" VARCHAR", index))
+            .collect::<Vec<_>>();
+        self.conn.execute(
+            &format!(
+                "CREATE TEMP TABLE excel_rows (excel_row BIGINT{}{});",
+                if fields.is_empty() { "" } else { ", " },
+                fields.join(", ")
+            ),
+            [],
+        )?;
+        let mut appender = self.conn.appender("excel_rows")?;
+        for (index, cells) in rows.into_iter().enumerate() {
+            let values =
+                iter::once(Value::BigInt(index as i64 + 1)).chain((0..columns).map(|column| {
+                    match cells.get(column).cloned().flatten() {
+                        Some(text) => Value::Text(text),
+                        None => Value::Null,
+                    }
+                }));
+            appender.append_row(appender_params_from_iter(values))?;
+        }
+        appender.flush()?;
+        Ok(())
+    }
+
+    // The cells of an A1 range, or of the range defined in the workbook under that name
+    fn excel_range(&self, range: &str) -> Result<CellRange, Box<dyn Error>> {
+        if let Some(cells) = CellRange::parse(range) {
+            return Ok(cells);
+        }
+        // Names are kept in the workbook part of an .xlsx package
+        let workbook = archive::read_entry(&self.file_path, "xl/workbook.xml")
+            .ok()
+            .flatten()
+            .ok_or_else(|| {
+                format!(
+                    "Excel range '{}' is a name, but names can only be read from .xlsx workbooks",
+                    range
+                )
+            })?;
+        let reference = defined_name(&String::from_utf8_lossy(&workbook), range)
+            .ok_or_else(|| format!("The workbook has no range named '{}'", range))?;
+        CellRange::parse(&reference).ok_or_else(|| {
+            format!(
+                "Excel range '{}' refers to {}, which isn't a single block of cells",
+                range, reference
+            )
+            .into()
+        })
+    }
+
+    // Whether serial dates count from 1904, as set in the workbook unless the options say
+    fn excel_uses_1904_dates(&self, xls: Option<&XlsWorkbook>) -> bool {
+        match self.options.excel.date_system {
+            ExcelDateSystem::Excel1900 => false,
+            ExcelDateSystem::Excel1904 => true,
+            ExcelDateSystem::Auto if xls.is_some() => xls.is_some_and(XlsWorkbook::date_1904),
+            ExcelDateSystem::Auto => archive::read_entry(&self.file_path, "xl/workbook.xml")
+                .ok()
+                .flatten()
+                .is_some_and(|workbook| uses_1904_dates(&String::from_utf8_lossy(&workbook))),
+        }
+    }
+
+    // DATE when no value of a date column has a time, otherwise TIMESTAMP. Values that are
+    // neither serial numbers nor dates fail the load rather than being loaded as NULL
+    fn excel_date_type(
+        &self,
+        column: &str,
+        value: &str,
+        date: &str,
+        rows: &str,
+    ) -> Result<String, Box<dyn Error>> {
+        let (unreadable, has_time): (i64, Option<bool>) = self.conn.query_row(
+            &format!(
+                "SELECT count({}) - count({date}), bool_or({date} <> date_trunc('day', {date})) {}",
+                value,
+                rows,
+                date = date
+            ),
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        if unreadable > 0 {
+            return Err(format!(
+                "Excel date column '{}' has {} values that aren't dates - set date_format for \
+                dates written as text, such as %d/%m/%Y",
+                column, unreadable
+            )
+            .into());
+        }
+        Ok(if has_time == Some(true) {
+            "TIMESTAMP"
+        } else {
+            "DATE"
+        }
+        .to_string())
+    }
+
+    // Column names from the header row, keeping GDAL's FieldN for blank header cells
+    fn excel_header(
+        &self,
+        fields: &[String],
+        header_row: usize,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let select_list = fields
+            .iter()
+            .map(|field| naming::quote_identifier(field))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM excel_rows WHERE excel_row = {}",
+            select_list, header_row
+        ))?;
+        let mut rows = stmt.query([])?;
+        let Some(row) = rows.next()? else {
+            return Err(format!(
+                "Excel header row {} is past the end of the sheet",
+                header_row
+            )
+            .into());
+        };
+        let mut names = Vec::with_capacity(fields.len());
+        for (index, field) in fields.iter().enumerate() {
+            let name = row
+                .get::<_, Option<String>>(index)?
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| field.clone());
+            names.push(name);
+        }
+        Ok(names)
+    }
+
+    // The narrowest type every non-blank value of each column casts to
+    fn excel_column_types(
+        &self,
+        fields: &[String],
+        rows: &str,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let checks = fields
+            .iter()
+            .map(|field| {
+                let value = format!("NULLIF(trim({}), '')", field);
+                let cases = INFERRED_TYPES
+                    .iter()
+                    .map(|(data_type, check)| {
+                        format!(
+                            "WHEN bool_and({}) THEN '{}'",
+                            check.replace("{v}", &value),
+                            data_type
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                format!(
+                    "CASE WHEN count({}) = 0 THEN 'VARCHAR' {} ELSE 'VARCHAR' END",
+                    value, cases
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut stmt = self.conn.prepare(&format!("SELECT {} {}", checks, rows))?;
+        let mut result = stmt.query([])?;
+        let row = result
+            .next()?
+            .ok_or("Excel type inference returned no row")?;
+        let mut types = Vec::with_capacity(fields.len());
+        for index in 0..fields.len() {
+            types.push(row.get::<_, String>(index)?);
+        }
+        Ok(types)
+    }
+}
+
+// TIMESTAMP for an Excel date - a serial number of days, with the time as a fraction, or text
+fn date_expression(value: &str, date_1904: bool, date_format: Option<&str>) -> String {
+    let serial = format!("TRY_CAST({} AS DOUBLE)", value);
+    let (epoch, days) = if date_1904 {
+        ("1904-01-01", serial.clone())
+    } else {
+        // Excel counts 1900 as a leap year, so serials before 1 March 1900 are a day out
+        (
+            "1899-12-30",
+            format!("({s} + CASE WHEN {s} < 61 THEN 1 ELSE 0 END)", s = serial),
+        )
+    };
+    let text = match date_format {
+        Some(format) => format!(
+            "coalesce(try_strptime({}, '{}'), TRY_CAST({} AS TIMESTAMP))",
+            value,
+            format.replace('\'', "''"),
+            value
+        ),
+        None => format!("TRY_CAST({} AS TIMESTAMP)", value),
+    };
+    format!(
+        "CASE WHEN {} IS NOT NULL THEN TIMESTAMP '{}' + \
+        to_microseconds(CAST(round({} * 86400000000) AS BIGINT)) ELSE {} END",
+        serial, epoch, days, text
+    )
+}
+
+// Whether the workbookPr element of xl/workbook.xml sets the 1904 date system
+fn uses_1904_dates(workbook: &str) -> bool {
+    workbook
+        .split_once("<workbookPr")
+        .and_then(|(_, element)| element.split_once('>'))
+        .is_some_and(|(attributes, _)| {
+            attributes.contains("date1904=\"1\"") || attributes.contains("date1904=\"true\"")
+        })
+}
+
+// Cells of a sheet, with rows and columns counted from 1
+struct CellRange {
+    // Sheet the cells are on - the first sheet when None
+    sheet: Option<String>,
+    first_row: usize,
+    last_row: usize,
+    first_column: usize,
+    last_column: usize,
+}
+
+impl CellRange {
+    // An A1 range such as B3:H500, $B$3:$H$500 or 'Q3 Report'!B3:H500. None for anything
+    // else, which may be a defined name
+    fn parse(reference: &str) -> Option<Self> {
+        let (sheet, cells) = match reference.trim().rsplit_once('!') {
+            Some((sheet, cells)) => (Some(sheet_name(sheet)?), cells),
+            None => (None, reference.trim()),
+        };
+        let (first, last) = cells.split_once(':')?;
+        let (first_column, first_row) = parse_cell(first)?;
+        let (last_column, last_row) = parse_cell(last)?;
+        Some(Self {
+            sheet,
+            first_row: first_row.min(last_row),
+            last_row: first_row.max(last_row),
+            first_column: first_column.min(last_column),
+            last_column: first_column.max(last_column),
+        })
+    }
+
+    // Whether a column GDAL read as FieldN, N counting from 1 for column A, is in the range
+    fn has_field(&self, field: &str) -> bool {
+        field
+            .strip_prefix("Field")
+            .and_then(|index| index.parse::<usize>().ok())
+            .is_some_and(|column| (self.first_column..=self.last_column).contains(&column))
+    }
+}
+
+// Check a range option is an A1 range or could be a defined name, which is only looked up
+// when the workbook is read
+pub(super) fn validate_range(range: &str) -> Result<(), Box<dyn Error>> {
+    let mut chars = range.chars();
+    let is_name = chars
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_' || c == '\\')
+        && chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | '.' | '\\'));
+    if CellRange::parse(range).is_none() && !is_name {
+        return Err(format!(
+            "Excel range '{}' isn't a cell range such as B3:H500 or a defined name",
+            range
+        )
+        .into());
+    }
+    Ok(())
+}
+
+// Column and row of a cell such as B3 or $B$3
+fn parse_cell(cell: &str) -> Option<(usize, usize)> {
+    let cell = cell.trim().replace('$', "");
+    let split = cell.find(|c: char| !c.is_ascii_alphabetic())?;
+    let (letters, digits) = cell.split_at(split);
+    if letters.is_empty() || letters.len() > 3 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let column = letters.chars().fold(0, |column, c| {
+        column * 26 + (c.to_ascii_uppercase() as usize - 'A' as usize + 1)
+    });
+    let row = digits.parse::<usize>().ok()?;
+    let valid = (1..=MAX_COLUMN).contains(&column) && (1..=MAX_ROW).contains(&row);
+    valid.then_some((column, row))
+}
+
+// A sheet name as written before the '!' of a reference, quoted when it has spaces or
+// punctuation, with quotes in it doubled
+fn sheet_name(sheet: &str) -> Option<String> {
+    match sheet
+        .strip_prefix('\'')
+        .and_then(|sheet| sheet.strip_suffix('\''))
+    {
+        Some(quoted) => Some(quoted.replace("''", "'")),
+        None if !sheet.is_empty() && !sheet.contains('\'') => Some(sheet.to_string()),
+        None => None,
+    }
+}
+
+// The reference of a definedName element in xl/workbook.xml, such as Report!$B$3:$H$500.
+// Names are matched without regard to case, as in Excel
+fn defined_name(workbook: &str, name: &str) -> Option<String> {
+    // The definedNames element wrapping them splits off too, but has no name attribute
+    for element in workbook.split("<definedName").skip(1) {
+        let Some((attributes, rest)) = element.split_once('>') else {
+            continue;
+        };
+        let Some((_, value)) = attributes.split_once(" name=\"") else {
+            continue;
+        };
+        let Some((value_name, _)) = value.split_once('"') else {
+            continue;
+        };
+        if !unescape_xml(value_name).eq_ignore_ascii_case(name) {
+            continue;
+        }
+        let reference = rest.split_once("</definedName>")?.0;
+        return Some(unescape_xml(reference));
+    }
+    None
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use duckdb::Connection;
+
+    fn date(value: &str, date_1904: bool, date_format: Option<&str>) -> Option<String> {
+        Connection::open_in_memory()
+            .unwrap()
+            .query_row(
+                &format!(
+                    "SELECT CAST({} AS VARCHAR);",
+                    date_expression(value, date_1904, date_format)
+                ),
+                [],
+                |row| row.get(0),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn parses_cell_ranges() {
+        let range = CellRange::parse("'Q3 ''Report'''!$H$500:B3").unwrap();
+        assert_eq!(range.sheet.as_deref(), Some("Q3 'Report'"));
+        assert_eq!((range.first_row, range.last_row), (3, 500));
+        assert_eq!((range.first_column, range.last_column), (2, 8));
+        assert!(range.has_field("Field2") && range.has_field("Field8"));
+        assert!(!range.has_field("Field9") && !range.has_field("name"));
+
+        let range = CellRange::parse("Sheet1!A1:XFD1048576").unwrap();
+        assert_eq!(range.sheet.as_deref(), Some("Sheet1"));
+        assert_eq!(range.last_column, MAX_COLUMN);
+        assert!(CellRange::parse("A1:XFE1").is_none());
+        assert!(CellRange::parse("A0:B2").is_none());
+        assert!(CellRange::parse("Report").is_none());
+        assert!(CellRange::parse("It's!A1:B2").is_none());
+    }
+
+    #[test]
+    fn validates_ranges_and_names() {
+        assert!(validate_range("B3:H500").is_ok());
+        assert!(validate_range("Report_2024.Q3").is_ok());
+        assert!(validate_range("B3-H500").is_err());
+        assert!(validate_range("3D").is_err());
+    }
+
+    #[test]
+    fn reads_defined_names_and_the_date_system() {
+        let workbook = r#"<workbook><workbookPr date1904="1"/><definedNames>
+            <definedName name="Other">Sheet1!$A$1:$A$2</definedName>
+            <definedName name="R&amp;D">'R&amp;D'!$B$3:$H$500</definedName>
+            </definedNames></workbook>"#;
+        assert_eq!(
+            defined_name(workbook, "r&d"),
+            Some("'R&D'!$B$3:$H$500".to_string())
+        );
+        assert_eq!(defined_name(workbook, "Missing"), None);
+        assert!(uses_1904_dates(workbook));
+        assert!(!uses_1904_dates(
+            "<workbook><workbookPr date1904=\"0\"/></workbook>"
+        ));
+    }
+
+    #[test]
+    fn converts_serial_dates() {
+        assert_eq!(
+            date("'45292'", false, None).as_deref(),
+            Some("2024-01-01 00:00:00")
+        );
+        assert_eq!(
+            date("'45292.5'", false, None).as_deref(),
+            Some("2024-01-01 12:00:00")
+        );
+        // Serials before 1 March 1900 allow for Excel's 29 February 1900
+        assert_eq!(
+            date("'1'", false, None).as_deref(),
+            Some("1900-01-01 00:00:00")
+        );
+        assert_eq!(
+            date("'61'", false, None).as_deref(),
+            Some("1900-03-01 00:00:00")
+        );
+        assert_eq!(
+            date("'0'", true, None).as_deref(),
+            Some("1904-01-01 00:00:00")
+        );
+    }
+
+    #[test]
+    fn converts_text_dates() {
+        assert_eq!(
+            date("'31/01/2024'", false, Some("%d/%m/%Y")).as_deref(),
+            Some("2024-01-31 00:00:00")
+        );
+        assert_eq!(
+            date("'2024-01-31 08:30:00'", false, None).as_deref(),
+            Some("2024-01-31 08:30:00")
+        );
+        assert_eq!(date("'soon'", false, None), None);
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::fs::File;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{OnceLock, PoisonError, RwLock};
+
+use duckdb::{Connection, OptionalExt};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use super::sql;
+
+// Extension directory used when none is set with set_extension_directory
+pub const EXTENSION_DIRECTORY_VARIABLE: &str = "DUCKDB_POSTGIS_EXTENSION_DIR";
+
+// Version and checksum a DuckDB extension must have, for reproducible loads
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExtensionPin {
+    // Version to install, as DuckDB reports it in duckdb_extensions(), such as "v1.1.1" or a
+    // commit hash - an installed extension of another version is replaced
+    pub version: Option<String>,
+    // SHA-256 of the installed .duckdb_extension file, as lowercase hex
+    pub sha256: Option<String>,
+}
+
+#[derive(Default)]
+struct ExtensionSettings {
+    directory: Option<PathBuf>,
+    repository: Option<String>,
+    pins: HashMap<String, ExtensionPin>,
+}
+
+fn settings() -> &'static RwLock<ExtensionSettings> {
+    static SETTINGS: OnceLock<RwLock<ExtensionSettings>> = OnceLock::new();
+    SETTINGS.get_or_init(RwLock::default)
+}
+
+// Load DuckDB extensions from a local directory instead of downloading them, for air-gapped
+// deployments, for the whole process. The directory has DuckDB's own layout,
+// <directory>/<duckdb version>/<platform>/<name>.duckdb_extension, as left by INSTALL with the
+// same extension_directory on a machine with internet access
+pub fn set_extension_directory(directory: Option<PathBuf>) {
+    settings()
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .directory = directory;
+}
+
+// Directory extensions are loaded from - set_extension_directory, else
+// DUCKDB_POSTGIS_EXTENSION_DIR, else None to install them from the DuckDB repository
+pub fn extension_directory() -> Option<PathBuf> {
+    settings()
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .directory
+        .clone()
+        .or_else(|| env::var_os(EXTENSION_DIRECTORY_VARIABLE).map(PathBuf::from))
+}
+
+// Install extensions from this repository URL (or local repository path) instead of the
+// default DuckDB repository, for the whole process
+pub fn set_extension_repository(repository: Option<String>) {
+    settings()
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .repository = repository;
+}
+
+// Require a version and/or checksum of an extension, such as "spatial", for the whole process
+// A load fails before reading any data when the extension doesn't match
+pub fn pin_extension(name: &str, pin: ExtensionPin) {
+    settings()
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .pins
+        .insert(name.to_string(), pin);
+}
+
+// Install and load a DuckDB extension, or load it from the extension directory when there is
+// one, then check it against its pin
+pub(crate) fn load_extension(conn: &Connection, name: &str) -> Result<(), Box<dyn Error>> {
+    let (repository, pin) = {
+        let settings = settings().read().unwrap_or_else(PoisonError::into_inner);
+        (
+            settings.repository.clone(),
+            settings.pins.get(name).cloned(),
+        )
+    };
+    let pin = pin.unwrap_or_default();
+
+    match extension_directory() {
+        Some(directory) => {
+            let directory = directory.to_string_lossy().into_owned();
+            conn.execute_batch(&format!(
+                "SET extension_directory = {}; SET autoinstall_known_extensions = false;",
+                sql::string_literal(&directory)
+            ))?;
+            // Without a repository to install from, the directory must already hold it
+            if repository.is_some() {
+                install_extension(conn, name, repository.as_deref(), &pin)?;
+            }
+            conn.execute_batch(&format!("LOAD {};", name))
+                .map_err(|e| offline_error(conn, name, &directory, e))?;
+        }
+        None => {
+            install_extension(conn, name, repository.as_deref(), &pin)?;
+            conn.execute_batch(&format!("LOAD {};", name))?;
+        }
+    }
+    verify_extension(conn, name, &pin)
+}
+
+fn install_extension(
+    conn: &Connection,
+    name: &str,
+    repository: Option<&str>,
+    pin: &ExtensionPin,
+) -> Result<(), Box<dyn Error>> {
+    let mut install = format!("INSTALL {}", name);
+    if let Some(repository) = repository {
+        install.push_str(&format!(" FROM {}", sql::string_literal(repository)));
+    }
+    if let Some(version) = &pin.version {
+        // INSTALL keeps an extension that is already installed, whatever its version
+        let installed = installed_extension(conn, name)?.and_then(|(version, _)| version);
+        if installed.as_deref() != Some(version.as_str()) {
+            install.insert_str(0, "FORCE ");
+        }
+        install.push_str(&format!(" VERSION {}", sql::string_literal(version)));
+    }
+    conn.execute_batch(&format!("{};", install))?;
+    Ok(())
+}
+
+// Fail when the loaded extension isn't the pinned version, or its file the pinned checksum
+fn verify_extension(
+    conn: &Connection,
+    name: &str,
+    pin: &ExtensionPin,
+) -> Result<(), Box<dyn Error>> {
+    if pin.version.is_none() && pin.sha256.is_none() {
+        return Ok(());
+    }
+    let (version, install_path) = installed_extension(conn, name)?
+        .ok_or_else(|| format!("DuckDB extension '{}' is not installed", name))?;
+    if let Some(expected) = &pin.version {
+        if version.as_deref() != Some(expected.as_str()) {
+            return Err(format!(
+                "DuckDB extension '{}' is version {}, not the pinned {}",
+                name,
+                version.as_deref().unwrap_or("unknown"),
+                expected
+            )
+            .into());
+        }
+    }
+    if let Some(expected) = &pin.sha256 {
+        let install_path = install_path
+            .ok_or_else(|| format!("DuckDB extension '{}' has no install path to verify", name))?;
+        let mut hasher = Sha256::new();
+        io::copy(&mut File::open(&install_path)?, &mut hasher)?;
+        let checksum = format!("{:x}", hasher.finalize());
+        if !checksum.eq_ignore_ascii_case(expected) {
+            return Err(format!(
+                "DuckDB extension '{}' at {} has SHA-256 {}, not the pinned {}",
+                name, install_path, checksum, expected
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+// Version and install path of an installed extension
+fn installed_extension(
+    conn: &Connection,
+    name: &str,
+) -> duckdb::Result<Option<(Option<String>, Option<String>)>> {
+    conn.query_row(
+        "SELECT extension_version, install_path FROM duckdb_extensions()
+        WHERE extension_name = ? AND installed;",
+        [name],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+}
+
+fn offline_error(
+    conn: &Connection,
+    name: &str,
+    directory: &str,
+    error: duckdb::Error,
+) -> Box<dyn Error> {
+    let expected = match duckdb_platform(conn) {
+        Ok((version, platform)) => format!(
+            "{}/{}/{}/{}.duckdb_extension",
+            directory, version, platform, name
+        ),
+        Err(_) => directory.to_string(),
+    };
+    format!(
+        "DuckDB extension '{}' is not available offline - expected it at {}. Install it \
+        there on a machine with internet access by running SET extension_directory = '{}'; \
+        INSTALL {}; in the same DuckDB version: {}",
+        name, expected, directory, name, error
+    )
+    .into()
+}
+
+// DuckDB version and platform, which name the directories extensions are stored under
+fn duckdb_platform(conn: &Connection) -> duckdb::Result<(String, String)> {
+    conn.query_row(
+        "SELECT (SELECT library_version FROM pragma_version()),
+            (SELECT platform FROM pragma_platform());",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+}
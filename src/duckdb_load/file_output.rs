@@ -0,0 +1,108 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use super::logging::{log_debug, log_info, log_warn};
+use super::{naming, sql, DuckDBFileProcessor, OutputTarget};
+
+impl DuckDBFileProcessor {
+    // Write the transformed table to a GeoPackage or SpatiaLite file instead of PostGIS
+    pub(super) fn write_file_output(
+        &self,
+        source_table: &str,
+        geom_columns: &[String],
+        target: &OutputTarget,
+    ) -> Result<(), Box<dyn Error>> {
+        let (path, driver, creation_options) = match target {
+            OutputTarget::Geopackage(path) => (path, "GPKG", ""),
+            OutputTarget::Spatialite(path) => (
+                path,
+                "SQLite",
+                ", DATASET_CREATION_OPTIONS ('SPATIALITE=YES')",
+            ),
+            OutputTarget::Postgis => return Err("PostGIS is not a file output target".into()),
+        };
+        log_debug!("WRITING {} FILE {}", driver, path.display());
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| format!("Invalid output path: {}", path.display()))?;
+
+        // GDAL names the layer after the file, so write under the final name in a scratch
+        // directory and move it into place once complete. The directory is unique per load,
+        // so outputs written side by side into one folder never remove each other's work
+        let parent = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        let scratch_dir = tempfile::Builder::new()
+            .prefix(".duckdb_postgis_")
+            .tempdir_in(parent)?
+            .into_path();
+        let scratch_path = scratch_dir.join(file_name);
+
+        let result = self
+            .copy_to_gdal_file(
+                source_table,
+                geom_columns,
+                &scratch_path,
+                driver,
+                creation_options,
+            )
+            .and_then(|_| Ok(fs::rename(&scratch_path, path)?));
+        if let Err(e) = fs::remove_dir_all(&scratch_dir) {
+            log_warn!("Failed to remove {}: {}", scratch_dir.display(), e);
+        }
+        result?;
+
+        log_info!(
+            "{} written with geometry columns: {:?}",
+            path.display(),
+            geom_columns
+        );
+        Ok(())
+    }
+
+    fn copy_to_gdal_file(
+        &self,
+        source_table: &str,
+        geom_columns: &[String],
+        path: &Path,
+        driver: &str,
+        creation_options: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        // GDAL layers hold one geometry column, so any others stay as WKT text
+        let select = match geom_columns.first() {
+            Some(geom_column) => format!(
+                "SELECT * EXCLUDE ({wkt}), ST_GeomFromText({wkt}) AS {column} FROM {table}",
+                wkt = naming::quote_identifier(&format!("{}_wkt", geom_column)),
+                column = naming::quote_identifier(geom_column),
+                table = source_table
+            ),
+            None => format!("SELECT * FROM {}", source_table),
+        };
+
+        let row_count: i64 = self.conn.query_row(
+            &format!("SELECT COUNT(*) FROM {};", source_table),
+            [],
+            |row| row.get(0),
+        )?;
+        self.report.borrow_mut().rows_loaded = row_count as u64;
+
+        let srid = match geom_columns.first() {
+            Some(geom_column) => self.geometry_srid(geom_column),
+            None => "4326".to_string(),
+        };
+        self.conn.execute(
+            &format!(
+                "COPY ({}) TO {} WITH (FORMAT GDAL, DRIVER '{}', SRS 'EPSG:{}'{});",
+                select,
+                sql::string_literal(&path.display().to_string()),
+                driver,
+                srid,
+                creation_options
+            ),
+            [],
+        )?;
+        Ok(())
+    }
+}
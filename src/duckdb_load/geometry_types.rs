@@ -0,0 +1,233 @@
+use std::error::Error;
+use std::io;
+
+use super::logging::{log_debug, log_info};
+use super::{
+    credentials, naming, run_load, DuckDBFileProcessor, GeometryFamily, LoadReport, MixedGeometry,
+    ProcessorOptions, TransformHook,
+};
+
+impl GeometryFamily {
+    const ALL: [GeometryFamily; 3] = [Self::Points, Self::Lines, Self::Polygons];
+
+    fn geometry_types(self) -> &'static str {
+        match self {
+            Self::Points => "'POINT', 'MULTIPOINT'",
+            Self::Lines => "'LINESTRING', 'MULTILINESTRING'",
+            Self::Polygons => "'POLYGON', 'MULTIPOLYGON'",
+        }
+    }
+
+    fn table_suffix(self) -> &'static str {
+        match self {
+            Self::Points => "points",
+            Self::Lines => "lines",
+            Self::Polygons => "polygons",
+        }
+    }
+}
+
+impl DuckDBFileProcessor {
+    // Keep only rows whose geometry is of the chosen family, for MixedGeometry::Filter
+    pub(super) fn apply_geometry_filter(&self) -> Result<(), Box<dyn Error>> {
+        let MixedGeometry::Filter(family) = self.options.mixed_geometry else {
+            return Ok(());
+        };
+        let Some(geom_column) = self.first_geometry_column()? else {
+            return Ok(());
+        };
+        let removed = self.conn.execute(
+            &format!(
+                "DELETE FROM data WHERE {column} IS NULL
+                    OR CAST(ST_GeometryType({column}) AS VARCHAR) NOT IN ({types});",
+                column = naming::quote_identifier(&geom_column),
+                types = family.geometry_types()
+            ),
+            [],
+        )?;
+        log_info!(
+            "Kept {:?} geometries only - {} other rows removed",
+            family,
+            removed
+        );
+        Ok(())
+    }
+
+    // Geometry families present in the first geometry column of the data table
+    fn geometry_families(&self) -> Result<Vec<GeometryFamily>, Box<dyn Error>> {
+        let Some(geom_column) = self.first_geometry_column()? else {
+            return Ok(Vec::new());
+        };
+        let types = self.geometry_types("data", &geom_column)?;
+        Ok(GeometryFamily::ALL
+            .into_iter()
+            .filter(|family| {
+                types
+                    .iter()
+                    .any(|found| family.geometry_types().contains(&format!("'{}'", found)))
+            })
+            .collect())
+    }
+
+    fn first_geometry_column(&self) -> Result<Option<String>, Box<dyn Error>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT column_name FROM information_schema.columns
+            WHERE table_name = 'data' AND data_type = 'GEOMETRY'
+            ORDER BY ordinal_position LIMIT 1",
+        )?;
+        let mut rows = stmt.query([])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(row.get(0)?)),
+            None => Ok(None),
+        }
+    }
+
+    // Promote single geometries to multi when a column mixes e.g. POLYGON and MULTIPOLYGON,
+    // so the column has one type for typed PostGIS columns and clients
+    pub(super) fn promote_mixed_multi(&self, geom_column: &str) -> Result<(), Box<dyn Error>> {
+        if !self.options.promote_to_multi {
+            return Ok(());
+        }
+        let types = self.geometry_types("data", geom_column)?;
+        let multi_type = match types
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .as_slice()
+        {
+            ["MULTIPOINT", "POINT"] => "MULTIPOINT",
+            ["LINESTRING", "MULTILINESTRING"] => "MULTILINESTRING",
+            ["MULTIPOLYGON", "POLYGON"] => "MULTIPOLYGON",
+            _ => return Ok(()),
+        };
+
+        self.conn.execute(
+            &format!(
+                "UPDATE data SET {column} = ST_Multi({column})
+                WHERE CAST(ST_GeometryType({column}) AS VARCHAR) <> '{multi}';",
+                column = naming::quote_identifier(geom_column),
+                multi = multi_type
+            ),
+            [],
+        )?;
+        log_debug!(
+            "Column {} mixes {:?} - promoted to {}",
+            geom_column,
+            types,
+            multi_type
+        );
+        self.report
+            .borrow_mut()
+            .multi_promotions
+            .push((geom_column.to_string(), multi_type.to_string()));
+        Ok(())
+    }
+
+    // Distinct geometry types in a column, sorted
+    pub(super) fn geometry_types(
+        &self,
+        table: &str,
+        geom_column: &str,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT DISTINCT CAST(ST_GeometryType({column}) AS VARCHAR) AS geometry_type
+            FROM {table} WHERE {column} IS NOT NULL ORDER BY geometry_type;",
+            column = naming::quote_identifier(geom_column),
+            table = table
+        ))?;
+        let types = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+        Ok(types)
+    }
+}
+
+// Load each geometry family of a mixed layer into its own <table>_points, _lines or _polygons
+// table, with one filtered load per family - a single family loads into the table as usual
+pub(super) fn run_separate_geometry_loads(
+    file_path: &str,
+    table_name: &str,
+    postgis_uri: &str,
+    schema_name: &str,
+    options: ProcessorOptions,
+    hook: Option<TransformHook>,
+) -> Result<LoadReport, io::Error> {
+    let families =
+        scan_geometry_families(file_path, table_name, postgis_uri, schema_name, &options).map_err(
+            |e| {
+                io::Error::other(format!(
+                    "Error reading geometry types of '{}': {}",
+                    file_path,
+                    credentials::redact(&e.to_string(), postgis_uri)
+                ))
+            },
+        )?;
+    if families.len() <= 1 {
+        let options = ProcessorOptions {
+            mixed_geometry: MixedGeometry::Generic,
+            ..options
+        };
+        return run_load(
+            file_path,
+            table_name,
+            postgis_uri,
+            schema_name,
+            options,
+            hook,
+        );
+    }
+    if hook.is_some() {
+        return Err(io::Error::other(
+            "with_transformed_data hooks can't be used when loading separate tables per geometry type",
+        ));
+    }
+
+    let mut combined: Option<LoadReport> = None;
+    for family in families {
+        let family_table = naming::derived_name(table_name, &format!("_{}", family.table_suffix()));
+        log_info!("Loading {:?} into {}", family, family_table);
+        let family_options = ProcessorOptions {
+            mixed_geometry: MixedGeometry::Filter(family),
+            ..options.clone()
+        };
+        let report = run_load(
+            file_path,
+            &family_table,
+            postgis_uri,
+            schema_name,
+            family_options,
+            None,
+        )?;
+        combined = Some(match combined {
+            None => LoadReport {
+                geometry_tables: vec![family_table],
+                ..report
+            },
+            Some(mut combined) => {
+                combined.rows_loaded += report.rows_loaded;
+                combined.geometry_tables.push(family_table);
+                combined
+            }
+        });
+    }
+    Ok(combined.unwrap_or_default())
+}
+
+fn scan_geometry_families(
+    file_path: &str,
+    table_name: &str,
+    postgis_uri: &str,
+    schema_name: &str,
+    options: &ProcessorOptions,
+) -> Result<Vec<GeometryFamily>, Box<dyn Error>> {
+    let processor = DuckDBFileProcessor::new_file(
+        file_path,
+        table_name,
+        postgis_uri,
+        schema_name,
+        options.clone(),
+    )?;
+    processor.lock_down_extensions()?;
+    processor.create_data_table()?;
+    processor.geometry_families()
+}
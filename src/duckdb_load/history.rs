@@ -0,0 +1,172 @@
+use std::error::Error;
+use std::io;
+use std::time::Duration;
+
+use super::logging::log_info;
+use super::{
+    connection, credentials, naming, open_connection, sql, unique_attach_alias, DuckDBFileProcessor,
+};
+
+// Table recording every load, created in the schema set by ProcessorOptions::load_history_schema
+pub const LOAD_HISTORY_TABLE: &str = "_duckdb_postgis_loads";
+
+// One row of the load history table
+#[derive(Debug, Clone)]
+pub struct LoadHistoryEntry {
+    pub load_id: i64,
+    pub loaded_at: String,
+    pub target_schema: String,
+    pub target_table: String,
+    pub source_file: String,
+    pub file_type: String,
+    pub source_sha256: Option<String>,
+    pub content_checksum: Option<String>,
+    pub rows_loaded: Option<i64>,
+    pub duration_ms: i64,
+    pub crate_version: String,
+    pub outcome: String,
+    pub error: Option<String>,
+}
+
+impl DuckDBFileProcessor {
+    pub(super) fn record_load_history(
+        &self,
+        history_schema: &str,
+        duration: Duration,
+        result: &Result<(), Box<dyn Error>>,
+    ) -> Result<(), Box<dyn Error>> {
+        // Failed loads may stop before Postgres was ever attached
+        self.ensure_postgis_attached()?;
+
+        let report = self.report.borrow().clone();
+        let source_sha256 = match report.source_sha256 {
+            Some(hash) => Some(hash),
+            None => self.hash_source_file()?,
+        };
+        let (outcome, error, rows_loaded) = match result {
+            Ok(()) => ("success", None, report.rows_loaded.to_string()),
+            Err(e) => (
+                "failure",
+                Some(self.redact(&e.to_string())),
+                "NULL".to_string(),
+            ),
+        };
+
+        let history_table = naming::qualified_name(history_schema, LOAD_HISTORY_TABLE);
+        let history_sql = format!(
+            "CREATE SCHEMA IF NOT EXISTS {schema};
+            CREATE TABLE IF NOT EXISTS {table} (
+                load_id bigserial PRIMARY KEY,
+                loaded_at timestamptz NOT NULL DEFAULT now(),
+                target_schema text NOT NULL,
+                target_table text NOT NULL,
+                source_file text NOT NULL,
+                file_type text NOT NULL,
+                source_sha256 text,
+                content_checksum text,
+                rows_loaded bigint,
+                duration_ms bigint NOT NULL,
+                crate_version text NOT NULL,
+                outcome text NOT NULL,
+                error text
+            );
+            INSERT INTO {table} (
+                target_schema, target_table, source_file, file_type, source_sha256,
+                content_checksum, rows_loaded, duration_ms, crate_version, outcome, error
+            ) VALUES ({}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {});",
+            sql::literal(&self.schema_name),
+            sql::literal(&self.table_name),
+            sql::literal(&self.source_name()),
+            sql::literal(&format!("{:?}", self.file_type)),
+            sql_optional_literal(source_sha256.as_deref()),
+            sql_optional_literal(report.content_checksum.as_deref()),
+            rows_loaded,
+            duration.as_millis(),
+            sql::literal(&report.crate_version),
+            sql::literal(outcome),
+            sql_optional_literal(error.as_deref()),
+            schema = naming::quote_identifier(history_schema),
+            table = history_table,
+        );
+        self.postgres_execute("record load history", &history_sql)?;
+        log_info!("Load recorded in {}", history_table);
+        Ok(())
+    }
+}
+
+// Read the most recent loads from the history table, newest first
+// Pass a table name to only return loads into that table
+pub fn query_load_history(
+    postgis_uri: &str,
+    history_schema: &str,
+    table_name: Option<&str>,
+    limit: usize,
+) -> Result<Vec<LoadHistoryEntry>, io::Error> {
+    let connection = credentials::resolve_target_connection(postgis_uri);
+    read_load_history(&connection, history_schema, table_name, limit).map_err(|e| {
+        io::Error::other(format!(
+            "Error querying load history in schema '{}': {}",
+            history_schema,
+            credentials::redact(&e.to_string(), &connection)
+        ))
+    })
+}
+
+fn read_load_history(
+    postgis_uri: &str,
+    history_schema: &str,
+    table_name: Option<&str>,
+    limit: usize,
+) -> Result<Vec<LoadHistoryEntry>, Box<dyn Error>> {
+    connection::validate_connection(postgis_uri)?;
+    let conn = open_connection()?;
+    let attach_alias = unique_attach_alias();
+    conn.execute(
+        &format!(
+            "ATTACH '{}' AS {} (TYPE POSTGRES, READ_ONLY)",
+            postgis_uri.replace('\'', "''"),
+            attach_alias
+        ),
+        [],
+    )?;
+
+    let filter = table_name
+        .map(|table| format!("WHERE target_table = {}", sql::literal(table)))
+        .unwrap_or_default();
+    let history_sql = format!(
+        "SELECT load_id, loaded_at::text, target_schema, target_table, source_file, file_type,
+            source_sha256, content_checksum, rows_loaded, duration_ms, crate_version, outcome, error
+        FROM {} {} ORDER BY load_id DESC LIMIT {}",
+        naming::qualified_name(history_schema, LOAD_HISTORY_TABLE),
+        filter,
+        limit
+    );
+    let mut stmt = conn.prepare(&format!(
+        "SELECT * FROM {};",
+        sql::postgres_query(&attach_alias, &history_sql)
+    ))?;
+    let entries = stmt
+        .query_map([], |row| {
+            Ok(LoadHistoryEntry {
+                load_id: row.get(0)?,
+                loaded_at: row.get(1)?,
+                target_schema: row.get(2)?,
+                target_table: row.get(3)?,
+                source_file: row.get(4)?,
+                file_type: row.get(5)?,
+                source_sha256: row.get(6)?,
+                content_checksum: row.get(7)?,
+                rows_loaded: row.get(8)?,
+                duration_ms: row.get(9)?,
+                crate_version: row.get(10)?,
+                outcome: row.get(11)?,
+                error: row.get(12)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(entries)
+}
+
+fn sql_optional_literal(value: Option<&str>) -> String {
+    value.map_or_else(|| "NULL".to_string(), sql::literal)
+}
@@ -0,0 +1,209 @@
+use std::error::Error;
+
+use super::logging::log_info;
+use super::{naming, DuckDBFileProcessor, IncrementalChanges, IncrementalKey};
+
+impl DuckDBFileProcessor {
+    // Apply only the differences between staging and the existing table, then drop staging.
+    // Returns false when there is no existing table or its schema changed, so the caller swaps
+    // staging in as a full load instead
+    pub(super) fn apply_incremental(
+        &self,
+        staging_table: &str,
+        key: &IncrementalKey,
+    ) -> Result<bool, Box<dyn Error>> {
+        if self.postgres_columns(&self.table_name)?.is_empty() {
+            return Ok(false);
+        }
+        if self.report.borrow().schema_changes.is_some() {
+            log_info!(
+                "Schema of {} changed, replacing it in full",
+                self.table_name
+            );
+            return Ok(false);
+        }
+
+        let columns: Vec<String> = self
+            .postgres_columns(&self.staging_name())?
+            .into_iter()
+            .map(|(column, _)| column)
+            .collect();
+        let (matches, values) = key_matches(key, &columns)?;
+
+        let target_table = naming::qualified_name(&self.schema_name, &self.table_name);
+        let deleted_sql = format!(
+            "FROM {} t WHERE NOT EXISTS (SELECT 1 FROM {} s WHERE {})",
+            target_table, staging_table, matches
+        );
+        let inserted_sql = format!(
+            "FROM {} s WHERE NOT EXISTS (SELECT 1 FROM {} t WHERE {})",
+            staging_table, target_table, matches
+        );
+        let updated_sql = format!(
+            "FROM {} s WHERE {} AND ({}) IS DISTINCT FROM ({})",
+            staging_table,
+            matches,
+            row("t", &values),
+            row("s", &values)
+        );
+
+        let count = |sql: &str| -> Result<u64, Box<dyn Error>> {
+            let count: Option<i64> = self.query_postgres(sql)?;
+            Ok(count.unwrap_or(0) as u64)
+        };
+        let changes = IncrementalChanges {
+            inserted: count(&format!("SELECT count(*) {}", inserted_sql))?,
+            updated: if values.is_empty() {
+                0
+            } else {
+                count(&format!(
+                    "SELECT count(*) FROM {} t JOIN {} s ON {} WHERE ({}) IS DISTINCT FROM ({})",
+                    target_table,
+                    staging_table,
+                    matches,
+                    row("t", &values),
+                    row("s", &values)
+                ))?
+            },
+            deleted: count(&format!("SELECT count(*) {}", deleted_sql))?,
+        };
+
+        let update_sql = if values.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "UPDATE {} t SET {} {};",
+                target_table,
+                values
+                    .iter()
+                    .map(|column| format!("{0} = s.{0}", naming::quote_identifier(column)))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                updated_sql
+            )
+        };
+        let all_columns: Vec<&String> = columns.iter().collect();
+        let apply_sql = format!(
+            "BEGIN;
+            DELETE {};
+            {}
+            INSERT INTO {} ({}) SELECT {} {};
+            DROP TABLE {};
+            COMMIT;",
+            deleted_sql,
+            update_sql,
+            target_table,
+            columns
+                .iter()
+                .map(|column| naming::quote_identifier(column))
+                .collect::<Vec<_>>()
+                .join(", "),
+            row("s", &all_columns),
+            inserted_sql,
+            staging_table
+        );
+        self.postgres_execute("apply incremental changes", &apply_sql)?;
+
+        log_info!(
+            "Incremental load of {}: {} inserted, {} updated, {} deleted",
+            self.table_name,
+            changes.inserted,
+            changes.updated,
+            changes.deleted
+        );
+        self.report.borrow_mut().incremental_changes = Some(changes);
+        Ok(true)
+    }
+}
+
+// Condition matching a target row t to a staging row s, on the key columns or on a hash of every
+// column, and the columns compared to find updated rows
+fn key_matches<'a>(
+    key: &IncrementalKey,
+    columns: &'a [String],
+) -> Result<(String, Vec<&'a String>), Box<dyn Error>> {
+    match key {
+        IncrementalKey::Columns(keys) => {
+            if keys.is_empty() {
+                return Err("Incremental loads need at least one key column".into());
+            }
+            if let Some(missing) = keys.iter().find(|key| !columns.contains(key)) {
+                return Err(format!(
+                    "Incremental key column {} is not in the loaded data",
+                    missing
+                )
+                .into());
+            }
+            let matches = keys
+                .iter()
+                .map(|key| format!("t.{0} = s.{0}", naming::quote_identifier(key)))
+                .collect::<Vec<_>>()
+                .join(" AND ");
+            let values = columns
+                .iter()
+                .filter(|column| !keys.contains(column))
+                .collect();
+            Ok((matches, values))
+        }
+        IncrementalKey::RowHash => {
+            let all: Vec<&String> = columns.iter().collect();
+            let matches = format!(
+                "md5(ROW({})::text) = md5(ROW({})::text)",
+                row("t", &all),
+                row("s", &all)
+            );
+            Ok((matches, Vec::new()))
+        }
+    }
+}
+
+// Columns of a row, qualified by the table alias
+fn row(alias: &str, columns: &[&String]) -> String {
+    columns
+        .iter()
+        .map(|column| format!("{}.{}", alias, naming::quote_identifier(column)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns() -> Vec<String> {
+        ["id", "region", "name", "geom"].map(String::from).to_vec()
+    }
+
+    #[test]
+    fn matches_rows_on_key_columns() {
+        let columns = columns();
+        let key = IncrementalKey::Columns(vec!["id".to_string(), "region".to_string()]);
+        let (matches, values) = key_matches(&key, &columns).unwrap();
+        assert_eq!(matches, r#"t."id" = s."id" AND t."region" = s."region""#);
+        assert_eq!(values, [&columns[2], &columns[3]]);
+        assert_eq!(row("s", &values), r#"s."name", s."geom""#);
+    }
+
+    #[test]
+    fn matches_rows_on_a_hash_of_every_column() {
+        let columns = columns();
+        let (matches, values) = key_matches(&IncrementalKey::RowHash, &columns).unwrap();
+        assert_eq!(
+            matches,
+            r#"md5(ROW(t."id", t."region", t."name", t."geom")::text) = "#.to_string()
+                + r#"md5(ROW(s."id", s."region", s."name", s."geom")::text)"#
+        );
+        assert!(values.is_empty());
+    }
+
+    #[test]
+    fn rejects_missing_or_empty_keys() {
+        let columns = columns();
+        let error = key_matches(&IncrementalKey::Columns(Vec::new()), &columns).unwrap_err();
+        assert!(error.to_string().contains("at least one key column"));
+
+        let key = IncrementalKey::Columns(vec!["ID".to_string()]);
+        let error = key_matches(&key, &columns).unwrap_err();
+        assert!(error.to_string().contains("ID is not in the loaded data"));
+    }
+}
@@ -0,0 +1,260 @@
+use std::error::Error;
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::{naming, sql, xls, DuckDBFileProcessor, FileType, ProcessorOptions};
+
+// Summary of a file, produced without loading anything into Postgres
+#[derive(Debug, Clone)]
+pub struct FileInspection {
+    pub file_type: String,
+    pub layers: Vec<LayerInfo>,
+    pub columns: Vec<ColumnInfo>,
+    pub crs: Option<CrsInfo>,
+    pub feature_count: u64,
+    pub extent: Option<Extent>,
+    pub suggested_table_name: String,
+}
+
+// A layer (or sheet) reported by GDAL
+#[derive(Debug, Clone)]
+pub struct LayerInfo {
+    pub name: String,
+    pub feature_count: Option<i64>,
+    pub geometry_columns: Vec<String>,
+    // EPSG code declared for the layer's first geometry column
+    pub crs: Option<String>,
+    // Attribute fields, with their GDAL field types
+    pub fields: Vec<ColumnInfo>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ColumnInfo {
+    pub name: String,
+    pub data_type: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct CrsInfo {
+    // EPSG code, e.g. "27700"
+    pub code: String,
+    pub confidence: CrsConfidence,
+}
+
+// How the CRS was determined
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CrsConfidence {
+    // Declared by the file itself
+    Declared,
+    // Guessed from the coordinate ranges because the file declares none
+    Inferred,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Extent {
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+}
+
+pub fn inspect_file(file_path: &str) -> Result<FileInspection, io::Error> {
+    read_inspection(file_path)
+        .map_err(|e| io::Error::other(format!("Error inspecting file '{}': {}", file_path, e)))
+}
+
+// List the layers (or sheets) of a file from its metadata alone, without reading any features,
+// so a layer can be picked before loading
+pub fn list_layers(file_path: &str) -> Result<Vec<LayerInfo>, io::Error> {
+    DuckDBFileProcessor::new_file(file_path, "", "", "", ProcessorOptions::default())
+        .and_then(|processor| processor.get_layers())
+        .map_err(|e| io::Error::other(format!("Error listing layers of '{}': {}", file_path, e)))
+}
+
+fn read_inspection(file_path: &str) -> Result<FileInspection, Box<dyn Error>> {
+    // Inspection never reaches Postgres, so no target is needed
+    let processor =
+        DuckDBFileProcessor::new_file(file_path, "", "", "", ProcessorOptions::default())?;
+    processor.create_data_table()?;
+
+    let columns = processor.get_column_info("data")?;
+    let feature_count: i64 = processor
+        .conn
+        .query_row("SELECT count(*) FROM data;", [], |row| row.get(0))?;
+    let layers = processor.get_layers()?;
+    let geometry_column = columns
+        .iter()
+        .find(|column| column.data_type == "GEOMETRY")
+        .map(|column| column.name.clone());
+    let extent = match &geometry_column {
+        Some(column) => processor.get_extent(column)?,
+        None => None,
+    };
+    let crs = match &geometry_column {
+        Some(_) => processor
+            .get_declared_crs()?
+            .or_else(|| extent.and_then(infer_crs)),
+        None => None,
+    };
+
+    let stem = Path::new(file_path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    Ok(FileInspection {
+        file_type: format!("{:?}", processor.file_type),
+        layers,
+        columns,
+        crs,
+        feature_count: feature_count as u64,
+        extent,
+        suggested_table_name: naming::sanitize_identifier(&stem),
+    })
+}
+
+// Whether a file of this type is read with GDAL, and so has layers st_read_meta can describe
+pub(super) fn is_gdal_format(file_type: &FileType, file_path: &str) -> bool {
+    match file_type {
+        FileType::Geopackage
+        | FileType::Shapefile
+        | FileType::Geojson
+        | FileType::Ods
+        | FileType::Dxf => true,
+        // Legacy .xls workbooks are read without GDAL
+        FileType::Excel => !xls::is_xls(file_path),
+        _ => false,
+    }
+}
+
+// Guess a CRS from coordinate ranges - only used when the file declares none
+pub(super) fn infer_crs(extent: Extent) -> Option<CrsInfo> {
+    let code = if extent.min_x >= -180.0
+        && extent.max_x <= 180.0
+        && extent.min_y >= -90.0
+        && extent.max_y <= 90.0
+    {
+        "4326"
+    } else if extent.min_x >= 0.0
+        && extent.max_x <= 700_000.0
+        && extent.min_y >= 0.0
+        && extent.max_y <= 1_300_000.0
+    {
+        // British National Grid bounds
+        "27700"
+    } else {
+        return None;
+    };
+    Some(CrsInfo {
+        code: code.to_string(),
+        confidence: CrsConfidence::Inferred,
+    })
+}
+
+impl DuckDBFileProcessor {
+    fn get_column_info(&self, table: &str) -> Result<Vec<ColumnInfo>, Box<dyn Error>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT column_name, data_type FROM information_schema.columns
+            WHERE table_name = ? ORDER BY ordinal_position",
+        )?;
+        let columns = stmt
+            .query_map([table], |row| {
+                Ok(ColumnInfo {
+                    name: row.get(0)?,
+                    data_type: row.get(1)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(columns)
+    }
+
+    pub(super) fn is_gdal_format(&self) -> bool {
+        is_gdal_format(&self.file_type, &self.file_path)
+    }
+
+    fn get_layers(&self) -> Result<Vec<LayerInfo>, Box<dyn Error>> {
+        // Only GDAL backed formats have layers
+        if !self.is_gdal_format() {
+            return Ok(Vec::new());
+        }
+
+        let query = format!(
+            "SELECT layer.name, layer.feature_count,
+                array_to_string(list_transform(layer.geometry_fields, field -> field.name), ','),
+                layer.geometry_fields[1].crs.auth_code,
+                to_json(list_transform(layer.fields, field -> [field.name, field.type]))::VARCHAR
+            FROM (SELECT unnest(layers) AS layer FROM st_read_meta({}));",
+            sql::string_literal(&self.file_path)
+        );
+        let mut stmt = self.conn.prepare(&query)?;
+        let rows = stmt
+            .query_map([], |row| {
+                let geometry_columns: Option<String> = row.get(2)?;
+                let layer = LayerInfo {
+                    name: row.get(0)?,
+                    feature_count: row.get(1)?,
+                    geometry_columns: geometry_columns
+                        .filter(|names| !names.is_empty())
+                        .map(|names| names.split(',').map(str::to_string).collect())
+                        .unwrap_or_default(),
+                    crs: row.get(3)?,
+                    fields: Vec::new(),
+                };
+                Ok((layer, row.get::<_, Option<String>>(4)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut layers = Vec::new();
+        for (mut layer, fields) in rows {
+            // Fields come back as a JSON list of [name, type] pairs
+            let fields: Vec<(String, String)> = match fields {
+                Some(fields) => serde_json::from_str(&fields)?,
+                None => Vec::new(),
+            };
+            layer.fields = fields
+                .into_iter()
+                .map(|(name, data_type)| ColumnInfo { name, data_type })
+                .collect();
+            layers.push(layer);
+        }
+        Ok(layers)
+    }
+
+    fn get_extent(&self, geometry_column: &str) -> Result<Option<Extent>, Box<dyn Error>> {
+        let query = format!(
+            "SELECT min(ST_XMin({0})), min(ST_YMin({0})), max(ST_XMax({0})), max(ST_YMax({0}))
+            FROM data;",
+            naming::quote_identifier(geometry_column)
+        );
+        let bounds: (Option<f64>, Option<f64>, Option<f64>, Option<f64>) =
+            self.conn.query_row(&query, [], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })?;
+        Ok(match bounds {
+            (Some(min_x), Some(min_y), Some(max_x), Some(max_y)) => Some(Extent {
+                min_x,
+                min_y,
+                max_x,
+                max_y,
+            }),
+            _ => None,
+        })
+    }
+
+    fn get_declared_crs(&self) -> Result<Option<CrsInfo>, Box<dyn Error>> {
+        if !self.is_gdal_format() {
+            return Ok(None);
+        }
+        let query = format!(
+            "SELECT layers[1].geometry_fields[1].crs.auth_code FROM st_read_meta({});",
+            sql::string_literal(&self.file_path)
+        );
+        let code: Option<String> = self.conn.query_row(&query, [], |row| row.get(0))?;
+        Ok(code.map(|code| CrsInfo {
+            code,
+            confidence: CrsConfidence::Declared,
+        }))
+    }
+}
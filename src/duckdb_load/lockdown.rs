@@ -0,0 +1,88 @@
+use std::error::Error;
+
+use super::logging::log_debug;
+use super::{extensions, DuckDBFileProcessor, FileType};
+
+// DuckDB file systems that reach the network. DuckDB only adds to the disabled file systems, so
+// every later setting lists these again
+const REMOTE_FILESYSTEMS: &str = "HTTPFileSystem,S3FileSystem,HuggingFaceFileSystem";
+
+// Locked-down loads only read the local file they are given. URLs, GDAL virtual paths, remote
+// services and Postgres sources would have the loader connect wherever the path says, so they
+// are refused before anything is fetched
+pub(crate) fn check_local_source(file_path: &str) -> Result<(), Box<dyn Error>> {
+    let lowered = file_path.trim_start().to_lowercase();
+    let remote = lowered.contains("://")
+        || lowered.starts_with("/vsi")
+        || ["oapif:", "wfs:", "pg:"]
+            .iter()
+            .any(|prefix| lowered.starts_with(prefix));
+    if remote {
+        return Err("Only local files can be loaded in locked-down mode".into());
+    }
+    Ok(())
+}
+
+impl DuckDBFileProcessor {
+    // Load everything the pipeline needs up front, then stop DuckDB fetching more extensions
+    // or reaching the network while it reads the source
+    pub(super) fn lock_down_extensions(&self) -> Result<(), Box<dyn Error>> {
+        if !self.options.locked_down {
+            return Ok(());
+        }
+        if self.file_type == FileType::Geopackage {
+            extensions::load_extension(&self.conn, "sqlite")?;
+        }
+        self.conn.execute_batch(&format!(
+            "SET autoinstall_known_extensions = false;
+            SET autoload_known_extensions = false;
+            SET allow_community_extensions = false;
+            SET disabled_filesystems = '{}';",
+            REMOTE_FILESYSTEMS
+        ))?;
+        log_debug!("Extension installs and remote file systems disabled");
+        Ok(())
+    }
+
+    // Once the source has been read, block all further local file access and freeze the settings
+    // This also stops INSTALL and LOAD, and hooks from reading or writing other files
+    pub(super) fn lock_down_file_access(&self) -> Result<(), Box<dyn Error>> {
+        if !self.options.locked_down {
+            return Ok(());
+        }
+        self.conn.execute_batch(&format!(
+            "SET disabled_filesystems = 'LocalFileSystem,{}';
+            SET lock_configuration = true;",
+            REMOTE_FILESYSTEMS
+        ))?;
+        log_debug!("Local file access disabled and DuckDB configuration locked");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_local_files() {
+        for path in ["roads.gpkg", "/data/uploads/roads.csv", r"C:\data\o'k.shp"] {
+            assert!(check_local_source(path).is_ok(), "{}", path);
+        }
+    }
+
+    #[test]
+    fn refuses_sources_that_connect_elsewhere() {
+        for path in [
+            "https://example.com/roads.geojson",
+            "s3://bucket/roads.parquet",
+            "/vsicurl/http://example.com/roads.gpkg",
+            "OAPIF:https://example.com/collections/roads",
+            "WFS:https://example.com/wfs?typeNames=roads",
+            "wfs://example.com/wfs?typeNames=roads",
+            "PG:host=db dbname=gis|public.roads",
+        ] {
+            assert!(check_local_source(path).is_err(), "{}", path);
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+#[cfg(not(feature = "tracing"))]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// How much loads print, set for the whole process with set_verbosity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Verbosity {
+    // Print nothing - errors are still returned
+    Quiet,
+    // Only problems a load worked around, such as a connection retry or a temporary file it
+    // couldn't remove, and loads that failed
+    Warn,
+    // One line per step, such as the detected file type and rows written
+    #[default]
+    Info,
+    // Also schemas, column mappings and CRS details, for troubleshooting
+    Debug,
+}
+
+// Format of the lines loads print, set for the whole process with set_log_format
+// With the tracing feature, events go to the tracing subscriber instead and this is ignored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    // One JSON object per line, with time (Unix seconds), level, event (message, stage or
+    // error), stage for stage events, and message
+    Json,
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Info as u8);
+static JSON_FORMAT: AtomicBool = AtomicBool::new(false);
+
+pub fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+pub fn verbosity() -> Verbosity {
+    match VERBOSITY.load(Ordering::Relaxed) {
+        0 => Verbosity::Quiet,
+        1 => Verbosity::Warn,
+        2 => Verbosity::Info,
+        _ => Verbosity::Debug,
+    }
+}
+
+pub fn set_log_format(format: LogFormat) {
+    JSON_FORMAT.store(format == LogFormat::Json, Ordering::Relaxed);
+}
+
+// Kind of event a log line records
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Event<'a> {
+    Message,
+    // A load entering detection, transform or write
+    Stage(&'a str),
+    // A load that failed, with the error it returned
+    Error,
+}
+
+// Log a message at a verbosity level
+pub(crate) fn emit(level: Verbosity, event: Event, message: fmt::Arguments) {
+    if level == Verbosity::Quiet || verbosity() < level {
+        return;
+    }
+    let label = match (event, level) {
+        (Event::Error, _) => "error",
+        (_, Verbosity::Warn) => "warn",
+        (_, Verbosity::Debug) => "debug",
+        _ => "info",
+    };
+    let (kind, stage) = match event {
+        Event::Message => ("message", None),
+        Event::Stage(stage) => ("stage", Some(stage)),
+        Event::Error => ("error", None),
+    };
+
+    write_event(label, kind, stage, message);
+}
+
+#[cfg(feature = "tracing")]
+fn write_event(label: &str, kind: &str, stage: Option<&str>, message: fmt::Arguments) {
+    match label {
+        "error" => tracing::error!(event = kind, stage, "{}", message),
+        "warn" => tracing::warn!(event = kind, stage, "{}", message),
+        "debug" => tracing::debug!(event = kind, stage, "{}", message),
+        _ => tracing::info!(event = kind, stage, "{}", message),
+    }
+}
+
+// Warnings and errors go to stderr and the rest to stdout, so a caller piping stdout still sees
+// problems
+#[cfg(not(feature = "tracing"))]
+fn write_event(label: &str, kind: &str, stage: Option<&str>, message: fmt::Arguments) {
+    let to_stderr = matches!(label, "warn" | "error");
+    if !JSON_FORMAT.load(Ordering::Relaxed) {
+        if to_stderr {
+            eprintln!("{}", message);
+        } else {
+            println!("{}", message);
+        }
+        return;
+    }
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |time| time.as_secs_f64());
+    let mut line = serde_json::json!({
+        "time": time,
+        "level": label,
+        "event": kind,
+        "message": message.to_string(),
+    });
+    if let Some(stage) = stage {
+        line["stage"] = stage.into();
+    }
+    if to_stderr {
+        eprintln!("{}", line);
+    } else {
+        println!("{}", line);
+    }
+}
+
+// println! at a verbosity level, or eprintln! for warnings
+macro_rules! log_at {
+    ($level:ident, $($arg:tt)*) => {
+        $crate::duckdb_load::logging::emit(
+            $crate::duckdb_load::Verbosity::$level,
+            $crate::duckdb_load::logging::Event::Message,
+            format_args!($($arg)*),
+        )
+    };
+}
+
+macro_rules! log_warn {
+    ($($arg:tt)*) => { $crate::duckdb_load::logging::log_at!(Warn, $($arg)*) };
+}
+
+macro_rules! log_info {
+    ($($arg:tt)*) => { $crate::duckdb_load::logging::log_at!(Info, $($arg)*) };
+}
+
+macro_rules! log_debug {
+    ($($arg:tt)*) => { $crate::duckdb_load::logging::log_at!(Debug, $($arg)*) };
+}
+
+pub(crate) use {log_at, log_debug, log_info, log_warn};
@@ -0,0 +1,48 @@
+use postgres::{Client, NoTls};
+use std::error::Error;
+
+use super::logging::{log_info, log_warn};
+use super::{naming, DuckDBFileProcessor, Maintenance};
+
+impl DuckDBFileProcessor {
+    // Gather planner statistics on the loaded table so the first queries plan well
+    pub(super) fn run_maintenance(&self) -> Result<(), Box<dyn Error>> {
+        if self.options.maintenance == Maintenance::None {
+            return Ok(());
+        }
+        // A versioned table is a view, so its statistics live on the version table
+        let table = self
+            .report
+            .borrow()
+            .version_table
+            .clone()
+            .unwrap_or_else(|| self.table_name.clone());
+        let qualified_table = naming::qualified_name(&self.schema_name, &table);
+
+        if self.options.maintenance == Maintenance::VacuumAnalyze {
+            match self.vacuum_analyze(&qualified_table) {
+                Ok(()) => {
+                    log_info!("VACUUM (ANALYZE) completed on {}", table);
+                    return Ok(());
+                }
+                // The table is already swapped in, so only its statistics are at stake
+                Err(e) => log_warn!(
+                    "VACUUM could not run on {}, so it is only analyzed: {}",
+                    table,
+                    self.redact(&e.to_string())
+                ),
+            }
+        }
+        self.postgres_execute("analyze", &format!("ANALYZE {};", qualified_table))?;
+        log_info!("ANALYZE completed on {}", table);
+        Ok(())
+    }
+
+    // VACUUM can't run inside the transaction the postgres extension holds open, so it needs
+    // its own connection - which has no TLS and takes only the options the postgres crate knows
+    fn vacuum_analyze(&self, qualified_table: &str) -> Result<(), Box<dyn Error>> {
+        let mut client = Client::connect(&self.postgis_uri, NoTls)?;
+        client.batch_execute(&format!("VACUUM (ANALYZE) {};", qualified_table))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,137 @@
+use duckdb::Connection;
+use std::error::Error;
+use std::io;
+
+use super::logging::{log_info, log_warn};
+use super::shared;
+use super::{
+    credentials, open_connection, run_load, run_processor, unique_attach_alias,
+    DuckDBFileProcessor, LoadReport, MixedGeometry, ProcessorOptions,
+};
+
+// One dataset of a manifest load
+#[derive(Debug, Clone)]
+pub struct ManifestEntry {
+    pub path: String,
+    pub table: String,
+    pub schema: String,
+    pub options: ProcessorOptions,
+}
+
+// Outcome of one manifest entry, with the error message when it failed
+#[derive(Debug)]
+pub struct ManifestLoad {
+    pub path: String,
+    pub table: String,
+    pub schema: String,
+    pub result: Result<LoadReport, String>,
+}
+
+// Aggregate report of a manifest load
+#[derive(Debug, Default)]
+pub struct ManifestReport {
+    pub loads: Vec<ManifestLoad>,
+    // Rows written across all successful loads
+    pub rows_loaded: u64,
+    pub failed: usize,
+}
+
+// Load every manifest entry in one session, in order. A failed entry is recorded in the report
+// and the remaining entries still load
+pub fn launch_manifest(
+    postgis_uri: &str,
+    entries: Vec<ManifestEntry>,
+) -> Result<ManifestReport, io::Error> {
+    // One DuckDB database, with its extensions and PostGIS attachment, serves every entry
+    let conn = open_connection()
+        .map_err(|e| io::Error::other(format!("Error opening DuckDB for the manifest: {}", e)))?;
+    let attach_alias = unique_attach_alias();
+
+    let mut report = ManifestReport::default();
+    let total = entries.len();
+    for (index, entry) in entries.into_iter().enumerate() {
+        log_info!(
+            "Manifest load {} of {}: '{}' into {}.{}",
+            index + 1,
+            total,
+            entry.path,
+            entry.schema,
+            entry.table
+        );
+        let result = load_entry(&conn, &attach_alias, postgis_uri, &entry);
+        reset_connection(&conn, &attach_alias).map_err(|e| {
+            io::Error::other(format!(
+                "Error resetting DuckDB between manifest loads: {}",
+                e
+            ))
+        })?;
+
+        match &result {
+            Ok(load) => report.rows_loaded += load.rows_loaded,
+            Err(e) => {
+                log_warn!("Manifest load of '{}' failed: {}", entry.path, e);
+                report.failed += 1;
+            }
+        }
+        report.loads.push(ManifestLoad {
+            path: entry.path,
+            table: entry.table,
+            schema: entry.schema,
+            result: result.map_err(|e| e.to_string()),
+        });
+    }
+    Ok(report)
+}
+
+fn load_entry(
+    conn: &Connection,
+    attach_alias: &str,
+    postgis_uri: &str,
+    entry: &ManifestEntry,
+) -> Result<LoadReport, io::Error> {
+    let mut options = entry.options.clone();
+    // Lockdown changes the database configuration and separate tables open their own
+    // processors, so those entries get a database of their own
+    if options.locked_down || options.mixed_geometry == MixedGeometry::SeparateTables {
+        return run_load(
+            &entry.path,
+            &entry.table,
+            postgis_uri,
+            &entry.schema,
+            options,
+            None,
+        );
+    }
+    options
+        .attach_alias
+        .get_or_insert_with(|| attach_alias.to_string());
+
+    let shared = conn.try_clone().map_err(io::Error::other)?;
+    let processor = DuckDBFileProcessor::new_with_connection(
+        Some(shared),
+        &entry.path,
+        &entry.table,
+        postgis_uri,
+        &entry.schema,
+        options,
+    )
+    .map_err(|e| {
+        io::Error::other(format!(
+            "Error creating FileProcessor for '{}': {}",
+            entry.path,
+            credentials::redact(&e.to_string(), postgis_uri)
+        ))
+    })?;
+    run_processor(processor, &entry.path, None)
+}
+
+// Drop the tables, views and attachments a load left in the shared database, keeping the
+// PostGIS attachment, so the next entry starts clean
+fn reset_connection(conn: &Connection, attach_alias: &str) -> Result<(), Box<dyn Error>> {
+    let statements = shared::drop_statements(conn)?
+        .into_iter()
+        .filter(|statement| *statement != shared::detach_statement(attach_alias))
+        .collect::<Vec<_>>();
+    conn.execute_batch(&statements.join("\n"))?;
+    Ok(())
+}
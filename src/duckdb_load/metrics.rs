@@ -0,0 +1,166 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use super::{DuckDBFileProcessor, FileType, LoadStage};
+
+// Upper bounds of the stage duration histogram buckets, in seconds
+const DURATION_BUCKETS: [f64; 10] = [0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0, 900.0, 3600.0];
+
+// Load counters for the whole process, exported in the Prometheus text format
+#[derive(Default)]
+struct Metrics {
+    // Successful loads by file type
+    files_processed: BTreeMap<String, u64>,
+    rows_loaded: u64,
+    // Failed loads by category: cancelled, timeout, or the stage that failed
+    failures: BTreeMap<&'static str, u64>,
+    stage_durations: BTreeMap<&'static str, Histogram>,
+}
+
+#[derive(Default)]
+struct Histogram {
+    // Observations at or below each bucket's bound
+    buckets: [u64; DURATION_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+fn metrics() -> MutexGuard<'static, Metrics> {
+    static METRICS: OnceLock<Mutex<Metrics>> = OnceLock::new();
+    METRICS
+        .get_or_init(Mutex::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+pub(crate) fn record_success(file_type: &FileType, rows_loaded: u64) {
+    let file_type = match file_type {
+        FileType::Custom(name) => name.to_lowercase(),
+        file_type => format!("{:?}", file_type).to_lowercase(),
+    };
+    let mut metrics = metrics();
+    *metrics.files_processed.entry(file_type).or_default() += 1;
+    metrics.rows_loaded += rows_loaded;
+}
+
+pub(crate) fn record_failure(category: &'static str) {
+    *metrics().failures.entry(category).or_default() += 1;
+}
+
+pub(crate) fn record_stage_duration(stage: LoadStage, duration: Duration) {
+    let seconds = duration.as_secs_f64();
+    let mut metrics = metrics();
+    let histogram = metrics.stage_durations.entry(stage.name()).or_default();
+    for (count, bound) in histogram.buckets.iter_mut().zip(DURATION_BUCKETS) {
+        if seconds <= bound {
+            *count += 1;
+        }
+    }
+    histogram.count += 1;
+    histogram.sum += seconds;
+}
+
+// Load metrics in the Prometheus text exposition format
+pub fn render_metrics() -> String {
+    let metrics = metrics();
+    let mut text = String::new();
+
+    text.push_str("# HELP duckdb_postgis_files_processed_total Files loaded successfully.\n");
+    text.push_str("# TYPE duckdb_postgis_files_processed_total counter\n");
+    for (file_type, count) in &metrics.files_processed {
+        let _ = writeln!(
+            text,
+            "duckdb_postgis_files_processed_total{{file_type=\"{}\"}} {}",
+            file_type, count
+        );
+    }
+
+    text.push_str("# HELP duckdb_postgis_rows_loaded_total Rows written by successful loads.\n");
+    text.push_str("# TYPE duckdb_postgis_rows_loaded_total counter\n");
+    let _ = writeln!(
+        text,
+        "duckdb_postgis_rows_loaded_total {}",
+        metrics.rows_loaded
+    );
+
+    text.push_str("# HELP duckdb_postgis_load_failures_total Failed loads by category.\n");
+    text.push_str("# TYPE duckdb_postgis_load_failures_total counter\n");
+    for (category, count) in &metrics.failures {
+        let _ = writeln!(
+            text,
+            "duckdb_postgis_load_failures_total{{category=\"{}\"}} {}",
+            category, count
+        );
+    }
+
+    text.push_str("# HELP duckdb_postgis_stage_duration_seconds Time spent in each load stage.\n");
+    text.push_str("# TYPE duckdb_postgis_stage_duration_seconds histogram\n");
+    for (stage, histogram) in &metrics.stage_durations {
+        for (count, bound) in histogram.buckets.iter().zip(DURATION_BUCKETS) {
+            let _ = writeln!(
+                text,
+                "duckdb_postgis_stage_duration_seconds_bucket{{stage=\"{}\",le=\"{}\"}} {}",
+                stage, bound, count
+            );
+        }
+        let _ = writeln!(
+            text,
+            "duckdb_postgis_stage_duration_seconds_bucket{{stage=\"{}\",le=\"+Inf\"}} {}",
+            stage, histogram.count
+        );
+        let _ = writeln!(
+            text,
+            "duckdb_postgis_stage_duration_seconds_sum{{stage=\"{}\"}} {}",
+            stage, histogram.sum
+        );
+        let _ = writeln!(
+            text,
+            "duckdb_postgis_stage_duration_seconds_count{{stage=\"{}\"}} {}",
+            stage, histogram.count
+        );
+    }
+    text
+}
+
+// Serve render_metrics on addr from a background thread, answering every request with the
+// metrics - for batch and watch-folder processes without their own HTTP server
+pub fn spawn_metrics_exporter(addr: SocketAddr) -> Result<JoinHandle<()>, io::Error> {
+    let listener = TcpListener::bind(addr)?;
+    Ok(thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            // Read the request head, then answer whatever was asked for
+            let mut reader = BufReader::new(&stream);
+            let mut line = String::new();
+            while reader.read_line(&mut line).is_ok_and(|read| read > 2) {
+                line.clear();
+            }
+            let body = render_metrics();
+            let mut writer = &stream;
+            let _ = write!(
+                writer,
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\n\
+                 Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+        }
+    }))
+}
+
+impl DuckDBFileProcessor {
+    // Record how long the current stage has run, as it ends
+    pub(super) fn record_stage_time(&self) {
+        let (stage, started) = self.stage.get();
+        let duration = started.elapsed();
+        record_stage_duration(stage, duration);
+        self.report
+            .borrow_mut()
+            .stage_seconds
+            .push((stage.name().to_string(), duration.as_secs_f64()));
+    }
+}
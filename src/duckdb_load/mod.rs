@@ -1,22 +1,171 @@
 use duckdb::arrow::datatypes::Schema;
 use duckdb::Connection;
+use sha2::{Digest, Sha256};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::error::Error;
-use std::fs::File;
-use std::io::{self, Read, Seek};
-use std::sync::Arc;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Seek};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use archive::ZipExtract;
+use logging::{log_debug, log_info, log_warn, Event};
+use postgres_source::PostgresSource;
+use remote::RemoteSource;
+
+mod access;
+mod antimeridian;
+mod archive;
+mod bench;
+mod cancel;
+mod config;
+mod connection;
+mod copy;
+mod credentials;
+mod crs;
+mod csv;
+mod csv_rejects;
+mod detect;
+mod duplicates;
+mod dxf;
+mod empty_geometry;
+mod estimate;
+mod excel;
+mod extensions;
+mod file_output;
+mod geometry_types;
+mod history;
+mod incremental;
+mod inspect;
+mod lockdown;
+mod logging;
+mod maintenance;
+mod manifest;
+mod metrics;
+mod naming;
+mod options;
+mod osm;
+mod partition;
+mod postgres_source;
+mod preflight;
+mod preview;
+mod profiles;
+mod profiling;
+mod progress;
+mod queue;
+mod raster;
+mod reader;
+mod remote;
+mod report;
+mod schema_drift;
+mod shapefile;
+mod shared;
+mod sql;
+mod summary;
+mod throttle;
+mod tiles;
+mod timeouts;
+mod versions;
+#[cfg(feature = "watch")]
+mod watch;
+mod wkt;
+mod xls;
+pub use bench::{run_bench, BenchReport, BenchRun};
+pub use cancel::{CancellationToken, LoadCancelled};
+pub use config::{ExtensionConfig, LoadConfig};
+pub use connection::PgConnectionConfig;
+pub use detect::{
+    determine_file_type, determine_file_type_from_bytes, register_file_detector, FileDetector,
+};
+pub use estimate::{estimate_rows, RowEstimate};
+pub use extensions::{
+    extension_directory, pin_extension, set_extension_directory, set_extension_repository,
+    ExtensionPin, EXTENSION_DIRECTORY_VARIABLE,
+};
+pub use history::{query_load_history, LoadHistoryEntry, LOAD_HISTORY_TABLE};
+pub use inspect::{
+    inspect_file, list_layers, ColumnInfo, CrsConfidence, CrsInfo, Extent, FileInspection,
+    LayerInfo,
+};
+pub use logging::{set_log_format, set_verbosity, verbosity, LogFormat, Verbosity};
+pub use manifest::{launch_manifest, ManifestEntry, ManifestLoad, ManifestReport};
+pub use metrics::{render_metrics, spawn_metrics_exporter};
+#[cfg(feature = "server")]
+pub(crate) use lockdown::check_local_source;
+#[cfg(any(feature = "http", feature = "server"))]
+pub(crate) use naming::validate_identifier;
+pub use options::{
+    Antimeridian, ColumnNaming, CoordinateStorage, CsvOptions, DuplicateColumns, DxfGeometry,
+    DxfOptions, EmptyGeometry, ExcelDateSystem, ExcelOptions, FilePartition, GeometryFamily,
+    IncrementalKey, Maintenance, MixedGeometry, OsmOptions, OutputTarget, ProcessorOptions,
+    RasterLoad, SchemaDrift, Timeouts, VectorTiles, WriteMethod,
+};
+pub use preview::{preview, preview_json, preview_schema, SchemaColumn};
+pub use profiles::{get_profile, register_profile, OptionProfile};
+pub use profiling::{OperatorProfile, QueryProfile};
+pub use progress::{LoadProgress, LoadStage};
+pub use queue::{JobState, LoadQueue, QueuedJob};
+pub use reader::launch_process_reader;
+pub use shared::launch_process_file_with_connection;
+pub use report::{IncrementalChanges, LoadReport, SchemaChanges};
+pub use throttle::WriteThrottle;
+pub use versions::rollback_table_version;
+#[cfg(feature = "watch")]
+pub use watch::watch_folder;
 
 // Enum that represents potential FileTypes
 // More will be added in the future
-#[derive(Debug, PartialEq)]
-enum FileType {
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileType {
     Geopackage,
     Shapefile,
     Geojson,
     Excel,
+    // OpenDocument spreadsheet, read as Excel workbooks are
+    Ods,
     Csv,
     Parquet,
+    OsmPbf,
+    Dxf,
+    GeoTiff,
+    Orc,
+    // A table or query in another Postgres database
+    Postgres,
+    // A format matched by a registered FileDetector, by name
+    Custom(String),
+}
+
+// Open an in-memory DuckDB connection with the required extensions loaded
+fn open_connection() -> Result<Connection, Box<dyn Error>> {
+    let conn = Connection::open(":memory:")?;
+    extensions::load_extension(&conn, "spatial")?;
+    extensions::load_extension(&conn, "postgres")?;
+    Ok(conn)
+}
+
+// Counter making the attach alias of each processor in a process unique
+static ATTACH_ALIASES: AtomicUsize = AtomicUsize::new(0);
+
+// Attach alias for a connection to the target database, unless one is configured
+fn unique_attach_alias() -> String {
+    format!("postgis_{}", ATTACH_ALIASES.fetch_add(1, Ordering::Relaxed))
+}
+
+// Table handed to with_transformed_data hooks before it is written to PostGIS
+pub struct TransformedData<'a> {
+    // DuckDB table that will be written
+    pub table: &'a str,
+    // Geometry columns - held in the table as WKT text in <column>_wkt
+    pub geometry_columns: &'a [String],
 }
 
+type TransformHook<'a> =
+    Box<dyn FnOnce(&Connection, &TransformedData) -> Result<(), Box<dyn Error>> + 'a>;
+
 // Struct representing core components
 struct DuckDBFileProcessor {
     file_path: String,
@@ -25,6 +174,25 @@ struct DuckDBFileProcessor {
     conn: Connection,
     postgis_uri: String,
     schema_name: String,
+    options: ProcessorOptions,
+    // Columns dropped when present, from the selected option profile
+    skip_columns: Vec<String>,
+    // Service the data was fetched from - file_path is then a temporary GeoJSON file
+    remote_source: Option<RemoteSource>,
+    // Postgres database the data is read from instead of a file
+    postgres_source: Option<PostgresSource>,
+    // Archive the data was extracted from - file_path is then the extracted file
+    zip_extract: Option<ZipExtract>,
+    // Name the target Postgres database is attached under
+    attach_alias: String,
+    // When the load started, and the stage it's in with its start, for the timeouts
+    started: Instant,
+    stage: Cell<(LoadStage, Instant)>,
+    // When the current stage or the whole load runs out of time, shared with the watchdog
+    deadline: Arc<Mutex<Option<Instant>>>,
+    report: RefCell<LoadReport>,
+    // Text columns parsed as WKT geometry, with the SRID their values gave
+    wkt_columns: RefCell<HashMap<String, Option<String>>>,
 }
 
 // Implementation for DuckDBFileProcessor
@@ -34,35 +202,154 @@ impl DuckDBFileProcessor {
         table_name: &str,
         postgis_uri: &str,
         schema_name: &str,
+        options: ProcessorOptions,
     ) -> Result<Self, Box<dyn Error>> {
-        // Determine FileType
-        let file_type = Self::determine_file_type(file_path)?;
+        Self::new_with_connection(
+            None,
+            file_path,
+            table_name,
+            postgis_uri,
+            schema_name,
+            options,
+        )
+    }
+
+    // As new_file, reusing a connection to a shared DuckDB database when one is given
+    fn new_with_connection(
+        conn: Option<Connection>,
+        file_path: &str,
+        table_name: &str,
+        postgis_uri: &str,
+        schema_name: &str,
+        mut options: ProcessorOptions,
+    ) -> Result<Self, Box<dyn Error>> {
+        let started = Instant::now();
+        if let Some(progress) = &options.progress {
+            progress.set_stage(LoadStage::Detection);
+        }
+        logging::emit(
+            Verbosity::Info,
+            Event::Stage(LoadStage::Detection.name()),
+            format_args!("Starting detection stage of {}", table_name),
+        );
+        // Resolve the option profile and validate before doing any work
+        let skip_columns = options.apply_profile()?;
+        options.validate()?;
+        if options.locked_down {
+            lockdown::check_local_source(file_path)?;
+        }
 
         // Create Connection Object
-        let conn = Connection::open(":memory:")?;
+        let conn = match conn {
+            Some(conn) => conn,
+            None => open_connection()?,
+        };
+
+        // Postgres sources are read through their own attachment rather than a file
+        let postgres_source = PostgresSource::parse(file_path);
 
-        // Define postgis_uri
-        let postgis_uri = postgis_uri;
+        // Remote sources are fetched into a local file that the normal pipeline reads
+        let remote_source = RemoteSource::parse(file_path);
+        let local_path = match &remote_source {
+            Some(source) => source
+                .fetch(&conn, &options)?
+                .to_string_lossy()
+                .into_owned(),
+            None => shapefile::resolve_shapefile_path(file_path)?,
+        };
+
+        // Zips of data files are extracted to a temporary directory and the inner file is read
+        let zip_extract = match &remote_source {
+            Some(_) => None,
+            None => ZipExtract::open(&local_path)?,
+        };
+        let local_path = match &zip_extract {
+            Some(extract) => extract.path.to_string_lossy().into_owned(),
+            None => local_path,
+        };
 
-        // Install and load required extensions
-        conn.execute("INSTALL spatial;", [])?;
-        conn.execute("LOAD spatial;", [])?;
-        conn.execute("INSTALL postgres;", [])?;
-        conn.execute("LOAD postgres;", [])?;
+        // Determine FileType
+        let file_type = if postgres_source.is_some() {
+            FileType::Postgres
+        } else {
+            match Self::determine_file_type(&local_path) {
+                Ok(FileType::Shapefile) => {
+                    shapefile::check_sidecars(&local_path)?;
+                    FileType::Shapefile
+                }
+                Ok(file_type) => file_type,
+                Err(e) => {
+                    if remote_source.is_some() {
+                        let _ = fs::remove_file(&local_path);
+                    }
+                    return Err(e);
+                }
+            }
+        };
 
+        let attach_alias = options
+            .attach_alias
+            .clone()
+            .unwrap_or_else(unique_attach_alias);
+        let deadline =
+            timeouts::deadline(&options.timeouts, started, LoadStage::Detection, started);
         Ok(Self {
-            file_path: file_path.to_string(),
+            file_path: local_path,
             table_name: table_name.to_string(),
             file_type,
             conn,
-            postgis_uri: postgis_uri.to_string(),
-            schema_name: schema_name.to_string(),
+            postgis_uri: cancel::with_application_name(
+                &timeouts::with_write_timeout(
+                    &credentials::resolve_target_connection(postgis_uri),
+                    &options.timeouts,
+                ),
+                &cancel::application_name(&attach_alias),
+            ),
+            schema_name: credentials::resolve_schema(schema_name),
+            options,
+            skip_columns,
+            remote_source,
+            postgres_source,
+            zip_extract,
+            attach_alias,
+            started,
+            stage: Cell::new((LoadStage::Detection, started)),
+            deadline: Arc::new(Mutex::new(deadline)),
+            report: RefCell::new(LoadReport::default()),
+            wkt_columns: RefCell::new(HashMap::new()),
         })
     }
 
-    fn process_new_file(&self) -> Result<(), Box<dyn Error>> {
+    // Where the data came from, for provenance and load history
+    fn source_name(&self) -> String {
+        match (&self.remote_source, &self.postgres_source) {
+            (Some(source), _) => source.url().to_string(),
+            (_, Some(source)) => source.display_name(),
+            (None, None) => match &self.zip_extract {
+                Some(extract) => extract.archive.clone(),
+                None => self.file_path.clone(),
+            },
+        }
+    }
+
+    fn process_new_file(&self, hook: Option<TransformHook>) -> Result<(), Box<dyn Error>> {
+        // Names are quoted in every statement, so only what Postgres can't hold is rejected
+        naming::validate_identifier("Table", &self.table_name)?;
+        naming::validate_identifier("Schema", &self.schema_name)?;
+        connection::validate_connection(&self.postgis_uri)?;
+
         // Call initial methods
-        self.create_data_table()?;
+        self.lock_down_extensions()?;
+        if self.file_type == FileType::GeoTiff && self.options.raster != RasterLoad::Footprint {
+            // The raster is uploaded from Rust, so DuckDB never needs to read a file
+            self.lock_down_file_access()?;
+            self.begin_stage(LoadStage::Write);
+            return self.load_raster_tiles();
+        }
+        self.checkpoint()?;
+        self.prepare_data_table()?;
+        self.checkpoint()?;
+        self.begin_stage(LoadStage::Transform);
         self.query_and_print_schema()?;
     
         // First, check if we have any geometry columns
@@ -81,16 +368,63 @@ impl DuckDBFileProcessor {
         if rows.next()?.is_some() {
             // Transform geometry columns and store the result
             let geom_columns = self.transform_geom_columns()?;
-            // Pass the geometry columns to load_data_postgis
-            self.load_data_postgis(&geom_columns)?;
+            self.lock_down_file_access()?;
+            self.run_transform_hook(hook, "transformed_data", &geom_columns)?;
+            self.compute_checksum("transformed_data")?;
+            self.checkpoint()?;
+            self.begin_stage(LoadStage::Write);
+            match &self.options.output_target {
+                // Pass the geometry columns to load_data_postgis
+                OutputTarget::Postgis => self.load_data_postgis(&geom_columns)?,
+                target => self.write_file_output("transformed_data", &geom_columns, target)?,
+            }
         } else {
             // No geometry columns - do a simple table copy
-            self.load_non_geo_data()?;
+            self.lock_down_file_access()?;
+            self.run_transform_hook(hook, "data", &[])?;
+            self.compute_checksum("data")?;
+            self.checkpoint()?;
+            self.begin_stage(LoadStage::Write);
+            match &self.options.output_target {
+                OutputTarget::Postgis => self.load_non_geo_data()?,
+                target => self.write_file_output("data", &[], target)?,
+            }
         }
     
         Ok(())
     }
 
+    // Read the file into the data table and apply the column and geometry options to it
+    fn prepare_data_table(&self) -> Result<(), Box<dyn Error>> {
+        self.create_data_table()?;
+        self.apply_column_types()?;
+        self.apply_column_selection()?;
+        self.apply_column_naming()?;
+        self.apply_coordinate_storage()?;
+        self.apply_file_partition()?;
+        self.apply_wkt_geometry()?;
+        self.apply_empty_geometry_policy()?;
+        self.apply_geometry_filter()
+    }
+
+    fn run_transform_hook(
+        &self,
+        hook: Option<TransformHook>,
+        table: &str,
+        geometry_columns: &[String],
+    ) -> Result<(), Box<dyn Error>> {
+        if let Some(hook) = hook {
+            hook(
+                &self.conn,
+                &TransformedData {
+                    table,
+                    geometry_columns,
+                },
+            )?;
+        }
+        Ok(())
+    }
+
     fn determine_file_type(file_path: &str) -> Result<FileType, Box<dyn Error>> {
         // Open file and read first 100 bytes for magic number detection
         let mut file = File::open(file_path)?;
@@ -98,6 +432,11 @@ impl DuckDBFileProcessor {
         let bytes_read = file.read(&mut header_buffer)?;
         let header = &header_buffer[..bytes_read];
 
+        // Registered detectors can claim files before the built-in formats
+        if let Some(file_type) = detect::detect_custom_type(Path::new(file_path), header) {
+            return Ok(file_type);
+        }
+
         // First try magic number detection
         if let Some(file_type) = Self::match_magic_numbers(header) {
             return Ok(file_type);
@@ -112,6 +451,11 @@ impl DuckDBFileProcessor {
 
     fn match_magic_numbers(header: &[u8]) -> Option<FileType> {
         match header {
+            // OpenDocument spreadsheet - a zip whose first entry is its mimetype, stored as is
+            [0x50, 0x4B, 0x03, 0x04, ..] if archive::is_opendocument_spreadsheet(header) => {
+                Some(FileType::Ods)
+            }
+
             // Excel (XLSX) - PKZip signature
             [0x50, 0x4B, 0x03, 0x04, ..] => Some(FileType::Excel),
             
@@ -128,12 +472,35 @@ impl DuckDBFileProcessor {
             
             // Shapefile
             [0x00, 0x00, 0x27, 0x0A, ..] => Some(FileType::Shapefile),
+
+            // Binary DXF
+            [b'A', b'u', b't', b'o', b'C', b'A', b'D', b' ', b'B', b'i', b'n', b'a', b'r', b'y', ..] => {
+                Some(FileType::Dxf)
+            }
+
+            // ORC
+            [b'O', b'R', b'C', ..] => Some(FileType::Orc),
+
+            // TIFF, little and big endian - BigTIFF is rejected when the tags are read
+            [b'I', b'I', 42 | 43, 0, ..] | [b'M', b'M', 0, 42 | 43, ..] => Some(FileType::GeoTiff),
+
+            // OSM PBF - a length prefixed BlobHeader whose type is "OSMHeader"
+            [_, _, _, _, 0x0A, 0x09, b'O', b'S', b'M', b'H', b'e', b'a', b'd', b'e', b'r', ..] => {
+                Some(FileType::OsmPbf)
+            }
             
             _ => None,
         }
     }
 
     fn detect_content_based_type(buffer: &[u8]) -> Result<FileType, Box<dyn Error>> {
+        // ASCII DXF opens with group code 0 and a SECTION, in whatever codepage the CAD tool used
+        let start = String::from_utf8_lossy(&buffer[..buffer.len().min(64)]).to_string();
+        let mut lines = start.lines().map(str::trim).filter(|line| !line.is_empty());
+        if lines.next() == Some("0") && lines.next() == Some("SECTION") {
+            return Ok(FileType::Dxf);
+        }
+
         // Try GeoJSON first
         if let Ok(text) = std::str::from_utf8(buffer) {
             let text_lower = text.trim_start().to_lowercase();
@@ -156,84 +523,515 @@ impl DuckDBFileProcessor {
     }
 
     fn is_valid_csv(content: &str) -> bool {
+        Self::detect_delimiter(content).is_some()
+    }
+
+    // Comma, then tab, semicolon and pipe - the first giving consistent fields wins
+    fn detect_delimiter(content: &str) -> Option<char> {
         let lines: Vec<&str> = content.lines().take(5).collect();
-        
         if lines.len() < 2 {
-            return false;
+            return None;
+        }
+
+        [',', '\t', ';', '|'].into_iter().find(|&delimiter| {
+            let first_line_fields = lines[0].split(delimiter).count();
+            // Require at least 2 columns and check for consistency
+            first_line_fields >= 2
+                && lines[1..].iter().all(|line| {
+                    let fields = line.split(delimiter).count();
+                    fields == first_line_fields
+                        && line.chars().all(|c| c.is_ascii() || c.is_whitespace())
+                })
+        })
+    }
+
+    // read_csv delim option for delimited text that isn't comma separated
+    fn csv_delimiter_option(&self) -> Result<String, Box<dyn Error>> {
+        let mut reader = BufReader::new(File::open(&self.file_path)?);
+        let mut head = Vec::new();
+        for _ in 0..5 {
+            if reader.read_until(b'\n', &mut head)? == 0 {
+                break;
+            }
+        }
+        match Self::detect_delimiter(&String::from_utf8_lossy(&head)) {
+            Some(delimiter) if delimiter != ',' => Ok(format!(", delim = '{}'", delimiter)),
+            _ => Ok(String::new()),
         }
-        
-        let first_line_fields = lines[0].split(',').count();
-        // Require at least 2 columns and check for consistency
-        first_line_fields >= 2 
-            && lines[1..].iter().all(|line| {
-                let fields = line.split(',').count();
-                fields == first_line_fields 
-                    && line.chars().all(|c| c.is_ascii() || c.is_whitespace())
-            })
     }
 
     fn create_data_table(&self) -> Result<(), Box<dyn Error>> {
         // Create initial data table
-        let query = match self.file_type {
+        let select = match &self.file_type {
             FileType::Geopackage | FileType::Shapefile | FileType::Geojson => {
-                format!(
-                    "CREATE TABLE data AS SELECT * FROM st_read('{}');",
-                    self.file_path
-                )
+                match &self.options.fid_column {
+                    Some(fid_column) => self.gdal_query_with_fid(fid_column)?,
+                    None => format!("SELECT * FROM {}", self.st_read_source()),
+                }
             }
-            FileType::Excel => {
-                format!(
-                    "CREATE TABLE data AS SELECT * FROM st_read('{}');",
-                    self.file_path
-                )
+            FileType::Excel | FileType::Ods => self.excel_select()?,
+            FileType::Csv => self.csv_select(self.options.csv.all_varchar)?,
+            FileType::Parquet => format!(
+                "SELECT * FROM read_parquet({})",
+                sql::string_literal(&self.file_path)
+            ),
+            FileType::OsmPbf => self.osm_select()?,
+            FileType::Dxf => self.dxf_select()?,
+            FileType::GeoTiff => self.raster_footprint_select()?,
+            // Detected so the error is clear - DuckDB has no ORC reader to load it with
+            FileType::Orc => return Err("ORC is not supported, convert to Parquet first".into()),
+            FileType::Postgres => match &self.postgres_source {
+                Some(source) => self.postgres_source_select(source)?,
+                None => return Err("Postgres source connection missing".into()),
+            },
+            FileType::Custom(name) => self.custom_select(name)?,
+        };
+        let select = self.resolve_duplicate_columns(select)?;
+        match self.execute_profiled("read source", &format!("CREATE TABLE data AS {};", select)) {
+            Err(e) if self.csv_types_failed(&e) => self.read_csv_as_text(&e)?,
+            result => {
+                result?;
             }
-            FileType::Csv => {
-                format!(
-                    "CREATE TABLE data AS SELECT * FROM read_csv('{}');",
-                    self.file_path
-                )
+        }
+        self.check_csv_rejects()?;
+        Ok(())
+    }
+
+    fn st_read_source(&self) -> String {
+        let encoding = match self.file_type {
+            FileType::Shapefile => self.shapefile_encoding(),
+            _ => None,
+        };
+        let file_path = sql::string_literal(&self.file_path);
+        match encoding {
+            // The shapefile driver re-encodes DBF attributes to UTF-8 from the given encoding
+            Some(encoding) => format!(
+                "st_read({}, open_options = ['ENCODING={}'])",
+                file_path, encoding
+            ),
+            _ => format!("st_read({})", file_path),
+        }
+    }
+
+    fn gdal_query_with_fid(&self, fid_column: &str) -> Result<String, Box<dyn Error>> {
+        if self.file_type == FileType::Geopackage {
+            // GPKG feature tables use an INTEGER PRIMARY KEY, so the SQLite rowid is the FID
+            // GDAL scans the layer in rowid order, so the two can be joined by position
+            extensions::load_extension(&self.conn, "sqlite")?;
+            let layer_name = self.get_layer_name()?;
+            Ok(format!(
+                "SELECT fids.rowid AS {}, source.*
+                FROM {} AS source
+                POSITIONAL JOIN (SELECT rowid FROM sqlite_scan({}, {}) ORDER BY rowid) AS fids",
+                naming::quote_identifier(fid_column),
+                self.st_read_source(),
+                sql::string_literal(&self.file_path),
+                sql::string_literal(&layer_name)
+            ))
+        } else {
+            // Shapefile and GeoJSON FIDs are the zero based feature index in the source. The
+            // positional join numbers features in the order GDAL reads them, which
+            // row_number() OVER () doesn't promise
+            let feature_count: i64 = self.conn.query_row(
+                &format!("SELECT count(*) FROM {};", self.st_read_source()),
+                [],
+                |row| row.get(0),
+            )?;
+            Ok(format!(
+                "SELECT fids.range AS {}, source.*
+                FROM {} AS source
+                POSITIONAL JOIN range({}) AS fids",
+                naming::quote_identifier(fid_column),
+                self.st_read_source(),
+                feature_count
+            ))
+        }
+    }
+
+    fn get_layer_name(&self) -> Result<String, Box<dyn Error>> {
+        // st_read reads the first layer when none is specified
+        let query = format!(
+            "SELECT layers[1].name FROM st_read_meta({});",
+            sql::string_literal(&self.file_path)
+        );
+        let mut stmt = self.conn.prepare(&query)?;
+        let mut rows = stmt.query([])?;
+        if let Some(row) = rows.next()? {
+            Ok(row.get(0)?)
+        } else {
+            Err(format!("No layers found in file: {}", self.file_path).into())
+        }
+    }
+
+    fn get_column_names(&self, table: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT column_name FROM information_schema.columns
+            WHERE table_name = ? ORDER BY ordinal_position",
+        )?;
+        let columns = stmt
+            .query_map([table], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(columns)
+    }
+
+    fn column_types_struct(&self) -> String {
+        let entries = self
+            .options
+            .column_types
+            .iter()
+            .map(|(column, data_type)| format!("'{}': '{}'", column.replace('\'', "''"), data_type))
+            .collect::<Vec<_>>();
+        format!("{{{}}}", entries.join(", "))
+    }
+
+    fn apply_column_types(&self) -> Result<(), Box<dyn Error>> {
+        if self.options.column_types.is_empty() {
+            return Ok(());
+        }
+
+        // Enforce overrides for every format - readers without type options are cast here
+        let columns = self.get_column_names("data")?;
+        for (column, data_type) in &self.options.column_types {
+            let source_column = columns
+                .iter()
+                .find(|c| c.eq_ignore_ascii_case(column))
+                .ok_or_else(|| format!("Column '{}' not found in source data", column))?;
+            let quoted = naming::quote_identifier(source_column);
+            self.conn.execute(
+                &format!(
+                    "ALTER TABLE data ALTER COLUMN {} TYPE {} USING CAST({} AS {});",
+                    quoted, data_type, quoted, data_type
+                ),
+                [],
+            )?;
+            log_debug!("Column {} cast to {}", source_column, data_type);
+        }
+        Ok(())
+    }
+
+    fn apply_column_selection(&self) -> Result<(), Box<dyn Error>> {
+        let include = self.options.include_columns.as_deref();
+        let exclude = &self.options.exclude_columns;
+        let renames = &self.options.rename_columns;
+        if include.is_none()
+            && exclude.is_empty()
+            && renames.is_empty()
+            && self.skip_columns.is_empty()
+        {
+            return Ok(());
+        }
+
+        // DuckDB column names are case insensitive, so match them the same way
+        let columns = self.get_column_names("data")?;
+        let matches = |requested: &str| columns.iter().any(|c| c.eq_ignore_ascii_case(requested));
+        let requested_columns = include
+            .unwrap_or_default()
+            .iter()
+            .chain(exclude)
+            .chain(renames.keys());
+        for requested in requested_columns {
+            if !matches(requested) {
+                return Err(format!("Column '{}' not found in source data", requested).into());
+            }
+        }
+
+        let mut mapping = Vec::new();
+        let mut report = self.report.borrow_mut();
+        for column in &columns {
+            let is_fid = self
+                .options
+                .fid_column
+                .as_ref()
+                .is_some_and(|fid| fid.eq_ignore_ascii_case(column));
+            let included =
+                include.is_none_or(|cols| cols.iter().any(|c| c.eq_ignore_ascii_case(column)));
+            let excluded = exclude
+                .iter()
+                .chain(&self.skip_columns)
+                .any(|c| c.eq_ignore_ascii_case(column));
+
+            // The FID column was explicitly requested, so it is always kept
+            if !is_fid && (!included || excluded) {
+                report.dropped_columns.push(column.clone());
+                continue;
+            }
+
+            let renamed = renames
+                .iter()
+                .find(|(source, _)| source.eq_ignore_ascii_case(column))
+                .map(|(_, target)| target.clone())
+                .unwrap_or_else(|| column.clone());
+            if &renamed != column {
+                report
+                    .column_renames
+                    .push((column.clone(), renamed.clone()));
+            }
+            mapping.push((column.clone(), renamed));
+        }
+        drop(report);
+
+        if mapping.is_empty() {
+            return Err("Column selection removed every column".into());
+        }
+        log_debug!("Selected columns: {:?}", mapping);
+        self.rebuild_data_table(&mapping)
+    }
+
+    fn apply_column_naming(&self) -> Result<(), Box<dyn Error>> {
+        if self.options.column_naming == ColumnNaming::Preserve {
+            return Ok(());
+        }
+
+        let columns = self.get_column_names("data")?;
+        let sanitized = naming::sanitize_column_names(&columns);
+        if columns == sanitized {
+            return Ok(());
+        }
+
+        let mapping = columns.into_iter().zip(sanitized).collect::<Vec<_>>();
+        self.rebuild_data_table(&mapping)?;
+
+        let mut report = self.report.borrow_mut();
+        for (original, renamed) in mapping {
+            if original != renamed {
+                log_debug!("Column {} renamed to {}", original, renamed);
+                report.column_renames.push((original, renamed));
             }
-            FileType::Parquet => {
+        }
+        Ok(())
+    }
+
+    fn rebuild_data_table(&self, mapping: &[(String, String)]) -> Result<(), Box<dyn Error>> {
+        // Rebuild the table in one statement so renames can't collide with each other
+        let select_list = mapping
+            .iter()
+            .map(|(source, target)| {
                 format!(
-                    "CREATE TABLE data AS SELECT * FROM read_parquet('{}');",
-                    self.file_path
+                    "{} AS {}",
+                    naming::quote_identifier(source),
+                    naming::quote_identifier(target)
                 )
-            }
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.conn.execute(
+            &format!(
+                "CREATE OR REPLACE TABLE data AS SELECT {} FROM data;",
+                select_list
+            ),
+            [],
+        )?;
+        Ok(())
+    }
+
+    fn apply_coordinate_storage(&self) -> Result<(), Box<dyn Error>> {
+        let (precision, scale) = match self.options.coordinate_storage {
+            CoordinateStorage::Double => return Ok(()),
+            CoordinateStorage::Numeric { precision, scale } => (precision, scale),
         };
-        self.conn.execute(&query, [])?;
+
+        // Find floating point columns that hold raw coordinates
+        let query = "
+            SELECT column_name
+            FROM information_schema.columns
+            WHERE table_name = 'data'
+            AND data_type IN ('DOUBLE', 'FLOAT')";
+        let mut stmt = self.conn.prepare(query)?;
+        let columns = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Cast to a fixed scale decimal - the postgres writer maps this to NUMERIC
+        for column in columns.iter().filter(|c| Self::is_coordinate_column(c)) {
+            self.conn.execute(
+                &format!(
+                    "ALTER TABLE data ALTER COLUMN {} TYPE DECIMAL({}, {});",
+                    naming::quote_identifier(column),
+                    precision,
+                    scale
+                ),
+                [],
+            )?;
+            log_debug!(
+                "Coordinate column {} stored as NUMERIC({}, {})",
+                column, precision, scale
+            );
+        }
         Ok(())
     }
 
+    fn is_coordinate_column(column_name: &str) -> bool {
+        // Match on the leading word so names like "X-coordinate (m)" or "lat_wgs84" are found
+        let lower = column_name.to_lowercase();
+        let first_word = lower
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .find(|word| !word.is_empty())
+            .unwrap_or("");
+        matches!(
+            first_word,
+            "lat"
+                | "latitude"
+                | "lon"
+                | "lng"
+                | "long"
+                | "longitude"
+                | "x"
+                | "y"
+                | "xcoord"
+                | "ycoord"
+                | "easting"
+                | "eastings"
+                | "northing"
+                | "northings"
+        )
+    }
+
+    fn compute_checksum(&self, table: &str) -> Result<(), Box<dyn Error>> {
+        if !self.options.compute_checksum {
+            return Ok(());
+        }
+
+        // Hash each row's canonical text form, then hash the sorted row hashes
+        // so the result doesn't depend on scan order
+        let query = format!(
+            "SELECT md5(coalesce(string_agg(row_hash, '' ORDER BY row_hash), ''))
+            FROM (SELECT md5(CAST(t AS VARCHAR)) AS row_hash FROM {} AS t);",
+            table
+        );
+        let checksum: String = self.conn.query_row(&query, [], |row| row.get(0))?;
+        log_debug!("Content checksum for {}: {}", self.table_name, checksum);
+
+        // Tie the checksum back to the exact source file
+        let source_sha256 = self.hash_source_file()?;
+
+        let mut report = self.report.borrow_mut();
+        report.content_checksum = Some(checksum);
+        report.source_sha256 = source_sha256;
+        Ok(())
+    }
+
+    fn hash_source_file(&self) -> Result<Option<String>, Box<dyn Error>> {
+        // Postgres sources have no file to hash
+        if self.postgres_source.is_some() {
+            return Ok(None);
+        }
+        let path = match &self.zip_extract {
+            Some(extract) => &extract.archive,
+            None => &self.file_path,
+        };
+        let mut hasher = Sha256::new();
+        io::copy(&mut File::open(path)?, &mut hasher)?;
+        Ok(Some(format!("{:x}", hasher.finalize())))
+    }
+
     fn query_and_print_schema(&self) -> Result<Arc<Schema>, Box<dyn Error>> {
+        let schema = self.data_schema()?;
+
+        // Print and return schema
+        log_debug!("Schema: {:?}", schema);
+        Ok(schema)
+    }
+
+    fn data_schema(&self) -> Result<Arc<Schema>, Box<dyn Error>> {
         // Create and prep query
         let query = "SELECT * FROM data LIMIT 10";
         let mut stmt = self.conn.prepare(query)?;
 
         // Run query
         let arrow_result = stmt.query_arrow([])?;
-        let schema = arrow_result.get_schema();
-
-        // Print and return schema
-        println!("Schema: {:?}", schema);
-        Ok(schema)
+        Ok(arrow_result.get_schema())
     }
 
-    fn get_crs_number(&self) -> Result<String, Box<dyn Error>> {
+    fn get_crs_number(&self, geom_column: &str) -> Result<String, Box<dyn Error>> {
+        // OSM coordinates are always WGS84
+        if self.file_type == FileType::OsmPbf {
+            return Ok("4326".to_string());
+        }
+
+        // GeoTIFFs declare their CRS in GeoKeys rather than anything GDAL vector reads
+        if self.file_type == FileType::GeoTiff {
+            return match self
+                .raster_epsg()?
+                .or_else(|| self.options.assumed_crs.clone())
+            {
+                Some(crs_number) => Ok(crs_number),
+                None => {
+                    Err(format!("CRS not found for the following file: {}", self.file_path).into())
+                }
+            };
+        }
+
+        // Postgres sources carry the SRID on each geometry value
+        if let Some(source) = &self.postgres_source {
+            return match self
+                .postgres_source_srid(source, geom_column)?
+                .or_else(|| self.options.assumed_crs.clone())
+            {
+                Some(crs_number) => Ok(crs_number),
+                None => Err(format!("CRS not found for column {}", geom_column).into()),
+            };
+        }
+
+        // GeoPackages record the CRS of each layer in their own SRS table
+        if self.file_type == FileType::Geopackage {
+            if let Some(crs_number) = self.geopackage_crs()? {
+                return Ok(crs_number);
+            }
+        }
+
+        // WKT read from text declares no CRS beyond an EWKT SRID= prefix
+        if let Some(crs_number) = self.wkt_column_crs(geom_column)? {
+            return Ok(crs_number);
+        }
+
+        // Custom formats may not be readable by GDAL, so an assumed CRS is taken as given
+        if let (FileType::Custom(_), Some(crs_number)) =
+            (&self.file_type, &self.options.assumed_crs)
+        {
+            return Ok(crs_number.clone());
+        }
+
         // Let and prep query
         let query = format!(
-            "SELECT layers[1].geometry_fields[1].crs.auth_code AS crs_number
-            FROM st_read_meta('{}');",
-            self.file_path
+            "SELECT layers[1].geometry_fields[1].crs.auth_code AS crs_number,
+                layers[1].geometry_fields[1].crs.wkt AS crs_wkt
+            FROM st_read_meta({});",
+            sql::string_literal(&self.file_path)
         );
         let mut stmt = self.conn.prepare(&query)?;
 
-        // Run query and return CRS number
+        // Run query and return CRS number, falling back to the assumed CRS
         let mut rows = stmt.query([])?;
-        if let Some(row) = rows.next()? {
-            let crs_number: String = row.get(0)?;
-            Ok(crs_number)
-        } else {
-            Err(format!("CRS not found for the following file: {}", self.file_path).into())
+        let (mut crs_number, meta_wkt) = match rows.next()? {
+            Some(row) => (
+                row.get::<_, Option<String>>(0)?,
+                row.get::<_, Option<String>>(1)?,
+            ),
+            None => (None, None),
+        };
+        // A shapefile's own .prj keeps the ESRI names and any vertical part GDAL may rewrite
+        let wkt = match self.file_type {
+            FileType::Shapefile => self.prj_wkt().or(meta_wkt),
+            _ => meta_wkt,
+        };
+
+        // Compound CRS - transform with the horizontal part and keep heights as they are
+        if let Some(compound) = wkt.as_deref().and_then(crs::compound_crs) {
+            log_warn!(
+                "Compound CRS with vertical CRS {} - heights are not transformed",
+                compound.vertical
+            );
+            self.report.borrow_mut().vertical_crs = Some(compound.vertical);
+            if compound.horizontal.is_some() {
+                crs_number = compound.horizontal;
+            }
+        }
+        if crs_number.is_none() {
+            crs_number = wkt.as_deref().and_then(crs::epsg_from_wkt);
+        }
+        match crs_number.or_else(|| self.options.assumed_crs.clone()) {
+            Some(crs_number) => Ok(crs_number),
+            None => Err(format!("CRS not found for the following file: {}", self.file_path).into()),
         }
     }
 
@@ -257,14 +1055,14 @@ impl DuckDBFileProcessor {
             // Handle the column based on its type
             if data_type == "BLOB" {
                 // Try to convert BLOB to geometry
-                println!("BLOB FOUND");
+                log_debug!("BLOB FOUND");
             }
             geom_columns.push(column_name);
         }
     
         // Process geometry columns as before
-        println!("Geometry columns: {:?}", &geom_columns);
-        let target_crs = "4326";
+        log_debug!("Geometry columns: {:?}", &geom_columns);
+        let target_crs = self.options.target_crs.as_deref().unwrap_or("4326");
         for column in &geom_columns {
             self.transform_crs(column, target_crs)?;
         }
@@ -274,29 +1072,47 @@ impl DuckDBFileProcessor {
 
     fn transform_crs(&self, geom_column: &str, target_crs: &str) -> Result<String, Box<dyn Error>> {
         // Get current CRS
-        let current_crs = self.get_crs_number()?;
-        println!("Current CRS for column {}: {}", geom_column, current_crs);
+        let current_crs = self.get_crs_number(geom_column)?;
+        self.promote_mixed_multi(geom_column)?;
+        log_debug!("Current CRS for column {}: {}", geom_column, current_crs);
+        let target_crs = if self.options.preserve_crs {
+            current_crs.as_str()
+        } else {
+            target_crs
+        };
+        let mut report = self.report.borrow_mut();
+        report.source_crs = Some(current_crs.clone());
+        report
+            .geometry_crs
+            .push((geom_column.to_string(), target_crs.to_string()));
+        drop(report);
 
         // Transform CRS if no match on target crs  
-        let create_table_query = if current_crs == target_crs {
-            format!(
-                "CREATE TABLE transformed_data AS SELECT *,
-                ST_AsText({}) as {}_wkt
-                FROM data;",
-                geom_column, geom_column
-            )
+        let quoted_column = naming::quote_identifier(geom_column);
+        let geometry = if current_crs == target_crs {
+            quoted_column.clone()
         } else {
             format!(
-                "CREATE TABLE transformed_data AS SELECT *,
-                ST_AsText(ST_Transform({}, 'EPSG:{}', 'EPSG:{}', always_xy := true)) AS {}_wkt
-                FROM data;",
-                geom_column, current_crs, target_crs, geom_column
+                "ST_Transform({}, 'EPSG:{}', 'EPSG:{}', always_xy := true)",
+                quoted_column, current_crs, target_crs
             )
         };
+        // Round vertices to a grid of the requested decimal places, keeping geometry valid
+        let geometry = match self.options.coordinate_precision {
+            Some(digits) => format!("ST_ReducePrecision({}, 1e-{})", geometry, digits),
+            None => geometry,
+        };
+        let create_table_query = format!(
+            "CREATE TABLE transformed_data AS SELECT *,
+            ST_AsText({}) AS {}
+            FROM data;",
+            geometry,
+            naming::quote_identifier(&format!("{}_wkt", geom_column))
+        );
 
-        self.conn.execute(&create_table_query, [])?;
+        self.execute_profiled(&format!("transform {}", geom_column), &create_table_query)?;
         self.conn.execute(
-            &format!("ALTER TABLE transformed_data DROP COLUMN {};", geom_column),
+            &format!("ALTER TABLE transformed_data DROP COLUMN {};", quoted_column),
             [],
         )?;
 
@@ -315,118 +1131,526 @@ impl DuckDBFileProcessor {
 
     fn load_data_postgis(&self, geom_columns: &[String]) -> Result<(), Box<dyn Error>> {
         // Attach Postgres DB instance
-        println!("LOADING GEOSPATIAL DATA");
-        self.conn.execute(
-            &format!(
-                "ATTACH '{}' AS gridwalk_db (TYPE POSTGRES)",
-                self.postgis_uri
-            ),
-            [],
-        )?;
+        log_debug!("LOADING GEOSPATIAL DATA");
+        self.ensure_postgis_attached()?;
+        self.check_target_database()?;
+        self.check_postgis_extension()?;
+        self.check_target_relation()?;
+        self.check_schema_privileges()?;
 
         // Create schema if it doesn't exist - Execute this directly in PostgreSQL
-        // Note: We need to escape single quotes in the SQL string
-        let create_schema_sql = format!("CREATE SCHEMA IF NOT EXISTS \"{}\";", self.schema_name);
-        self.conn.execute(
-            &format!(
-                "CALL postgres_execute('gridwalk_db', '{}');",
-                create_schema_sql.replace('\'', "''")
-            ),
-            [],
-        )?;
+        let create_schema_sql = format!(
+            "CREATE SCHEMA IF NOT EXISTS {};",
+            naming::quote_identifier(&self.schema_name)
+        );
+        self.postgres_execute("create schema", &create_schema_sql)?;
 
         // Schema qualified table name
-        let schema_qualified_table = format!("\"{}\".\"{}\"", self.schema_name, self.table_name);
+        let schema_qualified_table = naming::qualified_name(&self.schema_name, &self.table_name);
 
-        // Execute CRUD logic - First drop the table if it exists
-        let drop_table_sql = format!("DROP TABLE IF EXISTS {};", schema_qualified_table);
-        self.conn.execute(
-            &format!(
-                "CALL postgres_execute('gridwalk_db', '{}');",
-                drop_table_sql.replace('\'', "''")
-            ),
-            [],
-        )?;
+        // Load into a staging table, converting geometry there before swapping it in
+        self.load_via_staging(|staging_table| {
+            self.write_table_to_postgis("transformed_data", staging_table)?;
 
-        // Create data in table
-        let create_table_query = &format!(
-            "CREATE TABLE gridwalk_db.{} AS SELECT * FROM transformed_data;",
-            schema_qualified_table
-        );
-        self.conn.execute(create_table_query, [])?;
-
-        // Construct PostGIS query for each geometry column
-        let mut postgis_queries = Vec::new();
-        for geom_column in geom_columns {
-            postgis_queries.push(format!(
-                "ALTER TABLE {} ADD COLUMN {} geometry;
-                UPDATE {} SET {} = ST_GeomFromText({}_wkt, 4326);
-                ALTER TABLE {} DROP COLUMN {}_wkt;",
-                schema_qualified_table,
-                geom_column,
-                schema_qualified_table,
-                geom_column,
-                geom_column,
-                schema_qualified_table,
-                geom_column
-            ));
-        }
-
-        let postgis_query = &format!(
-            "CALL postgres_execute('gridwalk_db', '{}');",
-            postgis_queries.join("\n")
-        );
-        self.conn.execute(postgis_query, [])?;
+            // Construct PostGIS query for each geometry column
+            let mut postgis_queries = Vec::new();
+            for geom_column in geom_columns {
+                let srid = self.geometry_srid(geom_column);
+                // A native CRS is declared on the column so it shows in geometry_columns
+                let native_crs = self.options.preserve_crs || self.options.target_crs.is_some();
+                let column_type = if native_crs {
+                    format!("geometry(Geometry, {})", srid)
+                } else {
+                    "geometry".to_string()
+                };
+                let column = naming::quote_identifier(geom_column);
+                let wkt_column = naming::quote_identifier(&format!("{}_wkt", geom_column));
+                postgis_queries.push(format!(
+                    "ALTER TABLE {} ADD COLUMN {} {};
+                    UPDATE {} SET {} = ST_GeomFromText({}, {});
+                    ALTER TABLE {} DROP COLUMN {};",
+                    staging_table,
+                    column,
+                    column_type,
+                    staging_table,
+                    column,
+                    wkt_column,
+                    srid,
+                    staging_table,
+                    wkt_column
+                ));
+                if let Some(antimeridian_sql) = self.antimeridian_sql(staging_table, geom_column) {
+                    postgis_queries.push(antimeridian_sql);
+                }
+            }
+
+            self.execute_postgis_batches(&postgis_queries)
+        })?;
+
+        self.write_empty_geometry_table()?;
+        self.record_layer_extent(geom_columns)?;
+        self.write_category_summary(geom_columns)?;
+        self.write_vector_tiles(geom_columns)?;
+        self.write_provenance(&schema_qualified_table)?;
+        self.run_maintenance()?;
+        self.apply_table_access()?;
 
-        println!(
+        log_info!(
             "Table {} created and data inserted successfully with geometry columns: {:?}",
             self.table_name, geom_columns
         );
         Ok(())
     }
 
-    fn load_non_geo_data(&self) -> Result<(), Box<dyn Error>> {
-        // Attach Postgres DB instance
-        println!("LOADING NON GEOSPATIAL DATA");
+    // EPSG code a geometry column was written in
+    fn geometry_srid(&self, geom_column: &str) -> String {
+        self.report
+            .borrow()
+            .geometry_crs
+            .iter()
+            .find(|(column, _)| column == geom_column)
+            .map_or_else(|| "4326".to_string(), |(_, crs)| crs.clone())
+    }
+
+    // Table the load is written to before it's swapped in for the target
+    fn staging_name(&self) -> String {
+        naming::derived_name(&self.table_name, "__staging")
+    }
+
+    fn load_via_staging<F>(&self, write_staging: F) -> Result<(), Box<dyn Error>>
+    where
+        F: FnOnce(&str) -> Result<(), Box<dyn Error>>,
+    {
+        let schema_qualified_table = naming::qualified_name(&self.schema_name, &self.table_name);
+        let staging_table = naming::qualified_name(&self.schema_name, &self.staging_name());
+
+        // Clear out anything left behind by an earlier failed load
+        let drop_staging_sql = format!("DROP TABLE IF EXISTS {};", staging_table);
+        self.postgres_execute("drop stale staging table", &drop_staging_sql)?;
+
+        // The existing table is untouched until the swap, so a failure only discards staging
+        let applied = write_staging(&staging_table)
+            .and_then(|_| self.checkpoint())
+            .and_then(|_| self.check_schema_drift(&staging_table))
+            .and_then(|_| match &self.options.incremental {
+                Some(key) => self.apply_incremental(&staging_table, key),
+                None => Ok(false),
+            });
+        match applied {
+            Ok(true) => return Ok(()),
+            Ok(false) => {}
+            Err(e) => {
+                if let Err(cleanup_error) =
+                    self.postgres_execute("drop failed staging table", &drop_staging_sql)
+                {
+                    log_warn!(
+                        "Failed to drop staging table {}: {}",
+                        staging_table, cleanup_error
+                    );
+                }
+                return Err(e);
+            }
+        }
+
+        if self.options.partition.is_some() {
+            return self.attach_partition(&staging_table);
+        }
+        if let Some(keep_versions) = self.options.keep_versions {
+            return self.swap_in_version(&staging_table, keep_versions);
+        }
+
+        // Swap in one transaction so consumers never see a missing or partial table
+        let swap_sql = format!(
+            "BEGIN;
+            DROP TABLE IF EXISTS {};
+            ALTER TABLE {} RENAME TO {};
+            COMMIT;",
+            schema_qualified_table,
+            staging_table,
+            naming::quote_identifier(&self.table_name)
+        );
+        self.postgres_execute("swap staging table", &swap_sql)
+    }
+
+    fn attach_postgis(&self) -> Result<(), duckdb::Error> {
         self.conn.execute(
             &format!(
-                "ATTACH '{}' AS gridwalk_db (TYPE POSTGRES)",
-                self.postgis_uri
+                "ATTACH '{}' AS {} (TYPE POSTGRES)",
+                self.postgis_uri.replace('\'', "''"),
+                self.attach_alias
             ),
             [],
         )?;
-    
-        // Create schema if it doesn't exist
-        let create_schema_sql = format!("CREATE SCHEMA IF NOT EXISTS \"{}\";", self.schema_name);
-        self.conn.execute(
+        Ok(())
+    }
+
+    fn ensure_postgis_attached(&self) -> Result<(), Box<dyn Error>> {
+        let attached: bool = self.conn.query_row(
+            "SELECT count(*) > 0 FROM duckdb_databases() WHERE database_name = ?;",
+            [&self.attach_alias],
+            |row| row.get(0),
+        )?;
+        if !attached {
+            self.attach_postgis()?;
+        }
+        Ok(())
+    }
+
+    fn query_postgres<T: duckdb::types::FromSql>(
+        &self,
+        sql: &str,
+    ) -> Result<Option<T>, Box<dyn Error>> {
+        // Run a read-only query directly in PostgreSQL and return the first value
+        let query = format!(
+            "SELECT * FROM {};",
+            sql::postgres_query(&self.attach_alias, sql)
+        );
+        let mut stmt = self.conn.prepare(&query)?;
+        let mut rows = stmt.query([])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(row.get(0)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn target_relkind(&self) -> Result<Option<String>, Box<dyn Error>> {
+        self.query_postgres(&format!(
+            "SELECT c.relkind::text FROM pg_class c
+            JOIN pg_namespace n ON n.oid = c.relnamespace
+            WHERE n.nspname = {} AND c.relname = {}",
+            sql::literal(&self.schema_name),
+            sql::literal(&self.table_name)
+        ))
+    }
+
+    fn check_target_relation(&self) -> Result<(), Box<dyn Error>> {
+        // DROP TABLE / CREATE TABLE can't replace views or foreign tables, so fail before touching them
+        let relkind = self.target_relkind()?;
+        let kind = match relkind.as_deref() {
+            None | Some("r") | Some("p") => return Ok(()),
+            // A versioned table is a view of its latest version
+            Some("v") if self.options.keep_versions.is_some() => return Ok(()),
+            Some("v") => "a view",
+            Some("m") => "a materialized view",
+            Some("f") => "a foreign table",
+            Some(_) => "not a regular table",
+        };
+        Err(format!(
+            "Target {}.{} is {} - loads can only replace regular tables",
+            self.schema_name, self.table_name, kind
+        )
+        .into())
+    }
+
+    fn postgres_execute(&self, step: &str, sql: &str) -> Result<(), Box<dyn Error>> {
+        // Execute directly in PostgreSQL
+        self.execute_postgis(step, &sql::postgres_execute(&self.attach_alias, sql))
+    }
+
+    fn execute_postgis(&self, step: &str, sql: &str) -> Result<(), Box<dyn Error>> {
+        let max_attempts = self.options.reconnect_attempts;
+        let mut attempts = 0;
+        let mut needs_attach = false;
+
+        loop {
+            let result = if needs_attach {
+                self.attach_postgis()
+            } else {
+                Ok(())
+            }
+            .and_then(|_| self.execute_profiled(step, sql));
+
+            match result {
+                Ok(_) => return Ok(()),
+                Err(e) if Self::is_connection_error(&e) && attempts < max_attempts => {
+                    attempts += 1;
+                    log_warn!(
+                        "Postgres connection lost during {} - re-attaching (attempt {} of {}): {}",
+                        step,
+                        attempts,
+                        max_attempts,
+                        self.redact(&e.to_string())
+                    );
+                    thread::sleep(Duration::from_secs(attempts as u64));
+                    // The attached session is unusable once the server side has gone away
+                    let _ = self.conn.execute(
+                        &format!("DETACH DATABASE IF EXISTS {};", self.attach_alias),
+                        [],
+                    );
+                    needs_attach = true;
+                }
+                Err(e) if Self::is_connection_error(&e) => {
+                    return Err(format!(
+                        "Postgres connection lost during {} after {} reconnect attempts. \
+                        Table {}.{} is only replaced by the final staging swap - \
+                        re-run the load to complete it: {}",
+                        step,
+                        attempts,
+                        self.schema_name,
+                        self.table_name,
+                        self.redact(&e.to_string())
+                    )
+                    .into());
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn is_connection_error(error: &duckdb::Error) -> bool {
+        let message = error.to_string().to_lowercase();
+        [
+            "connection closed",
+            "server closed the connection",
+            "terminating connection",
+            "no connection to the server",
+            "could not connect",
+            "connection refused",
+            "ssl syscall error",
+            "broken pipe",
+        ]
+        .iter()
+        .any(|pattern| message.contains(pattern))
+    }
+
+    fn execute_postgis_batches(&self, scripts: &[String]) -> Result<(), Box<dyn Error>> {
+        // Send scripts in bounded batches - a single script for very wide tables is too large
+        // Each batch runs in its own transaction so a failed batch leaves no partial changes
+        let batches = scripts
+            .chunks(self.options.geometry_batch_size)
+            .collect::<Vec<_>>();
+        for (index, batch) in batches.iter().enumerate() {
+            self.checkpoint()?;
+            let batch_query = sql::postgres_execute(
+                &self.attach_alias,
+                &format!("BEGIN;\n{}\nCOMMIT;", batch.join("\n")),
+            );
+            let step = format!("PostGIS batch {}", index + 1);
+            self.execute_postgis(&step, &batch_query).map_err(|e| {
+                format!(
+                    "PostGIS batch {} of {} failed ({} earlier batches committed): {}",
+                    index + 1,
+                    batches.len(),
+                    index,
+                    e
+                )
+            })?;
+        }
+        Ok(())
+    }
+
+    fn write_table_to_postgis(
+        &self,
+        source_table: &str,
+        schema_qualified_table: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let row_count: i64 = self.conn.query_row(
+            &format!("SELECT count(*) FROM {};", source_table),
+            [],
+            |row| row.get(0),
+        )?;
+        self.report.borrow_mut().rows_loaded = row_count as u64;
+        self.report_rows(0, row_count as u64);
+
+        if self.options.write_method == WriteMethod::Copy {
+            return self.write_table_with_copy(source_table, schema_qualified_table);
+        }
+
+        let throttle = match &self.options.write_throttle {
+            Some(throttle) => throttle,
+            None => {
+                return self.execute_postgis(
+                    "write table",
+                    &format!(
+                        "CREATE TABLE {}.{} AS SELECT * FROM {};",
+                        self.attach_alias, schema_qualified_table, source_table
+                    ),
+                );
+            }
+        };
+
+        // Create the empty target, then insert in batches paced to the throttle
+        self.execute_postgis(
+            "create table",
             &format!(
-                "CALL postgres_execute('gridwalk_db', '{}');",
-                create_schema_sql.replace('\'', "''")
+                "CREATE TABLE {}.{} AS SELECT * FROM {} LIMIT 0;",
+                self.attach_alias, schema_qualified_table, source_table
             ),
-            [],
         )?;
-    
-        // Schema qualified table name
-        let schema_qualified_table = format!("\"{}\".\"{}\"", self.schema_name, self.table_name);
-    
-        // Drop existing table if it exists
-        let drop_table_sql = format!("DROP TABLE IF EXISTS {};", schema_qualified_table);
-        self.conn.execute(
+        let total_rows = row_count;
+        let average_row_bytes: f64 = self.conn.query_row(
             &format!(
-                "CALL postgres_execute('gridwalk_db', '{}');",
-                drop_table_sql.replace('\'', "''")
+                "SELECT coalesce(avg(strlen(CAST(t AS VARCHAR))), 0) FROM {} AS t;",
+                source_table
             ),
             [],
+            |row| row.get(0),
+        )?;
+
+        // Rows deleted after the table was created - by a with_transformed_data hook, duplicate
+        // removal or the empty geometry policy - leave gaps in the rowids, so batches run to
+        // the highest rowid rather than the row count
+        let rowid_end: i64 = self.conn.query_row(
+            &format!("SELECT coalesce(max(rowid) + 1, 0) FROM {};", source_table),
+            [],
+            |row| row.get(0),
         )?;
+
+        let mut offset = 0;
+        while offset < rowid_end {
+            self.checkpoint()?;
+            // Limits are re-read every batch so they can be adjusted mid-load
+            let rows_per_second = throttle.rows_per_second(average_row_bytes);
+            let batch_size = rows_per_second.map_or(rowid_end, |rows| rows as i64);
+            let started = Instant::now();
+
+            // Each batch commits on its own, so a reconnect resumes from the failed batch
+            self.execute_postgis(
+                &format!("insert rows {}..{}", offset, offset + batch_size),
+                &format!(
+                    "INSERT INTO {}.{} SELECT * FROM {} WHERE rowid >= {} AND rowid < {};",
+                    self.attach_alias,
+                    schema_qualified_table,
+                    source_table,
+                    offset,
+                    offset + batch_size
+                ),
+            )?;
+            offset += batch_size;
+            let written: i64 = self.conn.query_row(
+                &format!("SELECT count(*) FROM {} WHERE rowid < {};", source_table, offset),
+                [],
+                |row| row.get(0),
+            )?;
+            self.report_rows(written as u64, total_rows as u64);
+
+            if let Some(rows_per_second) = rows_per_second {
+                let target = Duration::from_secs_f64(batch_size as f64 / rows_per_second as f64);
+                if let Some(remaining) = target.checked_sub(started.elapsed()) {
+                    thread::sleep(remaining);
+                }
+            }
+            log_info!(
+                "Written {} of {} rows to {}",
+                written,
+                total_rows,
+                schema_qualified_table
+            );
+        }
+        Ok(())
+    }
+
+    fn write_provenance(&self, schema_qualified_table: &str) -> Result<(), Box<dyn Error>> {
+        if !self.options.table_comment && self.options.metadata_table.is_none() {
+            return Ok(());
+        }
+
+        let source_file = match (&self.remote_source, &self.postgres_source) {
+            (None, None) => Path::new(&self.file_path)
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| self.file_path.clone()),
+            _ => self.source_name(),
+        };
+        let loaded_at: String = self.conn.query_row(
+            "SELECT strftime(current_timestamp, '%Y-%m-%dT%H:%M:%S%z');",
+            [],
+            |row| row.get(0),
+        )?;
+        let report = self.report.borrow().clone();
+
+        if self.options.table_comment {
+            let comment = format!(
+                "Loaded by duckdb-postgis {} from '{}' ({:?}, source CRS {}) - {} rows at {}",
+                report.crate_version,
+                source_file,
+                self.file_type,
+                report
+                    .source_crs
+                    .as_deref()
+                    .map_or("none".to_string(), |crs| format!("EPSG:{}", crs)),
+                report.rows_loaded,
+                loaded_at
+            );
+            let relation = if self.options.keep_versions.is_some() {
+                "VIEW"
+            } else {
+                "TABLE"
+            };
+            self.postgres_execute(
+                "comment on table",
+                &format!(
+                    "COMMENT ON {} {} IS {};",
+                    relation,
+                    schema_qualified_table,
+                    sql::literal(&comment)
+                ),
+            )?;
+        }
+
+        if let Some(metadata_table) = &self.options.metadata_table {
+            let metadata = serde_json::json!({
+                "source_file": source_file,
+                "file_type": format!("{:?}", self.file_type),
+                "source_crs": report.source_crs,
+                "vertical_crs": report.vertical_crs,
+                "row_count": report.rows_loaded,
+                "extent": report
+                    .extent
+                    .map(|extent| [extent.min_x, extent.min_y, extent.max_x, extent.max_y]),
+                "loaded_at": loaded_at,
+                "crate_version": report.crate_version,
+            });
+            let qualified_metadata_table =
+                naming::qualified_name(&self.schema_name, metadata_table);
+            let metadata_sql = format!(
+                "CREATE TABLE IF NOT EXISTS {metadata_table} (
+                    table_schema text NOT NULL,
+                    table_name text NOT NULL,
+                    metadata jsonb NOT NULL,
+                    updated_at timestamptz NOT NULL DEFAULT now(),
+                    PRIMARY KEY (table_schema, table_name)
+                );
+                INSERT INTO {metadata_table} (table_schema, table_name, metadata)
+                VALUES ({schema}, {table}, {metadata}::jsonb)
+                ON CONFLICT (table_schema, table_name)
+                DO UPDATE SET metadata = EXCLUDED.metadata, updated_at = now();",
+                metadata_table = qualified_metadata_table,
+                schema = sql::literal(&self.schema_name),
+                table = sql::literal(&self.table_name),
+                metadata = sql::literal(&metadata.to_string()),
+            );
+            self.postgres_execute("write metadata row", &metadata_sql)?;
+        }
+        Ok(())
+    }
+
+    fn load_non_geo_data(&self) -> Result<(), Box<dyn Error>> {
+        // Attach Postgres DB instance
+        log_debug!("LOADING NON GEOSPATIAL DATA");
+        self.ensure_postgis_attached()?;
+        self.check_target_database()?;
+        self.check_target_relation()?;
+        self.check_schema_privileges()?;
     
-        // Create data in table directly from 'data' table (no transformation needed)
-        let create_table_query = &format!(
-            "CREATE TABLE gridwalk_db.{} AS SELECT * FROM data;",
-            schema_qualified_table
+        // Create schema if it doesn't exist
+        let create_schema_sql = format!(
+            "CREATE SCHEMA IF NOT EXISTS {};",
+            naming::quote_identifier(&self.schema_name)
         );
-        self.conn.execute(create_table_query, [])?;
+        self.postgres_execute("create schema", &create_schema_sql)?;
+    
+        // Schema qualified table name
+        let schema_qualified_table = naming::qualified_name(&self.schema_name, &self.table_name);
     
-        println!(
+        // Create data in a staging table directly from 'data' table (no transformation needed)
+        self.load_via_staging(|staging_table| self.write_table_to_postgis("data", staging_table))?;
+    
+        self.write_provenance(&schema_qualified_table)?;
+        self.run_maintenance()?;
+        self.apply_table_access()?;
+
+        log_info!(
             "Table {} created and data inserted successfully (no geometry columns)",
             self.table_name
         );
@@ -434,40 +1658,186 @@ impl DuckDBFileProcessor {
     }
 }
 
+impl Drop for DuckDBFileProcessor {
+    fn drop(&mut self) {
+        // Remove the temporary file a remote source was fetched into
+        if self.remote_source.is_some() {
+            if let Err(e) = fs::remove_file(&self.file_path) {
+                log_warn!("Failed to remove {}: {}", self.file_path, e);
+            }
+        }
+    }
+}
+
 pub fn launch_process_file(
     file_path: &str,
     table_name: &str,
     postgis_uri: &str,
     schema_name: &str,
 ) -> Result<(), io::Error> {
+    launch_process_file_with_options(
+        file_path,
+        table_name,
+        postgis_uri,
+        schema_name,
+        ProcessorOptions::default(),
+    )?;
+    Ok(())
+}
+
+pub fn launch_process_file_with_options(
+    file_path: &str,
+    table_name: &str,
+    postgis_uri: &str,
+    schema_name: &str,
+    options: ProcessorOptions,
+) -> Result<LoadReport, io::Error> {
+    run_load(
+        file_path,
+        table_name,
+        postgis_uri,
+        schema_name,
+        options,
+        None,
+    )
+}
+
+// As launch_process_file_with_options, connecting with settings given field by field
+pub fn launch_process_file_with_config(
+    file_path: &str,
+    table_name: &str,
+    connection: &PgConnectionConfig,
+    schema_name: &str,
+    options: ProcessorOptions,
+) -> Result<LoadReport, io::Error> {
+    let postgis_uri = connection.to_connection_string()?;
+    run_load(
+        file_path,
+        table_name,
+        &postgis_uri,
+        schema_name,
+        options,
+        None,
+    )
+}
+
+// Run detection and transformation, hand the live DuckDB connection to the hook for custom
+// steps, then write the (possibly modified) table to PostGIS with the standard writer
+pub fn with_transformed_data<F>(
+    file_path: &str,
+    table_name: &str,
+    postgis_uri: &str,
+    schema_name: &str,
+    options: ProcessorOptions,
+    hook: F,
+) -> Result<LoadReport, io::Error>
+where
+    F: FnOnce(&Connection, &TransformedData) -> Result<(), Box<dyn Error>>,
+{
+    run_load(
+        file_path,
+        table_name,
+        postgis_uri,
+        schema_name,
+        options,
+        Some(Box::new(hook)),
+    )
+}
+
+fn run_load(
+    file_path: &str,
+    table_name: &str,
+    postgis_uri: &str,
+    schema_name: &str,
+    options: ProcessorOptions,
+    hook: Option<TransformHook>,
+) -> Result<LoadReport, io::Error> {
+    if options.mixed_geometry == MixedGeometry::SeparateTables {
+        return geometry_types::run_separate_geometry_loads(
+            file_path,
+            table_name,
+            postgis_uri,
+            schema_name,
+            options,
+            hook,
+        );
+    }
+
     // Create new processor object
-    let processor = DuckDBFileProcessor::new_file(file_path, table_name, postgis_uri, schema_name)
-        .map_err(|e| {
-            io::Error::new(
-                io::ErrorKind::Other,
-                format!("Error creating FileProcessor for '{}': {}", file_path, e),
-            )
-        })?;
+    let processor =
+        DuckDBFileProcessor::new_file(file_path, table_name, postgis_uri, schema_name, options)
+            .map_err(|e| {
+                metrics::record_failure(LoadStage::Detection.name());
+                io::Error::other(format!(
+                    "Error creating FileProcessor for '{}': {}",
+                    file_path,
+                    credentials::redact(&e.to_string(), postgis_uri)
+                ))
+            })?;
+    run_processor(processor, file_path, hook)
+}
 
-    println!(
+fn run_processor(
+    processor: DuckDBFileProcessor,
+    file_path: &str,
+    hook: Option<TransformHook>,
+) -> Result<LoadReport, io::Error> {
+
+    log_info!(
         "Detected file type: {:?} for file: '{}'",
         processor.file_type, file_path
     );
 
-    // Process the file
-    processor.process_new_file().map_err(|e| {
-        io::Error::new(
-            io::ErrorKind::Other,
-            format!(
-                "Error processing {:?} file '{}': {}",
-                processor.file_type, file_path, e
-            ),
-        )
-    })?;
+    // Process the file, recording the outcome when load history is enabled
+    let started = Instant::now();
+    let watchdog = processor.start_watchdog();
+    let result = processor.process_new_file(hook);
+    drop(watchdog);
+    if let Some(history_schema) = &processor.options.load_history_schema {
+        if let Err(e) = processor.record_load_history(history_schema, started.elapsed(), &result) {
+            log_warn!(
+                "Failed to record load history: {}",
+                processor.redact(&e.to_string())
+            );
+        }
+    }
+    let result = result.map_err(|e| {
+        if processor.is_cancelled() {
+            return LoadCancelled::error(file_path);
+        }
+        if timeouts::is_timeout(e.as_ref()) || processor.is_out_of_time() {
+            return io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!(
+                    "Load of '{}' timed out: {}",
+                    file_path,
+                    processor.redact(&e.to_string())
+                ),
+            );
+        }
+        io::Error::other(format!(
+            "Error processing {:?} file '{}': {}",
+            processor.file_type,
+            file_path,
+            processor.redact(&e.to_string())
+        ))
+    });
+    processor.record_stage_time();
+    if let Err(e) = &result {
+        logging::emit(Verbosity::Warn, Event::Error, format_args!("{}", e));
+        metrics::record_failure(match e.kind() {
+            _ if LoadCancelled::is_cancelled(e) => "cancelled",
+            io::ErrorKind::TimedOut => "timeout",
+            _ => processor.stage.get().0.name(),
+        });
+    }
+    result?;
 
-    println!(
+    log_info!(
         "Successfully loaded {:?} file: '{}'",
         processor.file_type, file_path
     );
-    Ok(())
+    let report = processor.report.take();
+    metrics::record_success(&processor.file_type, report.rows_loaded);
+    Ok(report)
 }
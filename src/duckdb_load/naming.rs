@@ -0,0 +1,297 @@
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::error::Error;
+
+// Postgres limits identifiers to NAMEDATALEN - 1 bytes
+const MAX_IDENTIFIER_LENGTH: usize = 63;
+
+// Postgres reserved key words - these cannot be used as unquoted column names
+const RESERVED_WORDS: &[&str] = &[
+    "all",
+    "analyse",
+    "analyze",
+    "and",
+    "any",
+    "array",
+    "as",
+    "asc",
+    "asymmetric",
+    "authorization",
+    "binary",
+    "both",
+    "case",
+    "cast",
+    "check",
+    "collate",
+    "collation",
+    "column",
+    "concurrently",
+    "constraint",
+    "create",
+    "cross",
+    "current_catalog",
+    "current_date",
+    "current_role",
+    "current_schema",
+    "current_time",
+    "current_timestamp",
+    "current_user",
+    "default",
+    "deferrable",
+    "desc",
+    "distinct",
+    "do",
+    "else",
+    "end",
+    "except",
+    "false",
+    "fetch",
+    "for",
+    "foreign",
+    "freeze",
+    "from",
+    "full",
+    "grant",
+    "group",
+    "having",
+    "ilike",
+    "in",
+    "initially",
+    "inner",
+    "intersect",
+    "into",
+    "is",
+    "isnull",
+    "join",
+    "lateral",
+    "leading",
+    "left",
+    "like",
+    "limit",
+    "localtime",
+    "localtimestamp",
+    "natural",
+    "not",
+    "notnull",
+    "null",
+    "offset",
+    "on",
+    "only",
+    "or",
+    "order",
+    "outer",
+    "overlaps",
+    "placing",
+    "primary",
+    "references",
+    "returning",
+    "right",
+    "select",
+    "session_user",
+    "similar",
+    "some",
+    "symmetric",
+    "system_user",
+    "table",
+    "tablesample",
+    "then",
+    "to",
+    "trailing",
+    "true",
+    "union",
+    "unique",
+    "user",
+    "using",
+    "variadic",
+    "verbose",
+    "when",
+    "where",
+    "window",
+    "with",
+];
+
+// Sanitize a list of column names into unique snake_case Postgres identifiers
+// Output order matches input order
+pub(crate) fn sanitize_column_names(columns: &[String]) -> Vec<String> {
+    let mut used = HashSet::new();
+    columns
+        .iter()
+        .map(|column| {
+            let base = sanitize_identifier(column);
+            let mut candidate = base.clone();
+            let mut suffix = 2;
+            // DuckDB column names are case insensitive, but sanitized names are already lowercase
+            while !used.insert(candidate.clone()) {
+                let suffix_text = format!("_{}", suffix);
+                candidate = format!(
+                    "{}{}",
+                    truncate(&base, MAX_IDENTIFIER_LENGTH - suffix_text.len()),
+                    suffix_text
+                );
+                suffix += 1;
+            }
+            candidate
+        })
+        .collect()
+}
+
+pub(crate) fn sanitize_identifier(name: &str) -> String {
+    let mut result = String::with_capacity(name.len());
+    let mut previous: Option<char> = None;
+
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            // Split camelCase words - "siteCode" becomes "site_code"
+            if c.is_ascii_uppercase()
+                && previous.is_some_and(|p| p.is_ascii_lowercase() || p.is_ascii_digit())
+            {
+                result.push('_');
+            }
+            result.push(c.to_ascii_lowercase());
+        } else if !result.is_empty() && !result.ends_with('_') {
+            // Collapse any run of punctuation or whitespace into a single underscore
+            result.push('_');
+        }
+        previous = Some(c);
+    }
+
+    let mut result = result.trim_end_matches('_').to_string();
+    if result.is_empty() {
+        result = "column".to_string();
+    }
+    if result.starts_with(|c: char| c.is_ascii_digit()) {
+        result = format!("col_{}", result);
+    }
+    if RESERVED_WORDS.contains(&result.as_str()) {
+        result.push('_');
+    }
+    truncate(&result, MAX_IDENTIFIER_LENGTH).to_string()
+}
+
+fn truncate(identifier: &str, max_length: usize) -> &str {
+    // Sanitized identifiers are ASCII so byte slicing is safe
+    &identifier[..identifier.len().min(max_length)]
+}
+
+// Name of a table derived from another by adding a suffix, such as <table>__staging. Names
+// that would pass Postgres' limit are shortened and marked with a hash of the full table name,
+// so the truncated name can't fall back onto the table itself or onto another long name
+// sharing its prefix
+pub(crate) fn derived_name(table: &str, suffix: &str) -> String {
+    if table.len() + suffix.len() <= MAX_IDENTIFIER_LENGTH {
+        return format!("{}{}", table, suffix);
+    }
+    let hash = Sha256::digest(table.as_bytes())[..4]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+    let mut keep = MAX_IDENTIFIER_LENGTH.saturating_sub(suffix.len() + hash.len() + 1);
+    // Table names may hold multi-byte characters, which can't be split
+    while !table.is_char_boundary(keep) {
+        keep -= 1;
+    }
+    format!("{}_{}{}", &table[..keep], hash, suffix)
+}
+
+// Quote an identifier for Postgres or DuckDB SQL, doubling any embedded double quotes, so
+// names with quotes, semicolons or spaces can't break out of the statement
+pub(crate) fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+// Quoted schema-qualified name of a table
+pub(crate) fn qualified_name(schema: &str, table: &str) -> String {
+    format!("{}.{}", quote_identifier(schema), quote_identifier(table))
+}
+
+// Reject table and schema names Postgres can't hold as given - empty, longer than it keeps
+// (it would silently truncate them, so later statements miss the table) or containing NUL or
+// other control characters. Names derived from a valid table name by derived_name are valid
+// too, as it shortens them to fit
+pub(crate) fn validate_identifier(kind: &str, name: &str) -> Result<(), Box<dyn Error>> {
+    if name.trim().is_empty() {
+        return Err(format!("{} name must not be empty", kind).into());
+    }
+    if name.len() > MAX_IDENTIFIER_LENGTH {
+        return Err(format!(
+            "{} name '{}' is longer than Postgres' {} byte limit",
+            kind, name, MAX_IDENTIFIER_LENGTH
+        )
+        .into());
+    }
+    if name.chars().any(char::is_control) {
+        return Err(format!("{} name {:?} contains control characters", kind, name).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(columns: &[&str]) -> Vec<String> {
+        sanitize_column_names(&columns.iter().map(|c| c.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn quotes_identifiers_doubling_embedded_quotes() {
+        assert_eq!(quote_identifier("roads"), "\"roads\"");
+        assert_eq!(
+            quote_identifier("a\"; DROP TABLE x; --"),
+            "\"a\"\"; DROP TABLE x; --\""
+        );
+        assert_eq!(qualified_name("my schema", "t"), "\"my schema\".\"t\"");
+    }
+
+    #[test]
+    fn validates_identifiers() {
+        assert!(validate_identifier("Table", "roads").is_ok());
+        assert!(validate_identifier("Table", &"a".repeat(63)).is_ok());
+        assert!(validate_identifier("Table", &"a".repeat(64)).is_err());
+        assert!(validate_identifier("Table", " ").is_err());
+        assert!(validate_identifier("Table", "road\0s").is_err());
+        // 21 three-byte characters are 63 bytes
+        assert!(validate_identifier("Table", &"道".repeat(21)).is_ok());
+        assert!(validate_identifier("Table", &"道".repeat(22)).is_err());
+    }
+
+    #[test]
+    fn sanitizes_column_names() {
+        assert_eq!(
+            names(&["siteCode", "Site  Name!", "2019 total", "select", "", "--"]),
+            vec![
+                "site_code",
+                "site_name",
+                "col_2019_total",
+                "select_",
+                "column_",
+                "column__2"
+            ]
+        );
+    }
+
+    #[test]
+    fn sanitized_duplicates_get_numbered_within_the_limit() {
+        let long = "a".repeat(70);
+        let sanitized = names(&["Name", "name", "NAME", &long, &long]);
+        assert_eq!(sanitized[..3], ["name", "name_2", "name_3"]);
+        assert_eq!(sanitized[3], "a".repeat(63));
+        assert_eq!(sanitized[4], format!("{}_2", "a".repeat(61)));
+    }
+
+    #[test]
+    fn derived_names_fit_and_stay_distinct() {
+        assert_eq!(derived_name("roads", "__staging"), "roads__staging");
+        let long = "a".repeat(63);
+        let other = format!("{}b", "a".repeat(62));
+        for suffix in ["__staging", "_summary", "_tiles", "_empty_geometry", "__v"] {
+            let derived = derived_name(&long, suffix);
+            assert!(validate_identifier("Table", &derived).is_ok());
+            assert!(derived.ends_with(suffix));
+            assert_ne!(derived, derived_name(&other, suffix));
+        }
+        // Multi-byte characters aren't split
+        let derived = derived_name(&"道".repeat(21), "_summary");
+        assert!(derived.len() <= 63 && derived.ends_with("_summary"));
+    }
+}
@@ -0,0 +1,735 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use super::{excel, CancellationToken, LoadProgress, WriteThrottle};
+
+// Options controlling how a file is transformed and loaded
+// Defaults reproduce the behaviour of launch_process_file
+#[derive(Debug, Clone)]
+pub struct ProcessorOptions {
+    // Features requested per page from a remote source such as OAPIF:<collection url>
+    pub remote_page_size: usize,
+    // Only fetch remote features within this WGS84 [min_x, min_y, max_x, max_y] box
+    pub remote_bbox: Option<[f64; 4]>,
+    // Stop fetching from a remote source after this many features
+    pub remote_max_features: Option<usize>,
+    // Named option profile, see register_profile - explicit options take precedence
+    pub profile: Option<String>,
+    // EPSG code to assume when a file declares no CRS
+    pub assumed_crs: Option<String>,
+    // Keep geometry in its source CRS, with that SRID in PostGIS, instead of transforming to
+    // EPSG:4326 - for area and length calculations in a projected CRS
+    pub preserve_crs: bool,
+    // EPSG code geometry is transformed to, instead of EPSG:4326
+    pub target_crs: Option<String>,
+    // Decimal places kept in geometry coordinates, in units of the target CRS - 6 or 7 is
+    // around 10cm to 1cm in EPSG:4326 and makes web mapping tables smaller
+    pub coordinate_precision: Option<u8>,
+    // What to do with features that cross the antimeridian once in EPSG:4326
+    pub antimeridian: Antimeridian,
+    // Promote single geometries to multi when a column mixes the two, e.g. POLYGON and
+    // MULTIPOLYGON in a shapefile
+    pub promote_to_multi: bool,
+    // How a layer mixing points, lines and polygons is loaded
+    pub mixed_geometry: MixedGeometry,
+    // What to do with rows whose geometry is NULL or EMPTY
+    pub empty_geometry: EmptyGeometry,
+    // Skip up to this many malformed CSV rows instead of failing, listing them in the load
+    // report - a read with more rejected rows still fails
+    pub csv_max_rejected_rows: Option<u64>,
+    // Source text encoding - passed to read_csv, or as the GDAL ENCODING option for shapefiles
+    // Shapefiles otherwise use the codepage in their .cpg file or DBF header
+    pub encoding: Option<String>,
+    // Storage type for raw coordinate attribute columns (lat/lon, x/y, easting/northing)
+    pub coordinate_storage: CoordinateStorage,
+    // Column to carry the source feature ID (FID) for Geopackage, Shapefile and GeoJSON files
+    pub fid_column: Option<String>,
+    // Elements and tags loaded from OpenStreetMap PBF files
+    pub osm: OsmOptions,
+    // CAD layers and geometry types loaded from DXF drawings
+    pub dxf: DxfOptions,
+    // How delimited text files are read
+    pub csv: CsvOptions,
+    // Where the table sits in an Excel sheet
+    pub excel: ExcelOptions,
+    // How GeoTIFF rasters are loaded
+    pub raster: RasterLoad,
+    // DuckDB type overrides keyed by source column name, e.g. "postcode" => "VARCHAR"
+    pub column_types: HashMap<String, String>,
+    // What to do when the source has duplicate column names
+    pub duplicate_columns: DuplicateColumns,
+    // Source columns to keep - all columns are kept when None
+    pub include_columns: Option<Vec<String>>,
+    // Source columns to drop
+    pub exclude_columns: Vec<String>,
+    // Renames keyed by source column name, applied before column naming
+    pub rename_columns: HashMap<String, String>,
+    // How source column names are mapped to Postgres column names
+    pub column_naming: ColumnNaming,
+    // Where the transformed data is written
+    pub output_target: OutputTarget,
+    // How rows are written to Postgres
+    pub write_method: WriteMethod,
+    // What to do when the load changes the columns of an existing table
+    pub schema_drift: SchemaDrift,
+    // Apply only inserts, updates and deletes to an existing table, matching rows on this key
+    pub incremental: Option<IncrementalKey>,
+    // Keep this many versions of the table, as <table>__v<timestamp>, behind a view of the latest
+    pub keep_versions: Option<usize>,
+    // Role to own the loaded table, instead of the loader's role
+    pub table_owner: Option<String>,
+    // Roles granted SELECT on the loaded table, e.g. the app's read-only role or PUBLIC
+    pub grant_select: Vec<String>,
+    // Statistics gathering run on the table after a PostGIS load
+    pub maintenance: Maintenance,
+    // Name the target Postgres database is attached under in DuckDB - unique per processor
+    // when unset
+    pub attach_alias: Option<String>,
+    // Token another thread can use to cancel the load
+    pub cancellation: Option<CancellationToken>,
+    // Time limits for the load and its stages
+    pub timeouts: Timeouts,
+    // Handle the load reports its stage and rows written to
+    pub progress: Option<LoadProgress>,
+    // Append into a date partitioned target, taking the date from the file name
+    pub partition: Option<FilePartition>,
+    // Limit the Postgres write rate - keep a clone to adjust it during the load
+    pub write_throttle: Option<WriteThrottle>,
+    // Locked-down mode for untrusted uploads - local sources only, no extension installs or
+    // network file systems, and no local file access once the source has been read
+    pub locked_down: bool,
+    // Compute a deterministic content checksum of the transformed data
+    pub compute_checksum: bool,
+    // Profile the read, transform and write queries with DuckDB, adding their timings and
+    // slowest operators to the load report
+    pub profile_queries: bool,
+    // Maximum number of geometry column conversions sent in one postgres_execute call
+    pub geometry_batch_size: usize,
+    // Run CREATE EXTENSION postgis when the target database doesn't have it, instead of
+    // failing the load
+    pub create_postgis_extension: bool,
+    // Times to re-attach Postgres and retry a step when the session drops mid-load
+    pub reconnect_attempts: u32,
+    // Write a COMMENT ON TABLE describing where the table came from
+    pub table_comment: bool,
+    // Table in the target schema that receives a JSONB provenance row per loaded table
+    pub metadata_table: Option<String>,
+    // Schema holding the _duckdb_postgis_loads history table - no history is kept when None
+    pub load_history_schema: Option<String>,
+    // Column whose values get a feature count and bbox in a <table>_summary table
+    pub summary_category_column: Option<String>,
+    // Generate a <table>_tiles table of vector tiles after loading geometry
+    pub vector_tiles: Option<VectorTiles>,
+}
+
+impl Default for ProcessorOptions {
+    fn default() -> Self {
+        Self {
+            remote_page_size: 1000,
+            remote_bbox: None,
+            remote_max_features: None,
+            profile: None,
+            assumed_crs: None,
+            preserve_crs: false,
+            target_crs: None,
+            coordinate_precision: None,
+            antimeridian: Antimeridian::default(),
+            promote_to_multi: true,
+            mixed_geometry: MixedGeometry::default(),
+            empty_geometry: EmptyGeometry::default(),
+            csv_max_rejected_rows: None,
+            encoding: None,
+            coordinate_storage: CoordinateStorage::default(),
+            fid_column: None,
+            osm: OsmOptions::default(),
+            dxf: DxfOptions::default(),
+            csv: CsvOptions::default(),
+            excel: ExcelOptions::default(),
+            raster: RasterLoad::default(),
+            column_types: HashMap::new(),
+            duplicate_columns: DuplicateColumns::default(),
+            include_columns: None,
+            exclude_columns: Vec::new(),
+            rename_columns: HashMap::new(),
+            column_naming: ColumnNaming::default(),
+            output_target: OutputTarget::default(),
+            write_method: WriteMethod::default(),
+            schema_drift: SchemaDrift::default(),
+            incremental: None,
+            keep_versions: None,
+            table_owner: None,
+            grant_select: Vec::new(),
+            maintenance: Maintenance::default(),
+            attach_alias: None,
+            cancellation: None,
+            timeouts: Timeouts::default(),
+            progress: None,
+            partition: None,
+            write_throttle: None,
+            locked_down: false,
+            compute_checksum: false,
+            profile_queries: false,
+            geometry_batch_size: 10,
+            create_postgis_extension: false,
+            reconnect_attempts: 3,
+            table_comment: true,
+            metadata_table: None,
+            load_history_schema: None,
+            summary_category_column: None,
+            vector_tiles: None,
+        }
+    }
+}
+
+// Policy for turning source column names into Postgres column names
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ColumnNaming {
+    // Keep source column names as they are
+    #[default]
+    Preserve,
+    // Convert to snake_case, strip punctuation, avoid reserved words and de-duplicate
+    Sanitize,
+}
+
+// Policy for source columns that share a name
+// DuckDB column names are case insensitive, so "Name" and "name" are duplicates
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum DuplicateColumns {
+    // Keep the first column and suffix later ones with _2, _3, ... in source order
+    #[default]
+    Rename,
+    // Fail before any data is loaded
+    Fail,
+}
+
+// Handling of EPSG:4326 features crossing the antimeridian, which otherwise span the whole
+// world and render as streaks - applied in PostGIS to features wider than 180 degrees
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Antimeridian {
+    // Load geometry as transformed
+    #[default]
+    Keep,
+    // Split into parts either side of 180 degrees, each in -180 to 180
+    Split,
+    // Shift to 0-360 longitudes, for Pacific centred maps
+    Shift,
+}
+
+// Policy for layers mixing geometry families, such as GeoJSON with points and polygons
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum MixedGeometry {
+    // Load every feature into one generic geometry column
+    #[default]
+    Generic,
+    // Load each family present into its own <table>_points, _lines or _polygons table
+    SeparateTables,
+    // Load only the features of one family, dropping the rest
+    Filter(GeometryFamily),
+}
+
+// Policy for rows with NULL or EMPTY geometry - counted in the load report either way
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum EmptyGeometry {
+    // Load them with the rest
+    #[default]
+    Keep,
+    // Leave them out of the load
+    Drop,
+    // Load them into a <table>_empty_geometry table instead, with geometry as WKT text
+    SideTable,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GeometryFamily {
+    Points,
+    Lines,
+    Polygons,
+}
+
+// Destination for the transformed data
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum OutputTarget {
+    // Load into the PostGIS table given to launch_process_file_with_options
+    #[default]
+    Postgis,
+    // Write a GeoPackage file instead, replacing any existing file at the path
+    // The layer is named after the file and geometry is stored in EPSG:4326, or the source CRS
+    // with preserve_crs, or target_crs when set
+    Geopackage(PathBuf),
+    // Write a SpatiaLite database instead, for mobile and offline apps - as for Geopackage
+    Spatialite(PathBuf),
+}
+
+// How rows are written to Postgres
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WriteMethod {
+    // Write through the DuckDB postgres extension
+    #[default]
+    Attach,
+    // Export to CSV and stream it in with COPY FROM STDIN - much faster for large tables. The
+    // direct connection this needs has no TLS, so rows go through the attach when it fails
+    Copy,
+}
+
+// Policy for a re-load whose columns differ from the existing table - the changes are listed
+// in the load report either way
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SchemaDrift {
+    // Replace the table with the new schema
+    #[default]
+    Replace,
+    // Fail before the existing table is touched
+    Fail,
+    // Take added and retyped columns, and keep removed columns as empty columns
+    Migrate,
+}
+
+// How an incremental load matches incoming rows to the existing table
+#[derive(Debug, Clone, PartialEq)]
+pub enum IncrementalKey {
+    // Rows with the same values in these columns are the same row, updated when anything else
+    // differs
+    Columns(Vec<String>),
+    // Rows are matched on a hash of every column, so changed rows are deleted and re-inserted
+    RowHash,
+}
+
+// Maintenance run on the target table once it's loaded
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Maintenance {
+    None,
+    // ANALYZE, so the planner has statistics for the new data
+    #[default]
+    Analyze,
+    // VACUUM (ANALYZE), which also sets the visibility map for index-only scans
+    VacuumAnalyze,
+}
+
+// Time limits for a load. Running Postgres statements are cancelled as soon as a limit passes,
+// while a running DuckDB statement finishes and the load stops at its next checkpoint, between
+// stages and write batches. A load that runs out of time stops with an io::ErrorKind::TimedOut
+// error
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Timeouts {
+    // Detecting the file type and reading the source into DuckDB
+    pub detection: Option<Duration>,
+    // Transforming geometry, running the transform hook and computing checksums
+    pub transform: Option<Duration>,
+    // Writing to Postgres - also applied as the Postgres statement_timeout and connect_timeout
+    pub write: Option<Duration>,
+    // The whole load
+    pub total: Option<Duration>,
+}
+
+// Date partitioned append, e.g. for monthly drops named like crime_2024-06.csv
+// Each file replaces its own partition of the target and leaves the others in place
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilePartition {
+    // Regular expression matched against the file name - the first capture group holds the date
+    pub pattern: String,
+    // strptime format of the captured date, e.g. "%Y-%m"
+    pub date_format: String,
+    // DATE column added to every row and used as the partition key
+    pub column: String,
+    // Range covered by each partition, e.g. "1 month"
+    pub interval: String,
+}
+
+// Zoom range of the vector tile pyramid, written with ST_AsMVT
+// Each zoom level has four times as many tiles as the one before, so keep max_zoom modest
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VectorTiles {
+    pub min_zoom: u8,
+    pub max_zoom: u8,
+}
+
+// OpenStreetMap element types and tags to load from a .osm.pbf file
+#[derive(Debug, Clone, PartialEq)]
+pub struct OsmOptions {
+    // Tagged nodes, as points
+    pub nodes: bool,
+    // Ways, as lines or polygons when closed
+    pub ways: bool,
+    // Relations, with tags but no geometry
+    pub relations: bool,
+    // Tags to extract as columns - all tags are kept in a JSON tags column when empty
+    pub tags: Vec<String>,
+}
+
+impl Default for OsmOptions {
+    fn default() -> Self {
+        Self {
+            nodes: true,
+            ways: true,
+            relations: false,
+            tags: Vec::new(),
+        }
+    }
+}
+
+// How delimited text files are read
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CsvOptions {
+    // Whether the first row holds the column names - detected from the file when None.
+    // Without one the columns are named column_0, column_1, ...
+    pub has_header: Option<bool>,
+    // Rows sampled to detect column types - DuckDB's default of 20480 when None, or -1 for
+    // the whole file, which is slower but can't be caught out by a late value
+    pub sample_size: Option<i64>,
+    // Read every column as text, leaving types to column_types. Without it a file whose
+    // detected types fail part way through is read again this way, with a warning
+    pub all_varchar: bool,
+    // strptime format of date columns, such as %d/%m/%Y - detected when None
+    pub dateformat: Option<String>,
+    // Decimal separator of numbers, ',' for files written in many European locales
+    pub decimal_separator: Option<char>,
+}
+
+// Rows of an Excel sheet to read, for sheets with titles or notes above the table
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExcelOptions {
+    // Rows dropped from the top of the sheet
+    pub skip_rows: usize,
+    // Row holding the column names, counted from 1 after the skipped rows - rows between the
+    // skipped ones and the header are dropped too
+    pub header_row: usize,
+    // Whether the sheet has a header row - without one the columns are named Field1, Field2, ...
+    pub has_header: bool,
+    // Cells holding the table, as an A1 range such as B3:H500 or 'Q3 Report'!B3:H500, or the
+    // name of a range defined in the workbook. Cells outside it are dropped, and skip_rows and
+    // header_row count from its first row
+    pub range: Option<String>,
+    // Columns holding dates, by name, loaded as DATE, or TIMESTAMP when a value has a time.
+    // Excel serial numbers in them are converted from date_system, and text from date_format
+    pub date_columns: Vec<String>,
+    // Day the serial numbers in date_columns count from
+    pub date_system: ExcelDateSystem,
+    // strptime format of text dates in date_columns, such as %d/%m/%Y for UK dates - ISO and
+    // GDAL's 2024/01/31 dates are read without one
+    pub date_format: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExcelDateSystem {
+    // The workbook's own setting, or 1900 for .xls workbooks
+    #[default]
+    Auto,
+    // Windows Excel, where serial 1 is 1 January 1900
+    #[serde(rename = "1900")]
+    Excel1900,
+    // Older Mac Excel, where serial 0 is 1 January 1904
+    #[serde(rename = "1904")]
+    Excel1904,
+}
+
+impl Default for ExcelOptions {
+    fn default() -> Self {
+        Self {
+            skip_rows: 0,
+            header_row: 1,
+            has_header: true,
+            range: None,
+            date_columns: Vec::new(),
+            date_system: ExcelDateSystem::Auto,
+            date_format: None,
+        }
+    }
+}
+
+impl ExcelOptions {
+    // Whether the sheet can be read as GDAL lays it out, with the column names in row 1
+    pub(crate) fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+// Entity filtering for DXF drawings, which carry no CRS - set assumed_crs for them
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DxfOptions {
+    // CAD layers to load - all layers are loaded when empty
+    pub layers: Vec<String>,
+    // Geometry family to keep from the mixed entities in a drawing
+    pub geometry: DxfGeometry,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum DxfGeometry {
+    // Keep every entity in one geometry column, with its type in a geometry_type column
+    #[default]
+    Mixed,
+    Points,
+    Lines,
+    Polygons,
+}
+
+// How a GeoTIFF is loaded - only classic (not Big) TIFF files are read
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum RasterLoad {
+    // One row with the raster's footprint polygon, size, band count and pixel size
+    #[default]
+    Footprint,
+    // The pixels as a PostGIS raster column, cut into tiles of this size and kept in the
+    // file's own CRS - PostGIS must allow the GTiff driver (postgis.gdal_enabled_drivers)
+    Tiled {
+        tile_width: u32,
+        tile_height: u32,
+    },
+}
+
+// How raw coordinate attribute columns are written to PostGIS
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CoordinateStorage {
+    // Keep the DOUBLE type inferred by DuckDB
+    #[default]
+    Double,
+    // Cast to NUMERIC(precision, scale) so re-loads produce identical values
+    Numeric {
+        precision: u8,
+        scale: u8,
+    },
+}
+
+impl ProcessorOptions {
+    pub(crate) fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if let CoordinateStorage::Numeric { precision, scale } = self.coordinate_storage {
+            // DuckDB decimals support a maximum width of 38
+            if precision == 0 || precision > 38 || scale > precision {
+                return Err(format!(
+                    "Invalid coordinate storage NUMERIC({}, {}): precision must be between 1 and 38 and scale must not exceed it",
+                    precision, scale
+                )
+                .into());
+            }
+        }
+        if let Some(crs) = &self.assumed_crs {
+            if crs.is_empty() || !crs.chars().all(|c| c.is_ascii_digit()) {
+                return Err(format!("Assumed CRS '{}' must be a numeric EPSG code", crs).into());
+            }
+        }
+        if let Some(encoding) = &self.encoding {
+            let valid = !encoding.is_empty()
+                && encoding
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+            if !valid {
+                return Err(format!("Invalid encoding '{}'", encoding).into());
+            }
+        }
+        if self.excel.header_row == 0 {
+            return Err("Excel header_row counts from 1".into());
+        }
+        if !self.excel.has_header && self.excel.header_row != 1 {
+            return Err("Excel header_row can't be set for a sheet without a header".into());
+        }
+        if let Some(sample_size) = self.csv.sample_size {
+            if sample_size == 0 || sample_size < -1 {
+                return Err(format!(
+                    "CSV sample_size {} must be a number of rows, or -1 for the whole file",
+                    sample_size
+                )
+                .into());
+            }
+        }
+        if let Some(format) = &self.csv.dateformat {
+            if !format.contains('%') {
+                return Err(format!(
+                    "CSV dateformat '{}' has no strptime fields, such as %d/%m/%Y",
+                    format
+                )
+                .into());
+            }
+        }
+        if let Some(separator) = self.csv.decimal_separator {
+            if separator != '.' && separator != ',' {
+                return Err(format!(
+                    "CSV decimal_separator '{}' must be '.' or ','",
+                    separator
+                )
+                .into());
+            }
+        }
+        if let Some(range) = &self.excel.range {
+            excel::validate_range(range)?;
+        }
+        if let Some(format) = &self.excel.date_format {
+            if !format.contains('%') {
+                return Err(format!(
+                    "Excel date_format '{}' has no strptime fields, such as %d/%m/%Y",
+                    format
+                )
+                .into());
+            }
+        }
+        if self.write_method == WriteMethod::Copy && self.write_throttle.is_some() {
+            return Err("Write throttling is not supported with the COPY write method".into());
+        }
+        if let Some(partition) = &self.partition {
+            if partition.pattern.is_empty()
+                || partition.date_format.is_empty()
+                || partition.column.trim().is_empty()
+            {
+                return Err("Partition pattern, date format and column must not be empty".into());
+            }
+            // The interval is interpolated into SQL so only allow interval syntax characters
+            let valid = !partition.interval.trim().is_empty()
+                && partition
+                    .interval
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == ' ');
+            if !valid {
+                return Err(format!("Invalid partition interval '{}'", partition.interval).into());
+            }
+        }
+        if self.locked_down && self.write_method == WriteMethod::Copy {
+            // The COPY path exports through a local temporary file
+            return Err("The COPY write method is not available in locked-down mode".into());
+        }
+        if self.locked_down && self.profile_queries {
+            // Profiling is a DuckDB setting, which locked-down mode fixes once the source is read
+            return Err("Query profiling is not available in locked-down mode".into());
+        }
+        if self.output_target != OutputTarget::Postgis {
+            if self.locked_down {
+                return Err("File output targets are not available in locked-down mode".into());
+            }
+            if self.partition.is_some()
+                || self.summary_category_column.is_some()
+                || self.metadata_table.is_some()
+                || self.vector_tiles.is_some()
+            {
+                return Err(
+                    "Partitioning, summary, metadata and tile tables require the PostGIS output target"
+                        .into(),
+                );
+            }
+        }
+        if let Some(tiles) = self.vector_tiles {
+            if tiles.min_zoom > tiles.max_zoom || tiles.max_zoom > 22 {
+                return Err(format!(
+                    "Invalid vector tile zooms {}-{}: zooms must be ascending and at most 22",
+                    tiles.min_zoom, tiles.max_zoom
+                )
+                .into());
+            }
+        }
+        if let RasterLoad::Tiled {
+            tile_width,
+            tile_height,
+        } = self.raster
+        {
+            if tile_width == 0 || tile_height == 0 || tile_width > 10000 || tile_height > 10000 {
+                return Err(format!(
+                    "Invalid raster tile size {}x{}: tiles must be between 1 and 10000 pixels a side",
+                    tile_width, tile_height
+                )
+                .into());
+            }
+            if self.output_target != OutputTarget::Postgis || self.write_method == WriteMethod::Copy
+            {
+                return Err(
+                    "Tiled raster loads require the PostGIS output target and ATTACH write method"
+                        .into(),
+                );
+            }
+        }
+        if self.incremental.is_some()
+            && (self.partition.is_some()
+                || self.output_target != OutputTarget::Postgis
+                || self.raster != RasterLoad::Footprint)
+        {
+            return Err(
+                "Incremental loads require the PostGIS target without partitioning or raster tiles"
+                    .into(),
+            );
+        }
+        if let Some(keep_versions) = self.keep_versions {
+            if keep_versions == 0 {
+                return Err("keep_versions must keep at least one version".into());
+            }
+            if self.incremental.is_some()
+                || self.partition.is_some()
+                || self.output_target != OutputTarget::Postgis
+                || self.raster != RasterLoad::Footprint
+            {
+                return Err("Table versions require a full PostGIS load without partitioning or raster tiles".into());
+            }
+        }
+        if (self.table_owner.is_some() || !self.grant_select.is_empty())
+            && self.output_target != OutputTarget::Postgis
+        {
+            return Err("Table owner and grants require the PostGIS output target".into());
+        }
+        if let Some(alias) = &self.attach_alias {
+            // The alias is spliced into SQL unquoted, so only plain identifiers are allowed
+            let valid = alias.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && alias.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !valid {
+                return Err(format!("Invalid attach alias '{}'", alias).into());
+            }
+        }
+        if self.schema_drift == SchemaDrift::Migrate && self.partition.is_some() {
+            return Err("Schema migration is not supported with partitioned loads".into());
+        }
+        if self.empty_geometry == EmptyGeometry::SideTable
+            && self.output_target != OutputTarget::Postgis
+        {
+            return Err("The empty geometry side table requires the PostGIS output target".into());
+        }
+        if self.mixed_geometry == MixedGeometry::SeparateTables
+            && (self.partition.is_some() || self.output_target != OutputTarget::Postgis)
+        {
+            return Err(
+                "Separate tables per geometry type require the PostGIS target without partitioning"
+                    .into(),
+            );
+        }
+        if let Some(target_crs) = &self.target_crs {
+            if self.preserve_crs {
+                return Err("Set either preserve_crs or target_crs, not both".into());
+            }
+            if target_crs.is_empty() || !target_crs.chars().all(|c| c.is_ascii_digit()) {
+                return Err(format!("Target CRS '{}' must be an EPSG code", target_crs).into());
+            }
+        }
+        if self.antimeridian != Antimeridian::Keep
+            && (self.preserve_crs
+                || self.target_crs.as_deref().is_some_and(|crs| crs != "4326")
+                || self.output_target != OutputTarget::Postgis)
+        {
+            return Err(
+                "Antimeridian handling requires EPSG:4326 output to the PostGIS target".into(),
+            );
+        }
+        if self.coordinate_precision.is_some_and(|digits| digits > 15) {
+            return Err("Coordinate precision must be at most 15 decimal places".into());
+        }
+        if self.remote_page_size == 0 {
+            return Err("Remote page size must be at least 1".into());
+        }
+        if self.geometry_batch_size == 0 {
+            return Err("Geometry batch size must be at least 1".into());
+        }
+        for (column, data_type) in &self.column_types {
+            // Type names are interpolated into SQL so only allow type syntax characters
+            let valid = !data_type.trim().is_empty()
+                && data_type
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || " _(),[]".contains(c));
+            if !valid {
+                return Err(format!("Invalid type '{}' for column '{}'", data_type, column).into());
+            }
+        }
+        if let Some(fid_column) = &self.fid_column {
+            if fid_column.trim().is_empty() {
+                return Err("FID column name must not be empty".into());
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,84 @@
+use std::error::Error;
+
+use super::{naming, sql, DuckDBFileProcessor};
+
+impl DuckDBFileProcessor {
+    // Build OSM features with point, line or polygon geometry from the raw PBF elements
+    pub(super) fn osm_select(&self) -> Result<String, Box<dyn Error>> {
+        let osm = &self.options.osm;
+        // The element list is scanned several times, so read the file once
+        self.conn.execute(
+            &format!(
+                "CREATE TEMP TABLE osm_elements AS SELECT * FROM st_readosm({});",
+                sql::string_literal(&self.file_path)
+            ),
+            [],
+        )?;
+
+        let mut parts = Vec::new();
+        if osm.nodes {
+            // Untagged nodes are only way vertices
+            parts.push(
+                "SELECT 'node' AS osm_type, id AS osm_id, tags, ST_Point(lon, lat) AS geom
+                FROM osm_elements WHERE kind = 'node' AND cardinality(tags) > 0"
+                    .to_string(),
+            );
+        }
+        if osm.ways {
+            // Closed ways are areas unless tagged as linear features such as roundabouts
+            parts.push(
+                "SELECT 'way' AS osm_type, ways.id AS osm_id, ways.tags,
+                    CASE WHEN ST_IsClosed(lines.line) AND ST_NPoints(lines.line) >= 4
+                        AND (map_extract(ways.tags, 'area')[1] = 'yes'
+                            OR (map_extract(ways.tags, 'highway')[1] IS NULL
+                                AND map_extract(ways.tags, 'barrier')[1] IS NULL))
+                    THEN ST_MakePolygon(lines.line) ELSE lines.line END AS geom
+                FROM osm_elements AS ways
+                JOIN (
+                    SELECT refs.id, ST_MakeLine(list(ST_Point(nodes.lon, nodes.lat) ORDER BY refs.idx)) AS line
+                    FROM (
+                        SELECT id, unnest(refs) AS ref, unnest(range(len(refs))) AS idx
+                        FROM osm_elements WHERE kind = 'way'
+                    ) AS refs
+                    JOIN osm_elements AS nodes ON nodes.kind = 'node' AND nodes.id = refs.ref
+                    GROUP BY refs.id
+                ) AS lines ON lines.id = ways.id
+                WHERE ways.kind = 'way'"
+                    .to_string(),
+            );
+        }
+        if osm.relations {
+            // Relation geometry would need member assembly, so relations carry tags only
+            parts.push(
+                "SELECT 'relation' AS osm_type, id AS osm_id, tags, NULL::GEOMETRY AS geom
+                FROM osm_elements WHERE kind = 'relation'"
+                    .to_string(),
+            );
+        }
+        if parts.is_empty() {
+            return Err("No OSM element types selected".into());
+        }
+
+        // Requested tags become columns, otherwise every tag is kept as JSON
+        let tag_columns = if osm.tags.is_empty() {
+            "CAST(to_json(tags) AS VARCHAR) AS tags".to_string()
+        } else {
+            osm.tags
+                .iter()
+                .map(|tag| {
+                    format!(
+                        "map_extract(tags, {})[1] AS {}",
+                        sql::string_literal(tag),
+                        naming::quote_identifier(tag)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        Ok(format!(
+            "SELECT osm_type, osm_id, {}, geom FROM ({}) AS elements",
+            tag_columns,
+            parts.join(" UNION ALL ")
+        ))
+    }
+}
@@ -0,0 +1,173 @@
+use duckdb::Connection;
+use std::error::Error;
+use std::path::Path;
+
+use super::logging::{log_debug, log_info};
+use super::{naming, sql, DuckDBFileProcessor, FilePartition};
+
+impl DuckDBFileProcessor {
+    // Add the date taken from the file name as a column on every row
+    pub(super) fn apply_file_partition(&self) -> Result<(), Box<dyn Error>> {
+        let Some(partition) = &self.options.partition else {
+            return Ok(());
+        };
+
+        let columns = self.get_column_names("data")?;
+        if columns
+            .iter()
+            .any(|c| c.eq_ignore_ascii_case(&partition.column))
+        {
+            return Err(format!(
+                "Partition column '{}' already exists in source data",
+                partition.column
+            )
+            .into());
+        }
+
+        let (start, _) = self.partition_bounds()?;
+        self.conn.execute(
+            &format!(
+                "ALTER TABLE data ADD COLUMN {} DATE DEFAULT DATE '{}';",
+                naming::quote_identifier(&partition.column),
+                start
+            ),
+            [],
+        )?;
+        log_debug!("Partition {} = {}", partition.column, start);
+        self.report.borrow_mut().partition_date = Some(start);
+        Ok(())
+    }
+
+    // Start and end dates of this file's partition, parsed from the file name
+    fn partition_bounds(&self) -> Result<(String, String), Box<dyn Error>> {
+        let Some(partition) = &self.options.partition else {
+            return Err("No partition configured".into());
+        };
+        let source_name = self.source_name();
+        let file_name = Path::new(&source_name)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        file_partition_bounds(&self.conn, partition, &file_name)
+    }
+
+    // Replace this file's partition with the staging table, creating the partitioned target if needed
+    pub(super) fn attach_partition(&self, staging_table: &str) -> Result<(), Box<dyn Error>> {
+        let Some(partition) = &self.options.partition else {
+            return Err("No partition configured".into());
+        };
+        let (start, end) = self.partition_bounds()?;
+        let partition_name =
+            naming::derived_name(&self.table_name, &format!("_{}", start.replace('-', "")));
+        let schema_qualified_table = naming::qualified_name(&self.schema_name, &self.table_name);
+        let schema_qualified_partition = naming::qualified_name(&self.schema_name, &partition_name);
+
+        // One transaction, so a re-load of the same file swaps its partition atomically
+        let attach_sql = format!(
+            "BEGIN;
+            CREATE TABLE IF NOT EXISTS {target} (LIKE {staging}) PARTITION BY RANGE ({column});
+            DROP TABLE IF EXISTS {partition};
+            ALTER TABLE {staging} RENAME TO {partition_name};
+            ALTER TABLE {target} ATTACH PARTITION {partition} FOR VALUES FROM ('{start}') TO ('{end}');
+            COMMIT;",
+            target = schema_qualified_table,
+            staging = staging_table,
+            column = naming::quote_identifier(&partition.column),
+            partition = schema_qualified_partition,
+            partition_name = naming::quote_identifier(&partition_name),
+            start = start,
+            end = end,
+        );
+        self.postgres_execute("attach partition", &attach_sql)?;
+        log_info!(
+            "Partition {} attached to {} for [{}, {})",
+            partition_name,
+            self.table_name,
+            start,
+            end
+        );
+        Ok(())
+    }
+}
+
+// Start and end dates of the partition a file name falls in
+fn file_partition_bounds(
+    conn: &Connection,
+    partition: &FilePartition,
+    file_name: &str,
+) -> Result<(String, String), Box<dyn Error>> {
+    let query = format!(
+        "SELECT captured, CAST(start_date AS VARCHAR),
+            CAST(CAST(start_date + INTERVAL '{interval}' AS DATE) AS VARCHAR)
+        FROM (
+            SELECT captured, CAST(try_strptime(captured, {format}) AS DATE) AS start_date
+            FROM (SELECT regexp_extract({file}, {pattern}, 1) AS captured)
+        );",
+        file = sql::string_literal(file_name),
+        pattern = sql::string_literal(&partition.pattern),
+        format = sql::string_literal(&partition.date_format),
+        interval = partition.interval,
+    );
+    let (captured, start, end): (String, Option<String>, Option<String>) =
+        conn.query_row(&query, [], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })?;
+    match (start, end) {
+        (Some(start), Some(end)) => Ok((start, end)),
+        _ if captured.is_empty() => Err(format!(
+            "File name '{}' does not match partition pattern '{}'",
+            file_name, partition.pattern
+        )
+        .into()),
+        _ => Err(format!(
+            "Could not parse '{}' from file name '{}' with date format '{}'",
+            captured, file_name, partition.date_format
+        )
+        .into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monthly() -> FilePartition {
+        FilePartition {
+            pattern: r"_(\d{4}-\d{2})\.".to_string(),
+            date_format: "%Y-%m".to_string(),
+            column: "month".to_string(),
+            interval: "1 month".to_string(),
+        }
+    }
+
+    #[test]
+    fn takes_the_partition_from_the_file_name() {
+        let conn = Connection::open_in_memory().unwrap();
+        let bounds = file_partition_bounds(&conn, &monthly(), "crime_2024-06.csv").unwrap();
+        assert_eq!(bounds, ("2024-06-01".to_string(), "2024-07-01".to_string()));
+
+        let bounds = file_partition_bounds(&conn, &monthly(), "o'brien_2024-12.csv").unwrap();
+        assert_eq!(bounds, ("2024-12-01".to_string(), "2025-01-01".to_string()));
+
+        let daily = FilePartition {
+            pattern: r"(\d{8})".to_string(),
+            date_format: "%Y%m%d".to_string(),
+            column: "day".to_string(),
+            interval: "1 day".to_string(),
+        };
+        let bounds = file_partition_bounds(&conn, &daily, "readings20240229.parquet").unwrap();
+        assert_eq!(bounds, ("2024-02-29".to_string(), "2024-03-01".to_string()));
+    }
+
+    #[test]
+    fn rejects_file_names_without_a_date() {
+        let conn = Connection::open_in_memory().unwrap();
+        let error = file_partition_bounds(&conn, &monthly(), "crime_latest.csv").unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("does not match partition pattern"));
+
+        let error = file_partition_bounds(&conn, &monthly(), "crime_2024-13.csv").unwrap_err();
+        assert!(error.to_string().contains("Could not parse '2024-13'"));
+    }
+}
@@ -0,0 +1,163 @@
+use std::error::Error;
+
+use super::{connection, credentials, naming, sql, DuckDBFileProcessor};
+
+// Another Postgres database read as the source, given as PG:<connection>|<schema.table or query>
+#[derive(Debug, Clone)]
+pub(super) struct PostgresSource {
+    connection: String,
+    relation: String,
+}
+
+impl PostgresSource {
+    pub(super) fn parse(file_path: &str) -> Option<Self> {
+        let source = file_path
+            .get(..3)
+            .filter(|prefix| prefix.eq_ignore_ascii_case("PG:"))
+            .map(|_| &file_path[3..])?;
+        let (connection, relation) = source.split_once('|')?;
+        Some(Self {
+            connection: credentials::resolve_connection(connection.trim()),
+            relation: relation.trim().trim_end_matches(';').to_string(),
+        })
+    }
+
+    // Connection with the password removed, plus the table or query
+    pub(super) fn display_name(&self) -> String {
+        format!(
+            "PG:{}|{}",
+            credentials::redact(&self.connection, &self.connection),
+            self.relation
+        )
+    }
+
+    pub(super) fn connection(&self) -> &str {
+        &self.connection
+    }
+
+    // The relation as a query to run in the source database
+    fn query(&self) -> String {
+        let lowered = self.relation.to_lowercase();
+        if lowered.starts_with("select") || lowered.starts_with("with") {
+            return self.relation.clone();
+        }
+        let quoted = self
+            .relation
+            .split('.')
+            .map(|part| naming::quote_identifier(part.trim_matches('"')))
+            .collect::<Vec<_>>()
+            .join(".");
+        format!("SELECT * FROM {}", quoted)
+    }
+}
+
+impl DuckDBFileProcessor {
+    // Attach the source database and build the select for the data table
+    // PostGIS geometry and geography columns are sent as WKB and rebuilt as DuckDB geometry
+    pub(super) fn postgres_source_select(
+        &self,
+        source: &PostgresSource,
+    ) -> Result<String, Box<dyn Error>> {
+        connection::validate_connection(&source.connection)?;
+        self.conn.execute(
+            &format!(
+                "ATTACH '{}' AS source_db (TYPE POSTGRES, READ_ONLY)",
+                source.connection.replace('\'', "''")
+            ),
+            [],
+        )?;
+
+        let query = source.query();
+        let mut stmt = self.conn.prepare(&format!(
+            "DESCRIBE SELECT * FROM {};",
+            sql::postgres_query(
+                "source_db",
+                &format!("SELECT * FROM ({}) AS source LIMIT 0", query)
+            )
+        ))?;
+        let columns = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // DuckDB sees PostGIS types as text, so ask Postgres for the type of each column
+        let type_list = columns
+            .iter()
+            .map(|column| format!("pg_typeof({})::text", naming::quote_identifier(column)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let type_query = format!("SELECT {} FROM ({}) AS source LIMIT 1", type_list, query);
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT * FROM {};",
+            sql::postgres_query("source_db", &type_query)
+        ))?;
+        let mut rows = stmt.query([])?;
+        let Some(row) = rows.next()? else {
+            return Err(format!("Postgres source {} returned no rows", source.relation).into());
+        };
+        let mut geometry_columns = Vec::new();
+        for (index, column) in columns.iter().enumerate() {
+            let data_type: String = row.get(index)?;
+            if data_type == "geometry" || data_type == "geography" {
+                geometry_columns.push(column.clone());
+            }
+        }
+
+        let source_list = columns
+            .iter()
+            .map(|column| {
+                let quoted = naming::quote_identifier(column);
+                if geometry_columns.contains(column) {
+                    format!("ST_AsBinary({0}::geometry) AS {0}", quoted)
+                } else {
+                    quoted
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let source_query = format!("SELECT {} FROM ({}) AS source", source_list, query);
+        let replacements = geometry_columns
+            .iter()
+            .map(|column| {
+                format!(
+                    "ST_GeomFromWKB({0}) AS {0}",
+                    naming::quote_identifier(column)
+                )
+            })
+            .collect::<Vec<_>>();
+        let replace = if replacements.is_empty() {
+            String::new()
+        } else {
+            format!(" REPLACE ({})", replacements.join(", "))
+        };
+        Ok(format!(
+            "SELECT *{} FROM {}",
+            replace,
+            sql::postgres_query("source_db", &source_query)
+        ))
+    }
+
+    // SRID of a geometry column in the source, from its first non-null value
+    pub(super) fn postgres_source_srid(
+        &self,
+        source: &PostgresSource,
+        geom_column: &str,
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        let srid_query = format!(
+            "SELECT ST_SRID({0}::geometry)::text FROM ({1}) AS source
+            WHERE {0} IS NOT NULL LIMIT 1",
+            naming::quote_identifier(geom_column),
+            source.query()
+        );
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT * FROM {};",
+            sql::postgres_query("source_db", &srid_query)
+        ))?;
+        let mut rows = stmt.query([])?;
+        let srid = match rows.next()? {
+            Some(row) => row.get::<_, Option<String>>(0)?,
+            None => None,
+        };
+        // SRID 0 means the source didn't record one
+        Ok(srid.filter(|srid| srid != "0"))
+    }
+}
@@ -0,0 +1,133 @@
+use std::error::Error;
+
+use super::logging::{log_debug, log_info};
+use super::{sql, DuckDBFileProcessor};
+
+// Oldest Postgres the load's SQL runs on - table versions are found with starts_with, from 11
+const MIN_SERVER_VERSION: i32 = 110000;
+
+impl DuckDBFileProcessor {
+    // Fail before writing when the target is a standby or read-only, or its Postgres is too
+    // old, rather than part way through the load
+    pub(super) fn check_target_database(&self) -> Result<(), Box<dyn Error>> {
+        if self.query_postgres::<bool>("SELECT pg_is_in_recovery()")? == Some(true) {
+            return Err(
+                "The target database is a read-only hot standby - point the load at \
+                the primary server"
+                    .into(),
+            );
+        }
+        let read_only: Option<String> =
+            self.query_postgres("SELECT current_setting('default_transaction_read_only')")?;
+        if read_only.as_deref() == Some("on") {
+            return Err(
+                "The target database is read-only - default_transaction_read_only is \
+                on for this database or role"
+                    .into(),
+            );
+        }
+
+        let version_num: Option<i32> =
+            self.query_postgres("SELECT current_setting('server_version_num')::int")?;
+        if version_num.is_some_and(|version_num| version_num < MIN_SERVER_VERSION) {
+            let version: Option<String> =
+                self.query_postgres("SELECT current_setting('server_version')")?;
+            return Err(format!(
+                "The target database runs Postgres {}, but loading needs Postgres {} or later",
+                version.unwrap_or_default(),
+                MIN_SERVER_VERSION / 10000
+            )
+            .into());
+        }
+        log_debug!("Target database is writable, version {:?}", version_num);
+        Ok(())
+    }
+
+    // Check the target database has PostGIS before any geometry is written, creating the
+    // extension when create_postgis_extension is set
+    pub(super) fn check_postgis_extension(&self) -> Result<(), Box<dyn Error>> {
+        let version: Option<String> =
+            self.query_postgres("SELECT extversion FROM pg_extension WHERE extname = 'postgis'")?;
+        if let Some(version) = version {
+            log_debug!("Target database has PostGIS {}", version);
+            return Ok(());
+        }
+        if !self.options.create_postgis_extension {
+            return Err(
+                "The target database does not have the PostGIS extension - run \
+                CREATE EXTENSION postgis; in it, or set create_postgis_extension to create it"
+                    .into(),
+            );
+        }
+        self.postgres_execute(
+            "create PostGIS extension",
+            "CREATE EXTENSION IF NOT EXISTS postgis;",
+        )
+        .map_err(|e| {
+            format!(
+                "The target database does not have the PostGIS extension and it could not be \
+                created, which needs a superuser or the CREATE privilege on the database: {}",
+                e
+            )
+        })?;
+        log_info!("Created the PostGIS extension in the target database");
+        Ok(())
+    }
+
+    // Check the role can create tables in the target schema, or create the schema when it
+    // doesn't exist, and replace the target table, so a load fails before it writes anything
+    pub(super) fn check_schema_privileges(&self) -> Result<(), Box<dyn Error>> {
+        let Some(problem) = self.privilege_problem()? else {
+            return Ok(());
+        };
+        let role: Option<String> = self.query_postgres("SELECT current_user::text")?;
+        Err(format!("Role {} has {}", role.unwrap_or_default(), problem).into())
+    }
+
+    fn privilege_problem(&self) -> Result<Option<String>, Box<dyn Error>> {
+        let schema = sql::literal(&self.schema_name);
+        let can_create: Option<bool> = self.query_postgres(&format!(
+            "SELECT has_schema_privilege(oid, 'CREATE') FROM pg_namespace WHERE nspname = {}",
+            schema
+        ))?;
+        match can_create {
+            Some(true) => {}
+            Some(false) => {
+                return Ok(Some(format!(
+                    "insufficient privileges on schema {} - loading needs CREATE on it",
+                    self.schema_name
+                )))
+            }
+            // The load creates the schema, which needs CREATE on the database
+            None => {
+                let can_create_schema: Option<bool> = self.query_postgres(
+                    "SELECT has_database_privilege(current_database(), 'CREATE')",
+                )?;
+                if can_create_schema != Some(true) {
+                    return Ok(Some(format!(
+                        "insufficient privileges to create schema {} - it needs CREATE on the \
+                        database",
+                        self.schema_name
+                    )));
+                }
+                return Ok(None);
+            }
+        }
+
+        // Replacing a table drops it, which only members of its owning role can do
+        let owns_target: Option<bool> = self.query_postgres(&format!(
+            "SELECT pg_has_role(c.relowner, 'USAGE') FROM pg_class c
+            JOIN pg_namespace n ON n.oid = c.relnamespace
+            WHERE n.nspname = {} AND c.relname = {}",
+            schema,
+            sql::literal(&self.table_name)
+        ))?;
+        if owns_target == Some(false) {
+            return Ok(Some(format!(
+                "insufficient privileges on table {}.{} - only its owner can replace it",
+                self.schema_name, self.table_name
+            )));
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,153 @@
+use duckdb::arrow::datatypes::Schema;
+use duckdb::arrow::record_batch::RecordBatch;
+use duckdb::types::Value;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io;
+use std::sync::Arc;
+
+use super::{naming, DuckDBFileProcessor, ProcessorOptions};
+
+// Column of a previewed schema, in a form that can be serialized for UIs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SchemaColumn {
+    pub name: String,
+    // Arrow data type, e.g. "Utf8" or "Float64" - geometry columns are "Binary"
+    pub data_type: String,
+    pub nullable: bool,
+}
+
+impl SchemaColumn {
+    pub fn from_schema(schema: &Schema) -> Vec<SchemaColumn> {
+        schema
+            .fields()
+            .iter()
+            .map(|field| SchemaColumn {
+                name: field.name().clone(),
+                data_type: field.data_type().to_string(),
+                nullable: field.is_nullable(),
+            })
+            .collect()
+    }
+}
+
+// Arrow schema of the data as it would be loaded, with the column options applied, without
+// touching Postgres
+pub fn preview_schema(
+    file_path: &str,
+    options: ProcessorOptions,
+) -> Result<Arc<Schema>, io::Error> {
+    read_schema(file_path, options)
+        .map_err(|e| io::Error::other(format!("Error previewing schema of '{}': {}", file_path, e)))
+}
+
+// First rows of the data as it would be loaded, as Arrow record batches - geometry is WKB
+pub fn preview(
+    file_path: &str,
+    rows: usize,
+    options: ProcessorOptions,
+) -> Result<Vec<RecordBatch>, io::Error> {
+    read_preview(file_path, rows, options)
+        .map_err(|e| io::Error::other(format!("Error previewing rows of '{}': {}", file_path, e)))
+}
+
+// As preview, with each row as a JSON object and geometry as WKT
+pub fn preview_json(
+    file_path: &str,
+    rows: usize,
+    options: ProcessorOptions,
+) -> Result<Vec<serde_json::Value>, io::Error> {
+    read_preview_json(file_path, rows, options)
+        .map_err(|e| io::Error::other(format!("Error previewing rows of '{}': {}", file_path, e)))
+}
+
+fn read_schema(file_path: &str, options: ProcessorOptions) -> Result<Arc<Schema>, Box<dyn Error>> {
+    // Previews never reach Postgres, so no target is needed
+    let processor = DuckDBFileProcessor::new_file(file_path, "", "", "", options)?;
+    processor.prepare_data_table()?;
+    processor.data_schema()
+}
+
+fn read_preview(
+    file_path: &str,
+    rows: usize,
+    options: ProcessorOptions,
+) -> Result<Vec<RecordBatch>, Box<dyn Error>> {
+    let processor = DuckDBFileProcessor::new_file(file_path, "", "", "", options)?;
+    processor.prepare_data_table()?;
+    let mut stmt = processor
+        .conn
+        .prepare(&format!("SELECT * FROM data LIMIT {};", rows))?;
+    let batches = stmt.query_arrow([])?.collect();
+    Ok(batches)
+}
+
+fn read_preview_json(
+    file_path: &str,
+    rows: usize,
+    options: ProcessorOptions,
+) -> Result<Vec<serde_json::Value>, Box<dyn Error>> {
+    let processor = DuckDBFileProcessor::new_file(file_path, "", "", "", options)?;
+    processor.prepare_data_table()?;
+
+    // Numbers and booleans keep their JSON types, geometry is written as WKT and anything
+    // else as DuckDB's text form
+    let mut stmt = processor.conn.prepare(
+        "SELECT column_name, data_type FROM information_schema.columns
+        WHERE table_name = 'data' ORDER BY ordinal_position",
+    )?;
+    let columns = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    let select_list = columns
+        .iter()
+        .map(|(name, data_type)| {
+            let column = naming::quote_identifier(name);
+            match data_type.as_str() {
+                "GEOMETRY" => format!("ST_AsText({})", column),
+                "BOOLEAN" | "TINYINT" | "SMALLINT" | "INTEGER" | "BIGINT" | "UTINYINT"
+                | "USMALLINT" | "UINTEGER" | "UBIGINT" | "FLOAT" | "DOUBLE" => column,
+                data_type if data_type.starts_with("DECIMAL") => {
+                    format!("CAST({} AS DOUBLE)", column)
+                }
+                _ => format!("CAST({} AS VARCHAR)", column),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let mut stmt = processor
+        .conn
+        .prepare(&format!("SELECT {} FROM data LIMIT {};", select_list, rows))?;
+    let values = stmt
+        .query_map([], |row| {
+            let mut object = serde_json::Map::new();
+            for (index, (name, _)) in columns.iter().enumerate() {
+                object.insert(name.clone(), json_value(row.get(index)?));
+            }
+            Ok(serde_json::Value::Object(object))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(values)
+}
+
+fn json_value(value: Value) -> serde_json::Value {
+    match value {
+        Value::Boolean(value) => value.into(),
+        Value::TinyInt(value) => value.into(),
+        Value::SmallInt(value) => value.into(),
+        Value::Int(value) => value.into(),
+        Value::BigInt(value) => value.into(),
+        Value::UTinyInt(value) => value.into(),
+        Value::USmallInt(value) => value.into(),
+        Value::UInt(value) => value.into(),
+        Value::UBigInt(value) => value.into(),
+        // NaN and infinity have no JSON form and become null
+        Value::Float(value) => value.into(),
+        Value::Double(value) => value.into(),
+        Value::Text(value) => value.into(),
+        _ => serde_json::Value::Null,
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{OnceLock, PoisonError, RwLock};
+
+use super::{ColumnNaming, ProcessorOptions};
+
+// Named bundle of defaults selected with ProcessorOptions::profile
+// Anything set explicitly in ProcessorOptions takes precedence over the profile
+#[derive(Debug, Clone, Default)]
+pub struct OptionProfile {
+    // EPSG code to assume when a file declares no CRS
+    pub assumed_crs: Option<String>,
+    // Columns dropped when present - unlike exclude_columns, missing columns are ignored
+    pub skip_columns: Vec<String>,
+    // Text encoding of the source attributes
+    pub encoding: Option<String>,
+    // Column naming policy, used when the options keep the default
+    pub column_naming: Option<ColumnNaming>,
+    // Type overrides, used for columns without an explicit override
+    pub column_types: HashMap<String, String>,
+}
+
+fn registry() -> &'static RwLock<HashMap<String, OptionProfile>> {
+    static PROFILES: OnceLock<RwLock<HashMap<String, OptionProfile>>> = OnceLock::new();
+    PROFILES.get_or_init(|| RwLock::new(built_in_profiles()))
+}
+
+fn built_in_profiles() -> HashMap<String, OptionProfile> {
+    let mut profiles = HashMap::new();
+    // Ordnance Survey products are published in British National Grid
+    profiles.insert(
+        "os-open-data".to_string(),
+        OptionProfile {
+            assumed_crs: Some("27700".to_string()),
+            skip_columns: vec!["gml_id".to_string()],
+            column_naming: Some(ColumnNaming::Sanitize),
+            ..Default::default()
+        },
+    );
+    // ONS geoportal CSV exports carry ArcGIS bookkeeping columns
+    profiles.insert(
+        "ons-csv".to_string(),
+        OptionProfile {
+            skip_columns: vec!["ObjectId".to_string(), "GlobalID".to_string()],
+            column_naming: Some(ColumnNaming::Sanitize),
+            ..Default::default()
+        },
+    );
+    // Esri exports add derived geometry measures that go stale once reprojected
+    profiles.insert(
+        "esri-fgdb".to_string(),
+        OptionProfile {
+            skip_columns: vec![
+                "Shape_Length".to_string(),
+                "Shape_Leng".to_string(),
+                "Shape_Area".to_string(),
+            ],
+            column_naming: Some(ColumnNaming::Sanitize),
+            ..Default::default()
+        },
+    );
+    profiles
+}
+
+// Register a profile, replacing any existing profile (including built-ins) with the same name
+pub fn register_profile(name: &str, profile: OptionProfile) {
+    registry()
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(name.to_string(), profile);
+}
+
+pub fn get_profile(name: &str) -> Option<OptionProfile> {
+    registry()
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(name)
+        .cloned()
+}
+
+impl ProcessorOptions {
+    // Merge the selected profile into these options, returning the profile's skip columns
+    pub(crate) fn apply_profile(&mut self) -> Result<Vec<String>, Box<dyn Error>> {
+        let name = match &self.profile {
+            Some(name) => name,
+            None => return Ok(Vec::new()),
+        };
+        let profile =
+            get_profile(name).ok_or_else(|| format!("Unknown option profile '{}'", name))?;
+
+        if self.assumed_crs.is_none() {
+            self.assumed_crs = profile.assumed_crs;
+        }
+        if self.encoding.is_none() {
+            self.encoding = profile.encoding;
+        }
+        if self.column_naming == ColumnNaming::default() {
+            if let Some(column_naming) = profile.column_naming {
+                self.column_naming = column_naming;
+            }
+        }
+        for (column, data_type) in profile.column_types {
+            self.column_types.entry(column).or_insert(data_type);
+        }
+        Ok(profile.skip_columns)
+    }
+}
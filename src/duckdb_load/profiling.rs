@@ -0,0 +1,117 @@
+use std::env;
+use std::fs;
+use std::process;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::logging::{log_debug, log_warn};
+use super::{sql, DuckDBFileProcessor};
+
+// Operators listed per query in the load report, slowest first
+const TOP_OPERATORS: usize = 10;
+
+// DuckDB profile of one query the load ran, when profile_queries is set
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QueryProfile {
+    // Step of the load the query belongs to, such as "read source" or "export rows"
+    pub step: String,
+    // Wall time of the query in seconds, as measured by DuckDB
+    pub seconds: f64,
+    // Rows the query wrote
+    pub rows: u64,
+    // The slowest operators of the query plan
+    pub operators: Vec<OperatorProfile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OperatorProfile {
+    // Operator name, such as TABLE_SCAN or HASH_JOIN
+    pub name: String,
+    pub seconds: f64,
+    // Rows the operator produced
+    pub rows: u64,
+}
+
+impl DuckDBFileProcessor {
+    // Execute a transform or load query, profiling it into the report when profile_queries
+    // is set
+    pub(super) fn execute_profiled(&self, step: &str, sql: &str) -> duckdb::Result<usize> {
+        if !self.options.profile_queries {
+            return self.conn.execute(sql, []);
+        }
+
+        // DuckDB writes the profile of each query to the output file, replacing the last one
+        let profile_path = env::temp_dir().join(format!(
+            "duckdb_postgis_{}_{}_profile.json",
+            process::id(),
+            self.attach_alias
+        ));
+        self.conn.execute_batch(&format!(
+            "PRAGMA enable_profiling = 'json'; PRAGMA profiling_output = {};",
+            sql::string_literal(&profile_path.to_string_lossy())
+        ))?;
+        let result = self.conn.execute(sql, []);
+        self.conn.execute_batch("PRAGMA disable_profiling;")?;
+
+        match fs::read_to_string(&profile_path) {
+            Ok(text) => match serde_json::from_str::<Value>(&text) {
+                Ok(profile) => {
+                    let rows = result.as_ref().map_or(0, |rows| *rows as u64);
+                    let profile = query_profile(step, rows, &profile);
+                    log_debug!(
+                        "Profiled {}: {:.3}s, {} rows",
+                        step,
+                        profile.seconds,
+                        profile.rows
+                    );
+                    self.report.borrow_mut().query_profiles.push(profile);
+                }
+                Err(e) => log_warn!("Failed to parse DuckDB profile of {}: {}", step, e),
+            },
+            Err(e) => log_warn!("Failed to read DuckDB profile of {}: {}", step, e),
+        }
+        let _ = fs::remove_file(&profile_path);
+        result
+    }
+}
+
+// Summarise DuckDB's JSON profile, which names its fields differently between versions
+fn query_profile(step: &str, rows: u64, profile: &Value) -> QueryProfile {
+    let mut operators = Vec::new();
+    collect_operators(profile, &mut operators);
+    operators.sort_by(|a, b| b.seconds.total_cmp(&a.seconds));
+    operators.truncate(TOP_OPERATORS);
+
+    QueryProfile {
+        step: step.to_string(),
+        seconds: number_field(profile, &["latency", "timing", "operator_timing"]),
+        rows,
+        operators,
+    }
+}
+
+fn collect_operators(node: &Value, operators: &mut Vec<OperatorProfile>) {
+    let Some(children) = node.get("children").and_then(Value::as_array) else {
+        return;
+    };
+    for child in children {
+        let name = ["operator_type", "operator_name", "name"]
+            .iter()
+            .find_map(|field| child.get(*field).and_then(Value::as_str))
+            .unwrap_or("UNKNOWN");
+        operators.push(OperatorProfile {
+            name: name.trim().to_string(),
+            seconds: number_field(child, &["operator_timing", "timing"]),
+            rows: number_field(child, &["operator_cardinality", "cardinality"]) as u64,
+        });
+        collect_operators(child, operators);
+    }
+}
+
+fn number_field(node: &Value, fields: &[&str]) -> f64 {
+    fields
+        .iter()
+        .find_map(|field| node.get(*field).and_then(Value::as_f64))
+        .unwrap_or(0.0)
+}
@@ -0,0 +1,98 @@
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::Arc;
+
+use super::DuckDBFileProcessor;
+
+// Stage a load is in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoadStage {
+    // Detecting the file type and reading the source into DuckDB
+    Detection,
+    // Transforming geometry and running the transform hook
+    Transform,
+    // Writing to Postgres or the output file
+    Write,
+}
+
+impl LoadStage {
+    // Lowercase name used in progress and log events
+    pub fn name(self) -> &'static str {
+        match self {
+            LoadStage::Detection => "detection",
+            LoadStage::Transform => "transform",
+            LoadStage::Write => "write",
+        }
+    }
+}
+
+// Shared view of a running load's progress, set with ProcessorOptions::progress
+// Clones share the same state, so a handle kept by the caller can poll the load from another
+// thread
+#[derive(Debug, Clone, Default)]
+pub struct LoadProgress {
+    state: Arc<ProgressState>,
+}
+
+// Stage is 0 before the load starts, then 1 + the LoadStage index
+#[derive(Debug, Default)]
+struct ProgressState {
+    stage: AtomicU8,
+    rows_total: AtomicU64,
+    rows_written: AtomicU64,
+}
+
+impl LoadProgress {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // None until the load has started
+    pub fn stage(&self) -> Option<LoadStage> {
+        match self.state.stage.load(Ordering::Relaxed) {
+            1 => Some(LoadStage::Detection),
+            2 => Some(LoadStage::Transform),
+            3 => Some(LoadStage::Write),
+            _ => None,
+        }
+    }
+
+    // Rows to write, known once the write stage has counted them
+    pub fn rows_total(&self) -> u64 {
+        self.state.rows_total.load(Ordering::Relaxed)
+    }
+
+    // Rows written so far - only advances between batches of a throttled write
+    pub fn rows_written(&self) -> u64 {
+        self.state.rows_written.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn set_stage(&self, stage: LoadStage) {
+        let index = match stage {
+            LoadStage::Detection => 1,
+            LoadStage::Transform => 2,
+            LoadStage::Write => 3,
+        };
+        self.state.stage.store(index, Ordering::Relaxed);
+    }
+}
+
+impl DuckDBFileProcessor {
+    pub(super) fn report_stage(&self, stage: LoadStage) {
+        if let Some(progress) = &self.options.progress {
+            progress.set_stage(stage);
+        }
+    }
+
+    pub(super) fn report_rows(&self, rows_written: u64, rows_total: u64) {
+        if let Some(progress) = &self.options.progress {
+            progress
+                .state
+                .rows_total
+                .store(rows_total, Ordering::Relaxed);
+            progress
+                .state
+                .rows_written
+                .store(rows_written, Ordering::Relaxed);
+        }
+    }
+}
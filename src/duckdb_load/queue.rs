@@ -0,0 +1,369 @@
+use std::any::Any;
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+
+use super::{
+    launch_process_file_with_options, CancellationToken, LoadProgress, LoadReport, ProcessorOptions,
+};
+
+// Finished jobs kept for status and recent_jobs - the oldest are forgotten beyond this
+const MAX_FINISHED_JOBS: usize = 1000;
+
+// Where a queued load has got to
+#[derive(Debug, Clone)]
+pub enum JobState {
+    Queued,
+    Running,
+    Succeeded(Box<LoadReport>),
+    Failed(String),
+    // Cancelled while queued, or stopped by its token while running
+    Cancelled,
+}
+
+impl JobState {
+    pub fn is_finished(&self) -> bool {
+        !matches!(self, JobState::Queued | JobState::Running)
+    }
+}
+
+// Snapshot of a load submitted to a LoadQueue
+#[derive(Debug, Clone)]
+pub struct QueuedJob {
+    pub id: u64,
+    pub path: String,
+    pub table: String,
+    pub schema: String,
+    pub state: JobState,
+    // Stage and rows written of a running load
+    pub progress: LoadProgress,
+}
+
+struct Job {
+    info: QueuedJob,
+    postgis_uri: String,
+    // Taken by the worker that runs the load
+    options: Option<ProcessorOptions>,
+    cancellation: CancellationToken,
+}
+
+#[derive(Default)]
+struct Jobs {
+    next_id: u64,
+    pending: VecDeque<u64>,
+    jobs: BTreeMap<u64, Job>,
+    shutdown: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+    jobs: Mutex<Jobs>,
+    work: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Jobs> {
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+// In-process queue of loads run by a fixed number of worker threads, for callers such as a web
+// frontend that submit many uploads. Dropping the queue lets running loads finish and abandons
+// the ones still queued
+pub struct LoadQueue {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl LoadQueue {
+    // Queue running at most max_concurrent loads at a time
+    pub fn new(max_concurrent: usize) -> Self {
+        let shared = Arc::new(Shared::default());
+        let workers = (0..max_concurrent.max(1))
+            .map(|_| {
+                let shared = shared.clone();
+                thread::spawn(move || run_worker(&shared))
+            })
+            .collect();
+        Self { shared, workers }
+    }
+
+    // Add a load to the back of the queue, returning its job id
+    // The options' cancellation token and progress handle are used when set, so the caller can
+    // keep clones of them
+    pub fn enqueue(
+        &self,
+        file_path: &str,
+        table_name: &str,
+        postgis_uri: &str,
+        schema_name: &str,
+        mut options: ProcessorOptions,
+    ) -> u64 {
+        let cancellation = options
+            .cancellation
+            .get_or_insert_with(CancellationToken::new)
+            .clone();
+        let progress = options
+            .progress
+            .get_or_insert_with(LoadProgress::new)
+            .clone();
+
+        let mut jobs = self.shared.lock();
+        jobs.next_id += 1;
+        let id = jobs.next_id;
+        jobs.jobs.insert(
+            id,
+            Job {
+                info: QueuedJob {
+                    id,
+                    path: file_path.to_string(),
+                    table: table_name.to_string(),
+                    schema: schema_name.to_string(),
+                    state: JobState::Queued,
+                    progress,
+                },
+                postgis_uri: postgis_uri.to_string(),
+                options: Some(options),
+                cancellation,
+            },
+        );
+        jobs.pending.push_back(id);
+        drop(jobs);
+        self.shared.work.notify_one();
+        id
+    }
+
+    // None once a finished job has been forgotten, or for an id this queue never issued
+    pub fn status(&self, id: u64) -> Option<QueuedJob> {
+        self.shared.lock().jobs.get(&id).map(|job| job.info.clone())
+    }
+
+    // Up to limit of the most recently submitted jobs, newest first
+    pub fn recent_jobs(&self, limit: usize) -> Vec<QueuedJob> {
+        self.shared
+            .lock()
+            .jobs
+            .values()
+            .rev()
+            .take(limit)
+            .map(|job| job.info.clone())
+            .collect()
+    }
+
+    // Cancel a job - a queued job is dropped from the queue, a running one is cancelled through
+    // its token. Returns false when the job is unknown or already finished
+    pub fn cancel(&self, id: u64) -> bool {
+        let mut jobs = self.shared.lock();
+        let Some(job) = jobs.jobs.get_mut(&id) else {
+            return false;
+        };
+        match job.info.state {
+            JobState::Queued => {
+                job.info.state = JobState::Cancelled;
+                job.options = None;
+                jobs.pending.retain(|pending| *pending != id);
+                true
+            }
+            JobState::Running => {
+                job.cancellation.cancel();
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Drop for LoadQueue {
+    fn drop(&mut self) {
+        self.shared.lock().shutdown = true;
+        self.shared.work.notify_all();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn run_worker(shared: &Shared) {
+    loop {
+        let mut jobs = shared.lock();
+        let id = loop {
+            if jobs.shutdown {
+                return;
+            }
+            match jobs.pending.pop_front() {
+                Some(id) => break id,
+                None => jobs = shared.work.wait(jobs).unwrap_or_else(|e| e.into_inner()),
+            }
+        };
+        let Some(job) = jobs.jobs.get_mut(&id) else {
+            continue;
+        };
+        let Some(options) = job.options.take() else {
+            continue;
+        };
+        job.info.state = JobState::Running;
+        let path = job.info.path.clone();
+        let table = job.info.table.clone();
+        let schema = job.info.schema.clone();
+        let postgis_uri = job.postgis_uri.clone();
+        let cancellation = job.cancellation.clone();
+        drop(jobs);
+
+        // A load that panics fails its job rather than taking the worker down with it
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            launch_process_file_with_options(&path, &table, &postgis_uri, &schema, options)
+        }));
+        let state = finished_state(result, &cancellation);
+
+        let mut jobs = shared.lock();
+        if let Some(job) = jobs.jobs.get_mut(&id) {
+            job.info.state = state;
+        }
+        forget_finished(&mut jobs);
+    }
+}
+
+fn finished_state(
+    result: thread::Result<Result<LoadReport, io::Error>>,
+    cancellation: &CancellationToken,
+) -> JobState {
+    match result {
+        Ok(Ok(report)) => JobState::Succeeded(Box::new(report)),
+        Ok(Err(_)) if cancellation.is_cancelled() => JobState::Cancelled,
+        Ok(Err(e)) => JobState::Failed(e.to_string()),
+        Err(payload) => JobState::Failed(format!(
+            "Load panicked: {}",
+            panic_message(payload.as_ref())
+        )),
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown cause"
+    }
+}
+
+fn forget_finished(jobs: &mut Jobs) {
+    let finished = jobs
+        .jobs
+        .iter()
+        .filter(|(_, job)| job.info.state.is_finished())
+        .map(|(id, _)| *id)
+        .collect::<Vec<_>>();
+    for id in finished
+        .iter()
+        .take(finished.len().saturating_sub(MAX_FINISHED_JOBS))
+    {
+        jobs.jobs.remove(id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    // Queue without workers, so jobs stay queued until the test moves them on
+    fn idle_queue() -> LoadQueue {
+        LoadQueue {
+            shared: Arc::new(Shared::default()),
+            workers: Vec::new(),
+        }
+    }
+
+    fn wait_until_finished(queue: &LoadQueue, id: u64) -> JobState {
+        let started = Instant::now();
+        loop {
+            let state = queue.status(id).unwrap().state;
+            if state.is_finished() || started.elapsed() > Duration::from_secs(30) {
+                return state;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn cancels_queued_jobs() {
+        let queue = idle_queue();
+        let first = queue.enqueue("a.csv", "a", "", "public", ProcessorOptions::default());
+        let second = queue.enqueue("b.csv", "b", "", "public", ProcessorOptions::default());
+        assert!(matches!(
+            queue.status(first).unwrap().state,
+            JobState::Queued
+        ));
+
+        assert!(queue.cancel(first));
+        assert!(matches!(
+            queue.status(first).unwrap().state,
+            JobState::Cancelled
+        ));
+        assert!(!queue.cancel(first));
+        assert!(!queue.cancel(99));
+        assert_eq!(Vec::from(queue.shared.lock().pending.clone()), [second]);
+
+        let recent = queue.recent_jobs(10);
+        assert_eq!(
+            recent.iter().map(|job| job.id).collect::<Vec<_>>(),
+            [second, first]
+        );
+    }
+
+    #[test]
+    fn fails_jobs_whose_load_fails() {
+        let queue = LoadQueue::new(1);
+        let id = queue.enqueue(
+            "does-not-exist.geojson",
+            "missing",
+            "",
+            "public",
+            ProcessorOptions::default(),
+        );
+        assert!(matches!(
+            wait_until_finished(&queue, id),
+            JobState::Failed(_)
+        ));
+    }
+
+    #[test]
+    fn fails_jobs_whose_load_panics() {
+        let token = CancellationToken::new();
+        let result =
+            panic::catch_unwind(|| -> Result<LoadReport, io::Error> { panic!("bad geometry") });
+        match finished_state(result, &token) {
+            JobState::Failed(message) => assert_eq!(message, "Load panicked: bad geometry"),
+            state => panic!("Expected a failed job, got {:?}", state),
+        }
+
+        let result = Ok(Err(io::Error::other("stopped")));
+        token.cancel();
+        assert!(matches!(
+            finished_state(result, &token),
+            JobState::Cancelled
+        ));
+    }
+
+    #[test]
+    fn forgets_the_oldest_finished_jobs() {
+        let queue = idle_queue();
+        let ids = (0..MAX_FINISHED_JOBS + 2)
+            .map(|_| queue.enqueue("a.csv", "a", "", "public", ProcessorOptions::default()))
+            .collect::<Vec<_>>();
+        for id in &ids[..MAX_FINISHED_JOBS + 1] {
+            queue.cancel(*id);
+        }
+        forget_finished(&mut queue.shared.lock());
+        assert!(queue.status(ids[0]).is_none());
+        assert!(queue.status(ids[1]).is_some());
+        assert!(matches!(
+            queue.status(ids[MAX_FINISHED_JOBS + 1]).unwrap().state,
+            JobState::Queued
+        ));
+    }
+}
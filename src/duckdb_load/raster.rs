@@ -0,0 +1,449 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use super::logging::{log_debug, log_info};
+use super::{naming, sql, DuckDBFileProcessor, RasterLoad};
+
+// Rasters are uploaded in parts of this many bytes, so the file is never held in memory whole
+const UPLOAD_PART_SIZE: usize = 1 << 20;
+
+// TIFF and GeoTIFF tags read for the footprint
+const IMAGE_WIDTH: u16 = 256;
+const IMAGE_LENGTH: u16 = 257;
+const SAMPLES_PER_PIXEL: u16 = 277;
+const MODEL_PIXEL_SCALE: u16 = 33550;
+const MODEL_TIEPOINT: u16 = 33922;
+const MODEL_TRANSFORMATION: u16 = 34264;
+const GEO_KEY_DIRECTORY: u16 = 34735;
+
+// GeoKeys holding the EPSG code - 32767 marks a user defined CRS
+const GEOGRAPHIC_TYPE_KEY: u16 = 2048;
+const PROJECTED_CS_TYPE_KEY: u16 = 3072;
+const USER_DEFINED: u16 = 32767;
+
+// Size, bands, georeferencing and CRS read from a GeoTIFF's first image
+#[derive(Debug, Clone)]
+pub(super) struct GeoTiffInfo {
+    pub width: u32,
+    pub height: u32,
+    pub bands: u32,
+    pub pixel_width: f64,
+    pub pixel_height: f64,
+    pub extent: [f64; 4],
+    pub epsg: Option<u16>,
+}
+
+impl DuckDBFileProcessor {
+    // One row with the raster's metadata and its footprint polygon, for the normal geometry path
+    pub(super) fn raster_footprint_select(&self) -> Result<String, Box<dyn Error>> {
+        let info = read_geotiff_info(Path::new(&self.file_path))?;
+        let file_name = Path::new(&self.file_path)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let [min_x, min_y, max_x, max_y] = info.extent;
+        Ok(format!(
+            "SELECT '{}' AS file_name, {} AS width, {} AS height, {} AS band_count,
+                {} AS pixel_width, {} AS pixel_height,
+                ST_MakeEnvelope({}, {}, {}, {}) AS geom",
+            file_name.replace('\'', "''"),
+            info.width,
+            info.height,
+            info.bands,
+            info.pixel_width,
+            info.pixel_height,
+            min_x,
+            min_y,
+            max_x,
+            max_y
+        ))
+    }
+
+    pub(super) fn raster_epsg(&self) -> Result<Option<String>, Box<dyn Error>> {
+        let info = read_geotiff_info(Path::new(&self.file_path))?;
+        Ok(info.epsg.map(|epsg| epsg.to_string()))
+    }
+
+    // Load the pixels into a PostGIS raster column, tiled like raster2pgsql -t
+    // The file is uploaded in parts through the attached database, so it goes over the same
+    // connection - TLS included - as everything else, and PostGIS decodes and tiles it
+    pub(super) fn load_raster_tiles(&self) -> Result<(), Box<dyn Error>> {
+        let RasterLoad::Tiled {
+            tile_width,
+            tile_height,
+        } = self.options.raster
+        else {
+            return Err("Raster tiling is not enabled".into());
+        };
+        log_debug!("LOADING RASTER TILES");
+        let srid = self
+            .raster_epsg()?
+            .or_else(|| self.options.assumed_crs.clone())
+            .map(|srid| srid.parse::<i32>())
+            .transpose()?
+            .ok_or_else(|| format!("CRS not found for the following file: {}", self.file_path))?;
+        self.report.borrow_mut().source_crs = Some(srid.to_string());
+
+        self.ensure_postgis_attached()?;
+        self.check_target_database()?;
+        self.check_postgis_extension()?;
+        self.check_gtiff_driver()?;
+        self.check_target_relation()?;
+        self.check_schema_privileges()?;
+        let create_schema_sql = format!(
+            "CREATE SCHEMA IF NOT EXISTS {};",
+            naming::quote_identifier(&self.schema_name)
+        );
+        self.postgres_execute("create schema", &create_schema_sql)?;
+
+        self.load_via_staging(|staging_table| {
+            let upload_table = naming::qualified_name(
+                &self.schema_name,
+                &naming::derived_name(&self.table_name, "__raster_upload"),
+            );
+            self.postgres_execute(
+                "create raster upload table",
+                &format!(
+                    "DROP TABLE IF EXISTS {0};
+                    CREATE TABLE {0} (part integer PRIMARY KEY, bytes bytea NOT NULL);",
+                    upload_table
+                ),
+            )?;
+            let tiled = self.upload_raster(&upload_table).and_then(|()| {
+                self.postgres_execute(
+                    "tile raster",
+                    &format!(
+                        "CREATE TABLE {staging} (rid serial PRIMARY KEY, rast raster);
+                        INSERT INTO {staging} (rast)
+                        SELECT ST_Tile(rast, {tile_width}, {tile_height})
+                        FROM (
+                            SELECT ST_FromGDALRaster(
+                                string_agg(bytes, ''::bytea ORDER BY part), {srid}) AS rast
+                            FROM {upload}
+                        ) AS raster;",
+                        staging = staging_table,
+                        upload = upload_table,
+                        tile_width = tile_width,
+                        tile_height = tile_height,
+                        srid = srid
+                    ),
+                )
+            });
+            let dropped = self.postgres_execute(
+                "drop raster upload table",
+                &format!("DROP TABLE IF EXISTS {};", upload_table),
+            );
+            tiled?;
+            dropped?;
+            let tiles: i64 = self
+                .query_postgres(&format!("SELECT count(*) FROM {}", staging_table))?
+                .unwrap_or_default();
+            self.report.borrow_mut().rows_loaded = tiles as u64;
+            Ok(())
+        })?;
+
+        // Constraints register the table in raster_columns with its SRID, scale and extent
+        self.postgres_execute(
+            "add raster constraints",
+            &format!(
+                "SELECT AddRasterConstraints({}::name, {}::name, 'rast'::name);",
+                sql::literal(&self.schema_name),
+                sql::literal(&self.table_name)
+            ),
+        )?;
+        let schema_qualified_table = naming::qualified_name(&self.schema_name, &self.table_name);
+        self.write_provenance(&schema_qualified_table)?;
+        self.run_maintenance()?;
+        self.apply_table_access()?;
+        log_info!(
+            "Raster table {} loaded with {}x{} tiles",
+            self.table_name,
+            tile_width,
+            tile_height
+        );
+        Ok(())
+    }
+
+    // ST_FromGDALRaster fails with an unhelpful message when PostGIS has the GTiff driver
+    // disabled, which is its default. Calling postgis_gdal_version() first loads the raster
+    // library, so the setting is read as PostGIS sees it
+    fn check_gtiff_driver(&self) -> Result<(), Box<dyn Error>> {
+        let drivers: Option<String> = self.query_postgres(
+            "SELECT current_setting('postgis.gdal_enabled_drivers', true)
+            FROM (SELECT postgis_gdal_version() OFFSET 0) AS loaded",
+        )?;
+        let drivers = drivers.unwrap_or_default();
+        let enabled = drivers.split_whitespace().any(|driver| {
+            driver.eq_ignore_ascii_case("GTiff") || driver.eq_ignore_ascii_case("ENABLE_ALL")
+        });
+        if !enabled {
+            return Err(format!(
+                "PostGIS has the GTiff GDAL driver disabled (postgis.gdal_enabled_drivers is \
+                '{}'), so it can't read the raster - add GTiff to that setting on the server",
+                drivers
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    // Copy the raster file into the upload table, one part per row
+    fn upload_raster(&self, upload_table: &str) -> Result<(), Box<dyn Error>> {
+        let mut file = File::open(&self.file_path)?;
+        let mut buffer = vec![0u8; UPLOAD_PART_SIZE];
+        let mut part = 0;
+        loop {
+            let mut filled = 0;
+            while filled < buffer.len() {
+                match file.read(&mut buffer[filled..])? {
+                    0 => break,
+                    read => filled += read,
+                }
+            }
+            if filled == 0 {
+                break;
+            }
+            self.postgres_execute(
+                "upload raster",
+                &format!(
+                    "INSERT INTO {} (part, bytes) VALUES ({}, decode('{}', 'hex'));",
+                    upload_table,
+                    part,
+                    hex(&buffer[..filled])
+                ),
+            )?;
+            part += 1;
+            self.checkpoint()?;
+        }
+        log_debug!("Raster uploaded in {} parts", part);
+        Ok(())
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let mut encoded = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        encoded.push(DIGITS[(byte >> 4) as usize] as char);
+        encoded.push(DIGITS[(byte & 0x0f) as usize] as char);
+    }
+    encoded
+}
+
+// Minimal classic TIFF reader - only the first IFD and the tags above are read
+fn read_geotiff_info(path: &Path) -> Result<GeoTiffInfo, Box<dyn Error>> {
+    let mut reader = TiffReader::open(path)?;
+    let first_ifd = reader.read_u32_at(4)?;
+    let tags = reader.read_ifd(first_ifd)?;
+    let find = |tag: u16| tags.iter().find(|entry| entry.tag == tag);
+
+    let width = match find(IMAGE_WIDTH) {
+        Some(entry) => reader.read_integers(entry)?[0],
+        None => return Err("TIFF has no ImageWidth".into()),
+    };
+    let height = match find(IMAGE_LENGTH) {
+        Some(entry) => reader.read_integers(entry)?[0],
+        None => return Err("TIFF has no ImageLength".into()),
+    };
+    let bands = match find(SAMPLES_PER_PIXEL) {
+        Some(entry) => reader.read_integers(entry)?[0],
+        None => 1,
+    };
+
+    // Pixel corners to model coordinates, from either a scale and tiepoint or a full transform
+    let (transform, pixel_width, pixel_height) = match (
+        find(MODEL_PIXEL_SCALE),
+        find(MODEL_TIEPOINT),
+        find(MODEL_TRANSFORMATION),
+    ) {
+        (Some(scale), Some(tiepoint), _) => {
+            let scale = reader.read_doubles(scale)?;
+            let tiepoint = reader.read_doubles(tiepoint)?;
+            if scale.len() < 2 || tiepoint.len() < 6 {
+                return Err("Malformed GeoTIFF georeferencing tags".into());
+            }
+            let origin_x = tiepoint[3] - tiepoint[0] * scale[0];
+            let origin_y = tiepoint[4] + tiepoint[1] * scale[1];
+            (
+                [scale[0], 0.0, origin_x, 0.0, -scale[1], origin_y],
+                scale[0],
+                scale[1],
+            )
+        }
+        (_, _, Some(matrix)) => {
+            let m = reader.read_doubles(matrix)?;
+            if m.len() < 16 {
+                return Err("Malformed GeoTIFF transformation tag".into());
+            }
+            (
+                [m[0], m[1], m[3], m[4], m[5], m[7]],
+                m[0].hypot(m[4]),
+                m[1].hypot(m[5]),
+            )
+        }
+        _ => return Err("TIFF has no georeferencing - not a GeoTIFF".into()),
+    };
+    let corners = [
+        (0.0, 0.0),
+        (width as f64, 0.0),
+        (0.0, height as f64),
+        (width as f64, height as f64),
+    ]
+    .map(|(i, j)| {
+        (
+            transform[0] * i + transform[1] * j + transform[2],
+            transform[3] * i + transform[4] * j + transform[5],
+        )
+    });
+    let extent = [
+        corners.iter().map(|c| c.0).fold(f64::INFINITY, f64::min),
+        corners.iter().map(|c| c.1).fold(f64::INFINITY, f64::min),
+        corners
+            .iter()
+            .map(|c| c.0)
+            .fold(f64::NEG_INFINITY, f64::max),
+        corners
+            .iter()
+            .map(|c| c.1)
+            .fold(f64::NEG_INFINITY, f64::max),
+    ];
+
+    let epsg = match find(GEO_KEY_DIRECTORY) {
+        Some(entry) => {
+            let keys = reader.read_integers(entry)?;
+            // Header is version, revision, minor revision and key count, then 4 shorts per key
+            let key_value = |key_id: u16| {
+                keys.get(4..)
+                    .unwrap_or_default()
+                    .chunks_exact(4)
+                    .find(|key| key[0] == key_id as u32 && key[1] == 0)
+                    .map(|key| key[3] as u16)
+                    .filter(|code| *code != USER_DEFINED)
+            };
+            key_value(PROJECTED_CS_TYPE_KEY).or_else(|| key_value(GEOGRAPHIC_TYPE_KEY))
+        }
+        None => None,
+    };
+
+    Ok(GeoTiffInfo {
+        width,
+        height,
+        bands,
+        pixel_width,
+        pixel_height,
+        extent,
+        epsg,
+    })
+}
+
+struct IfdEntry {
+    tag: u16,
+    field_type: u16,
+    count: u32,
+    // Offset of the value, which is inside the entry itself when it fits in 4 bytes
+    value_offset: u64,
+}
+
+struct TiffReader {
+    file: File,
+    little_endian: bool,
+}
+
+impl TiffReader {
+    fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut file = File::open(path)?;
+        let mut header = [0u8; 4];
+        file.read_exact(&mut header)?;
+        let little_endian = match header {
+            [b'I', b'I', 42, 0] => true,
+            [b'M', b'M', 0, 42] => false,
+            [b'I', b'I', 43, 0] | [b'M', b'M', 0, 43] => {
+                return Err("BigTIFF files are not supported".into())
+            }
+            _ => return Err("Not a TIFF file".into()),
+        };
+        Ok(Self {
+            file,
+            little_endian,
+        })
+    }
+
+    fn read_bytes<const N: usize>(&mut self, offset: u64) -> Result<[u8; N], Box<dyn Error>> {
+        let mut bytes = [0u8; N];
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(&mut bytes)?;
+        if !self.little_endian {
+            bytes.reverse();
+        }
+        Ok(bytes)
+    }
+
+    fn read_u16_at(&mut self, offset: u64) -> Result<u16, Box<dyn Error>> {
+        Ok(u16::from_le_bytes(self.read_bytes(offset)?))
+    }
+
+    fn read_u32_at(&mut self, offset: u64) -> Result<u32, Box<dyn Error>> {
+        Ok(u32::from_le_bytes(self.read_bytes(offset)?))
+    }
+
+    fn read_ifd(&mut self, offset: u32) -> Result<Vec<IfdEntry>, Box<dyn Error>> {
+        let count = self.read_u16_at(offset as u64)?;
+        let mut entries = Vec::with_capacity(count as usize);
+        for index in 0..count as u64 {
+            let entry = offset as u64 + 2 + index * 12;
+            let field_type = self.read_u16_at(entry + 2)?;
+            let count = self.read_u32_at(entry + 4)?;
+            let size = match field_type {
+                3 => 2,
+                4 => 4,
+                12 => 8,
+                _ => 1,
+            } * count as u64;
+            let value_offset = if size <= 4 {
+                entry + 8
+            } else {
+                self.read_u32_at(entry + 8)? as u64
+            };
+            entries.push(IfdEntry {
+                tag: self.read_u16_at(entry)?,
+                field_type,
+                count,
+                value_offset,
+            });
+        }
+        Ok(entries)
+    }
+
+    // SHORT or LONG values
+    fn read_integers(&mut self, entry: &IfdEntry) -> Result<Vec<u32>, Box<dyn Error>> {
+        (0..entry.count as u64)
+            .map(|index| match entry.field_type {
+                3 => Ok(self.read_u16_at(entry.value_offset + index * 2)? as u32),
+                4 => self.read_u32_at(entry.value_offset + index * 4),
+                other => Err(
+                    format!("Unexpected TIFF field type {} for tag {}", other, entry.tag).into(),
+                ),
+            })
+            .collect()
+    }
+
+    // DOUBLE values
+    fn read_doubles(&mut self, entry: &IfdEntry) -> Result<Vec<f64>, Box<dyn Error>> {
+        if entry.field_type != 12 {
+            return Err(format!(
+                "Unexpected TIFF field type {} for tag {}",
+                entry.field_type, entry.tag
+            )
+            .into());
+        }
+        (0..entry.count as u64)
+            .map(|index| {
+                Ok(f64::from_le_bytes(
+                    self.read_bytes(entry.value_offset + index * 8)?,
+                ))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,81 @@
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use super::logging::log_warn;
+use super::{run_load, DuckDBFileProcessor, FileType, LoadReport, ProcessorOptions};
+
+// Temporary file a stream is spooled into, removed when dropped
+pub(super) struct SpooledFile {
+    pub(super) path: PathBuf,
+}
+
+impl Drop for SpooledFile {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            log_warn!("Failed to remove {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+// Load a file read from a stream, such as an upload held in memory. DuckDB and GDAL read from
+// paths, so the stream is spooled to a temporary file named with the detected type's extension
+pub fn launch_process_reader<R: Read>(
+    reader: R,
+    table_name: &str,
+    postgis_uri: &str,
+    schema_name: &str,
+    options: ProcessorOptions,
+) -> Result<LoadReport, io::Error> {
+    let spooled = spool(reader)?;
+    let path = spooled.path.to_string_lossy().into_owned();
+    run_load(&path, table_name, postgis_uri, schema_name, options, None)
+}
+
+// The file is named uniquely rather than after the table, so uploads to the same table can
+// run side by side
+pub(super) fn spool<R: Read>(mut reader: R) -> Result<SpooledFile, io::Error> {
+    let (mut file, base_path) = tempfile::Builder::new()
+        .prefix("duckdb_postgis_")
+        .suffix("_upload")
+        .tempfile()?
+        .keep()
+        .map_err(|e| e.error)?;
+    let copied = io::copy(&mut reader, &mut file);
+    drop(file);
+    let mut spooled = SpooledFile { path: base_path };
+    copied?;
+
+    // Some GDAL drivers, such as XLSX, only open files with their usual extension
+    let file_type = DuckDBFileProcessor::determine_file_type(&spooled.path.to_string_lossy())
+        .map_err(|e| io::Error::other(format!("Error detecting file type: {}", e)))?;
+    let extension = match file_type {
+        FileType::Geopackage => "gpkg",
+        FileType::Shapefile => "shp",
+        FileType::Geojson => "geojson",
+        // Zip archives share the XLSX signature and are recognised by content either way
+        FileType::Excel => excel_extension(&spooled.path)?,
+        FileType::Ods => "ods",
+        FileType::Csv => "csv",
+        FileType::Parquet => "parquet",
+        FileType::OsmPbf => "osm.pbf",
+        FileType::Dxf => "dxf",
+        FileType::GeoTiff => "tif",
+        FileType::Orc => "orc",
+        FileType::Postgres | FileType::Custom(_) => return Ok(spooled),
+    };
+    let path = spooled.path.with_extension(extension);
+    fs::rename(&spooled.path, &path)?;
+    spooled.path = path;
+    Ok(spooled)
+}
+
+fn excel_extension(path: &Path) -> Result<&'static str, io::Error> {
+    let mut signature = [0u8; 4];
+    File::open(path)?.read_exact(&mut signature)?;
+    Ok(if signature == [0x50, 0x4B, 0x03, 0x04] {
+        "xlsx"
+    } else {
+        "xls"
+    })
+}
@@ -0,0 +1,513 @@
+use duckdb::Connection;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use super::logging::{log_debug, log_info, log_warn};
+use super::{extensions, sql, ProcessorOptions};
+
+// Features held in memory from a remote source unless remote_max_features sets the limit, so
+// a service that never stops paging fails rather than exhausting memory
+const MAX_REMOTE_FEATURES: usize = 10_000_000;
+
+// Feature services fetched into a local GeoJSON file before the normal pipeline runs
+#[derive(Debug, Clone)]
+pub(super) enum RemoteSource {
+    // OGC API Features collection, given as OAPIF:<collection url> like GDAL
+    OgcApiFeatures(String),
+    // WFS 2.0 service, given as wfs://<host/path>?typeNames=<type> or WFS:<service url>
+    Wfs(String),
+    // ArcGIS REST FeatureServer layer, given as its URL - https://<host>/.../FeatureServer/<layer id>
+    FeatureServer(String),
+}
+
+impl RemoteSource {
+    pub(super) fn parse(file_path: &str) -> Option<Self> {
+        if let Some(url) = strip_prefix_ignore_case(file_path, "OAPIF:") {
+            return Some(Self::OgcApiFeatures(url.trim_end_matches('/').to_string()));
+        }
+        if let Some(url) = strip_prefix_ignore_case(file_path, "wfs://") {
+            return Some(Self::Wfs(format!("https://{}", url)));
+        }
+        if let Some(url) = strip_prefix_ignore_case(file_path, "WFS:") {
+            return Some(Self::Wfs(url.to_string()));
+        }
+        let is_http = strip_prefix_ignore_case(file_path, "https://").is_some()
+            || strip_prefix_ignore_case(file_path, "http://").is_some();
+        if is_http && file_path.to_lowercase().contains("/featureserver/") {
+            return Some(Self::FeatureServer(
+                file_path.trim_end_matches('/').to_string(),
+            ));
+        }
+        None
+    }
+
+    pub(super) fn url(&self) -> &str {
+        match self {
+            Self::OgcApiFeatures(url) | Self::Wfs(url) | Self::FeatureServer(url) => url,
+        }
+    }
+
+    // Page through the service and write every feature to a temporary GeoJSON file, named
+    // uniquely so loads fetching side by side never share it
+    pub(super) fn fetch(
+        &self,
+        conn: &Connection,
+        options: &ProcessorOptions,
+    ) -> Result<PathBuf, Box<dyn Error>> {
+        // DuckDB does the HTTP requests, so only httpfs is needed
+        extensions::load_extension(conn, "httpfs")?;
+
+        let (features, crs) = match self {
+            Self::OgcApiFeatures(url) => (fetch_ogc_api_features(conn, url, options)?, None),
+            Self::Wfs(url) => fetch_wfs(conn, url, options)?,
+            Self::FeatureServer(url) => fetch_feature_server(conn, url, options)?,
+        };
+        if features.is_empty() {
+            return Err(format!("No features returned from {}", self.url()).into());
+        }
+        log_info!("Fetched {} features from {}", features.len(), self.url());
+
+        let mut collection = json!({ "type": "FeatureCollection", "features": features });
+        // A named CRS member tells GDAL the features aren't WGS84
+        if let Some(crs) = crs {
+            collection["crs"] = crs;
+        }
+        let (mut file, path) = tempfile::Builder::new()
+            .prefix("duckdb_postgis_")
+            .suffix(".geojson")
+            .tempfile()?
+            .keep()
+            .map_err(|e| e.error)?;
+        if let Err(e) = file.write_all(&serde_json::to_vec(&collection)?) {
+            let _ = fs::remove_file(&path);
+            return Err(e.into());
+        }
+        Ok(path)
+    }
+}
+
+fn fetch_ogc_api_features(
+    conn: &Connection,
+    collection_url: &str,
+    options: &ProcessorOptions,
+) -> Result<Vec<Value>, Box<dyn Error>> {
+    let items_url = if collection_url.ends_with("/items") {
+        collection_url.to_string()
+    } else {
+        format!("{}/items", collection_url)
+    };
+    let mut url = format!(
+        "{}{}f=json&limit={}",
+        items_url,
+        if items_url.contains('?') { '&' } else { '?' },
+        options.remote_page_size
+    );
+    if let Some([min_x, min_y, max_x, max_y]) = options.remote_bbox {
+        url.push_str(&format!("&bbox={},{},{},{}", min_x, min_y, max_x, max_y));
+    }
+    page_ogc_api_features(url, options, |url| fetch_json(conn, url))
+}
+
+// Follow the next links from the first page until the collection is exhausted. A link back to
+// a page already fetched ends the paging, so a server repeating its links can't loop forever
+fn page_ogc_api_features(
+    mut url: String,
+    options: &ProcessorOptions,
+    mut fetch: impl FnMut(&str) -> Result<Value, Box<dyn Error>>,
+) -> Result<Vec<Value>, Box<dyn Error>> {
+    let mut features = Vec::new();
+    let mut visited = HashSet::new();
+    loop {
+        let page = fetch(&url)?;
+        let page_features = page["features"]
+            .as_array()
+            .ok_or_else(|| format!("Response from {} is not a FeatureCollection", url))?;
+        features.extend(page_features.iter().cloned());
+        if reached_limit(&mut features, options, &url)? || page_features.is_empty() {
+            break;
+        }
+
+        let next = page["links"].as_array().and_then(|links| {
+            links
+                .iter()
+                .find(|link| link["rel"] == "next")
+                .and_then(|link| link["href"].as_str())
+        });
+        visited.insert(url);
+        match next {
+            Some(next) if visited.contains(next) => {
+                log_warn!(
+                    "OGC API Features server linked back to {}, so paging stopped after {} \
+                    features",
+                    next,
+                    features.len()
+                );
+                break;
+            }
+            Some(next) => url = next.to_string(),
+            None => break,
+        }
+    }
+    Ok(features)
+}
+
+// GetFeature pages are requested as GeoJSON, but GML responses are converted with GDAL
+fn fetch_wfs(
+    conn: &Connection,
+    service_url: &str,
+    options: &ProcessorOptions,
+) -> Result<(Vec<Value>, Option<Value>), Box<dyn Error>> {
+    if !service_url.to_lowercase().contains("typename") {
+        return Err(format!("WFS source {} has no typeNames parameter", service_url).into());
+    }
+    let mut base_url = format!(
+        "{}{}service=WFS&version=2.0.0&request=GetFeature&outputFormat=application/json&count={}",
+        service_url,
+        if service_url.contains('?') { '&' } else { '?' },
+        options.remote_page_size
+    );
+    if let Some([min_x, min_y, max_x, max_y]) = options.remote_bbox {
+        // WFS 2.0 uses lat/lon order for EPSG:4326, so give the bbox in CRS84
+        base_url.push_str(&format!(
+            "&bbox={},{},{},{},urn:ogc:def:crs:OGC:1.3:CRS84",
+            min_x, min_y, max_x, max_y
+        ));
+    }
+
+    let mut features = Vec::new();
+    let mut crs = None;
+    let mut previous_first: Option<Value> = None;
+    loop {
+        let url = format!("{}&startIndex={}", base_url, features.len());
+        let text = fetch_text(conn, &url)?;
+        let (page, matched) = if text.trim_start().starts_with('<') {
+            (gml_to_geojson(conn, &text)?, gml_number_matched(&text))
+        } else {
+            let page: Value = serde_json::from_str(&text)?;
+            let matched = page["numberMatched"].as_u64();
+            (page, matched)
+        };
+        let page_features = page["features"]
+            .as_array()
+            .ok_or_else(|| format!("Response from {} is not a FeatureCollection", url))?;
+        if crs.is_none() {
+            crs = page.get("crs").cloned();
+        }
+        // Servers that don't page ignore startIndex and answer with the first page again
+        if !page_features.is_empty() && page_features.first() == previous_first.as_ref() {
+            log_warn!(
+                "WFS server at {} repeated a page, so it doesn't support paging - {} features \
+                were fetched",
+                service_url,
+                features.len()
+            );
+            break;
+        }
+        previous_first = page_features.first().cloned();
+        features.extend(page_features.iter().cloned());
+        // Servers may cap count below the page size asked for, such as GeoServer's
+        // maxFeatures, so a short page isn't the end - only numberMatched or an empty page is
+        if reached_limit(&mut features, options, &url)?
+            || page_features.is_empty()
+            || matched.is_some_and(|matched| features.len() as u64 >= matched)
+        {
+            break;
+        }
+    }
+    Ok((features, crs))
+}
+
+// numberMatched of a WFS 2.0 GML FeatureCollection, which GDAL drops when converting it.
+// Servers may give "unknown"
+fn gml_number_matched(gml: &str) -> Option<u64> {
+    let start = gml.find("numberMatched=\"")? + "numberMatched=\"".len();
+    let length = gml[start..].find('"')?;
+    gml[start..start + length].parse().ok()
+}
+
+fn gml_to_geojson(conn: &Connection, gml: &str) -> Result<Value, Box<dyn Error>> {
+    if gml.contains("ExceptionReport") {
+        return Err(format!("WFS server returned an exception: {}", gml).into());
+    }
+    // A directory of its own per page, removed with the .gfs schema file GDAL leaves beside
+    // the GML, so WFS loads running side by side never read each other's pages
+    let directory = tempfile::Builder::new()
+        .prefix("duckdb_postgis_")
+        .suffix("_wfs")
+        .tempdir()?;
+    let gml_path = directory.path().join("page.gml");
+    let json_path = directory.path().join("page.geojson");
+    fs::write(&gml_path, gml)?;
+    convert_gml_page(conn, &gml_path, &json_path)
+}
+
+fn convert_gml_page(
+    conn: &Connection,
+    gml_path: &Path,
+    json_path: &Path,
+) -> Result<Value, Box<dyn Error>> {
+    let gml_path = sql::string_literal(&gml_path.display().to_string());
+    conn.execute(
+        &format!(
+            "COPY (SELECT * FROM st_read({})) TO {} WITH (FORMAT GDAL, DRIVER 'GeoJSON');",
+            gml_path,
+            sql::string_literal(&json_path.display().to_string())
+        ),
+        [],
+    )?;
+    let auth_code: Option<String> = conn.query_row(
+        &format!(
+            "SELECT layers[1].geometry_fields[1].crs.auth_code FROM st_read_meta({});",
+            gml_path
+        ),
+        [],
+        |row| row.get(0),
+    )?;
+
+    let mut page: Value = serde_json::from_str(&fs::read_to_string(json_path)?)?;
+    if let Some(code) = auth_code {
+        page["crs"] = named_crs(&code);
+    }
+    Ok(page)
+}
+
+// Features are requested in the layer's own spatial reference and paged with resultOffset
+fn fetch_feature_server(
+    conn: &Connection,
+    layer_url: &str,
+    options: &ProcessorOptions,
+) -> Result<(Vec<Value>, Option<Value>), Box<dyn Error>> {
+    let layer = fetch_arcgis_json(conn, &format!("{}?f=json", layer_url))?;
+    let spatial_reference = layer["extent"]["spatialReference"]
+        .as_object()
+        .or_else(|| layer["sourceSpatialReference"].as_object());
+    // latestWkid holds the EPSG code when wkid is an Esri code such as 102100
+    let wkid = spatial_reference
+        .and_then(|sr| sr.get("latestWkid").or_else(|| sr.get("wkid")))
+        .and_then(Value::as_i64);
+
+    let mut base_url = format!(
+        "{}/query?where=1%3D1&outFields=*&returnGeometry=true&f=geojson&resultRecordCount={}",
+        layer_url, options.remote_page_size
+    );
+    if let Some(wkid) = wkid {
+        base_url.push_str(&format!("&outSR={}", wkid));
+    }
+    if let Some([min_x, min_y, max_x, max_y]) = options.remote_bbox {
+        base_url.push_str(&format!(
+            "&geometry={},{},{},{}&geometryType=esriGeometryEnvelope&inSR=4326&spatialRel=esriSpatialRelIntersects",
+            min_x, min_y, max_x, max_y
+        ));
+    }
+
+    let mut features = Vec::new();
+    loop {
+        let url = format!("{}&resultOffset={}", base_url, features.len());
+        let page = fetch_arcgis_json(conn, &url)?;
+        let page_features = page["features"]
+            .as_array()
+            .ok_or_else(|| format!("Response from {} is not a FeatureCollection", url))?;
+        features.extend(page_features.iter().cloned());
+        // Servers cap pages at their maxRecordCount and flag when more remain
+        let exceeded = page["exceededTransferLimit"].as_bool().unwrap_or(false)
+            || page["properties"]["exceededTransferLimit"]
+                .as_bool()
+                .unwrap_or(false);
+        if reached_limit(&mut features, options, &url)?
+            || page_features.is_empty()
+            || (!exceeded && page_features.len() < options.remote_page_size)
+        {
+            break;
+        }
+    }
+
+    Ok((features, wkid.map(|wkid| named_crs(&wkid.to_string()))))
+}
+
+// ArcGIS reports failures as an error object in a successful response
+fn fetch_arcgis_json(conn: &Connection, url: &str) -> Result<Value, Box<dyn Error>> {
+    let response = fetch_json(conn, url)?;
+    if let Some(error) = response.get("error") {
+        return Err(format!("ArcGIS server error from {}: {}", url, error).into());
+    }
+    Ok(response)
+}
+
+// GeoJSON 2008 named CRS member, which GDAL still reads
+fn named_crs(epsg_code: &str) -> Value {
+    json!({
+        "type": "name",
+        "properties": { "name": format!("urn:ogc:def:crs:EPSG::{}", epsg_code) }
+    })
+}
+
+fn fetch_json(conn: &Connection, url: &str) -> Result<Value, Box<dyn Error>> {
+    Ok(serde_json::from_str(&fetch_text(conn, url)?)?)
+}
+
+fn fetch_text(conn: &Connection, url: &str) -> Result<String, Box<dyn Error>> {
+    log_debug!("Fetching {}", url);
+    let content: String = conn.query_row(
+        &format!(
+            "SELECT content FROM read_text({});",
+            sql::string_literal(url)
+        ),
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(content)
+}
+
+// Trim to remote_max_features, returning true once it has been reached
+// Whether enough features have been fetched, trimming them to remote_max_features. Without
+// that limit, a source passing MAX_REMOTE_FEATURES is an error
+fn reached_limit(
+    features: &mut Vec<Value>,
+    options: &ProcessorOptions,
+    url: &str,
+) -> Result<bool, Box<dyn Error>> {
+    match options.remote_max_features {
+        Some(max) if features.len() >= max => {
+            features.truncate(max);
+            Ok(true)
+        }
+        None if features.len() > MAX_REMOTE_FEATURES => Err(format!(
+            "{} returned more than {} features - set remote_max_features to load part of it",
+            url, MAX_REMOTE_FEATURES
+        )
+        .into()),
+        _ => Ok(false),
+    }
+}
+
+fn strip_prefix_ignore_case<'a>(value: &'a str, prefix: &str) -> Option<&'a str> {
+    value
+        .get(..prefix.len())
+        .filter(|start| start.eq_ignore_ascii_case(prefix))
+        .map(|_| &value[prefix.len()..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn page(ids: &[i64], next: Option<&str>) -> Value {
+        let features = ids
+            .iter()
+            .map(|id| json!({ "type": "Feature", "id": id, "geometry": null }))
+            .collect::<Vec<_>>();
+        let links = next
+            .map(|next| vec![json!({ "rel": "next", "href": next })])
+            .unwrap_or_default();
+        json!({ "type": "FeatureCollection", "features": features, "links": links })
+    }
+
+    // Pages served by URL, recording the URLs fetched
+    fn serve<'a>(
+        pages: HashMap<&'static str, Value>,
+        fetched: &'a mut Vec<String>,
+    ) -> impl FnMut(&str) -> Result<Value, Box<dyn Error>> + 'a {
+        move |url| {
+            fetched.push(url.to_string());
+            pages
+                .get(url)
+                .cloned()
+                .ok_or_else(|| format!("No page at {}", url).into())
+        }
+    }
+
+    fn ids(features: &[Value]) -> Vec<i64> {
+        features.iter().filter_map(|f| f["id"].as_i64()).collect()
+    }
+
+    #[test]
+    fn follows_next_links_until_the_last_page() {
+        let pages = HashMap::from([
+            ("p1", page(&[1, 2], Some("p2"))),
+            ("p2", page(&[3, 4], Some("p3"))),
+            ("p3", page(&[5], None)),
+        ]);
+        let mut fetched = Vec::new();
+        let options = ProcessorOptions::default();
+        let features =
+            page_ogc_api_features("p1".into(), &options, serve(pages, &mut fetched)).unwrap();
+        assert_eq!(ids(&features), [1, 2, 3, 4, 5]);
+        assert_eq!(fetched, ["p1", "p2", "p3"]);
+    }
+
+    #[test]
+    fn stops_when_a_next_link_repeats_a_page() {
+        let pages = HashMap::from([
+            ("p1", page(&[1, 2], Some("p2"))),
+            ("p2", page(&[3, 4], Some("p1"))),
+        ]);
+        let mut fetched = Vec::new();
+        let options = ProcessorOptions::default();
+        let features =
+            page_ogc_api_features("p1".into(), &options, serve(pages, &mut fetched)).unwrap();
+        assert_eq!(ids(&features), [1, 2, 3, 4]);
+        assert_eq!(fetched, ["p1", "p2"]);
+
+        // A page linking to itself is fetched once
+        let pages = HashMap::from([("p1", page(&[1], Some("p1")))]);
+        let mut fetched = Vec::new();
+        page_ogc_api_features("p1".into(), &options, serve(pages, &mut fetched)).unwrap();
+        assert_eq!(fetched, ["p1"]);
+    }
+
+    #[test]
+    fn stops_at_an_empty_page_or_the_feature_limit() {
+        let pages = HashMap::from([
+            ("p1", page(&[1, 2], Some("p2"))),
+            ("p2", page(&[], Some("p3"))),
+        ]);
+        let mut fetched = Vec::new();
+        let options = ProcessorOptions::default();
+        let features =
+            page_ogc_api_features("p1".into(), &options, serve(pages, &mut fetched)).unwrap();
+        assert_eq!(ids(&features), [1, 2]);
+        assert_eq!(fetched, ["p1", "p2"]);
+
+        let pages = HashMap::from([
+            ("p1", page(&[1, 2], Some("p2"))),
+            ("p2", page(&[3, 4], Some("p3"))),
+        ]);
+        let mut fetched = Vec::new();
+        let options = ProcessorOptions {
+            remote_max_features: Some(3),
+            ..ProcessorOptions::default()
+        };
+        let features =
+            page_ogc_api_features("p1".into(), &options, serve(pages, &mut fetched)).unwrap();
+        assert_eq!(ids(&features), [1, 2, 3]);
+        assert_eq!(fetched, ["p1", "p2"]);
+    }
+
+    #[test]
+    fn rejects_responses_that_arent_feature_collections() {
+        let pages = HashMap::from([("p1", json!({ "type": "Feature" }))]);
+        let mut fetched = Vec::new();
+        let options = ProcessorOptions::default();
+        let error =
+            page_ogc_api_features("p1".into(), &options, serve(pages, &mut fetched)).unwrap_err();
+        assert!(error.to_string().contains("not a FeatureCollection"));
+    }
+
+    #[test]
+    fn parses_remote_sources() {
+        assert!(matches!(
+            RemoteSource::parse("oapif:https://example.com/collections/roads/"),
+            Some(RemoteSource::OgcApiFeatures(url)) if url == "https://example.com/collections/roads"
+        ));
+        assert!(matches!(
+            RemoteSource::parse("wfs://example.com/wfs?typeNames=roads"),
+            Some(RemoteSource::Wfs(url)) if url == "https://example.com/wfs?typeNames=roads"
+        ));
+        assert!(RemoteSource::parse("roads.geojson").is_none());
+    }
+}
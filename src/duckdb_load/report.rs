@@ -0,0 +1,107 @@
+use serde::Serialize;
+
+use super::{Extent, QueryProfile};
+
+// Column differences between a re-load and the table it replaced
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SchemaChanges {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    // Columns whose type changed, as (column, old type, new type)
+    pub retyped: Vec<(String, String, String)>,
+}
+
+// Rows changed by an incremental load
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct IncrementalChanges {
+    pub inserted: u64,
+    pub updated: u64,
+    pub deleted: u64,
+}
+
+// Summary of a completed load, returned by launch_process_file_with_options
+#[derive(Debug, Clone, Serialize)]
+pub struct LoadReport {
+    // Version of this crate that performed the load
+    pub crate_version: String,
+    // EPSG code of the source geometry, when the file has one
+    pub source_crs: Option<String>,
+    // EPSG code each geometry column was loaded in, as (column, code) pairs
+    pub geometry_crs: Vec<(String, String)>,
+    // Vertical CRS of a compound source CRS, whose heights are kept as they are
+    pub vertical_crs: Option<String>,
+    // Geometry columns whose single geometries were promoted to multi, as (column, type) pairs
+    pub multi_promotions: Vec<(String, String)>,
+    // Tables loaded by MixedGeometry::SeparateTables, one per geometry type, when it split the layer
+    pub geometry_tables: Vec<String>,
+    // Rows with a NULL geometry, and with an EMPTY one, before the empty geometry policy
+    pub null_geometries: u64,
+    pub empty_geometries: u64,
+    // Number of rows written to the target table
+    pub rows_loaded: u64,
+    // WGS84 extent of the first geometry column, for zoom-to-layer
+    pub extent: Option<Extent>,
+    // Malformed CSV rows skipped under csv_max_rejected_rows
+    pub rejected_rows: u64,
+    // The first few rejected rows, with their line number and error
+    pub rejected_row_samples: Vec<String>,
+    // Whether every CSV column was read as text because a type detected from the sample
+    // failed further into the file
+    pub csv_all_varchar: bool,
+    // CSV or spreadsheet text columns parsed as WKT geometry
+    pub wkt_columns: Vec<String>,
+    // Column changes against the existing table, when a re-load changed its schema
+    pub schema_changes: Option<SchemaChanges>,
+    // Rows inserted, updated and deleted, when an incremental load changed an existing table
+    pub incremental_changes: Option<IncrementalChanges>,
+    // Version table the load created, when keep_versions is set
+    pub version_table: Option<String>,
+    // Source column names that were renamed, as (original, renamed) pairs
+    pub column_renames: Vec<(String, String)>,
+    // Duplicate source column names that were suffixed, as (original, renamed) pairs
+    pub duplicate_column_renames: Vec<(String, String)>,
+    // Source columns dropped by the column selection options
+    pub dropped_columns: Vec<String>,
+    // Partition the rows were appended to, as the start date taken from the file name
+    pub partition_date: Option<String>,
+    // SHA-256 of the source file, computed alongside the content checksum
+    pub source_sha256: Option<String>,
+    // MD5 of the sorted row hashes of the transformed data, when requested
+    pub content_checksum: Option<String>,
+    // Seconds spent in each load stage, as (stage, seconds) pairs in the order they ran
+    pub stage_seconds: Vec<(String, f64)>,
+    // DuckDB profiles of the read, transform and write queries, when profile_queries is set
+    pub query_profiles: Vec<QueryProfile>,
+}
+
+impl Default for LoadReport {
+    fn default() -> Self {
+        Self {
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            source_crs: None,
+            geometry_crs: Vec::new(),
+            vertical_crs: None,
+            multi_promotions: Vec::new(),
+            geometry_tables: Vec::new(),
+            null_geometries: 0,
+            empty_geometries: 0,
+            rows_loaded: 0,
+            extent: None,
+            rejected_rows: 0,
+            rejected_row_samples: Vec::new(),
+            csv_all_varchar: false,
+            wkt_columns: Vec::new(),
+            schema_changes: None,
+            incremental_changes: None,
+            version_table: None,
+            column_renames: Vec::new(),
+            duplicate_column_renames: Vec::new(),
+            dropped_columns: Vec::new(),
+            partition_date: None,
+            source_sha256: None,
+            content_checksum: None,
+            stage_seconds: Vec::new(),
+            query_profiles: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::error::Error;
+
+use super::logging::log_info;
+use super::{naming, sql, DuckDBFileProcessor, SchemaChanges, SchemaDrift};
+
+impl DuckDBFileProcessor {
+    // Compare the staging table with the table it replaces and apply the drift policy
+    pub(super) fn check_schema_drift(&self, staging_table: &str) -> Result<(), Box<dyn Error>> {
+        let existing = self.postgres_columns(&self.table_name)?;
+        if existing.is_empty() {
+            return Ok(());
+        }
+        let incoming = self.postgres_columns(&self.staging_name())?;
+
+        let changes = schema_changes(&existing, &incoming);
+        if changes.added.is_empty() && changes.removed.is_empty() && changes.retyped.is_empty() {
+            return Ok(());
+        }
+
+        log_info!(
+            "Schema drift against existing table {}: added {:?}, removed {:?}, retyped {:?}",
+            self.table_name,
+            changes.added,
+            changes.removed,
+            changes.retyped
+        );
+        self.report.borrow_mut().schema_changes = Some(changes.clone());
+        match self.options.schema_drift {
+            SchemaDrift::Replace => Ok(()),
+            SchemaDrift::Fail => Err(format!(
+                "Schema of {} has drifted from the existing table - added {:?}, removed {:?}, retyped {:?}",
+                self.table_name, changes.added, changes.removed, changes.retyped
+            )
+            .into()),
+            // Keep removed columns, empty, so queries against the old schema still run
+            SchemaDrift::Migrate => {
+                for column in &changes.removed {
+                    let data_type = column_type(&existing, column).unwrap_or_default();
+                    self.postgres_execute(
+                        "keep removed column",
+                        &format!(
+                            "ALTER TABLE {} ADD COLUMN {} {};",
+                            staging_table,
+                            naming::quote_identifier(column),
+                            data_type
+                        ),
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    // Column names and formatted types of a table in the target schema, empty when it's missing
+    pub(super) fn postgres_columns(
+        &self,
+        table: &str,
+    ) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+        let columns_sql = format!(
+            "SELECT a.attname::text, format_type(a.atttypid, a.atttypmod)
+            FROM pg_attribute a
+            JOIN pg_class c ON c.oid = a.attrelid
+            JOIN pg_namespace n ON n.oid = c.relnamespace
+            WHERE n.nspname = {} AND c.relname = {} AND a.attnum > 0 AND NOT a.attisdropped
+            ORDER BY a.attnum",
+            sql::literal(&self.schema_name),
+            sql::literal(table)
+        );
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT * FROM {};",
+            sql::postgres_query(&self.attach_alias, &columns_sql)
+        ))?;
+        let columns = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(columns)
+    }
+}
+
+// Columns added, removed and retyped going from the existing to the incoming columns, each given
+// as (name, type)
+fn schema_changes(existing: &[(String, String)], incoming: &[(String, String)]) -> SchemaChanges {
+    SchemaChanges {
+        added: incoming
+            .iter()
+            .filter(|(column, _)| column_type(existing, column).is_none())
+            .map(|(column, _)| column.clone())
+            .collect(),
+        removed: existing
+            .iter()
+            .filter(|(column, _)| column_type(incoming, column).is_none())
+            .map(|(column, _)| column.clone())
+            .collect(),
+        retyped: incoming
+            .iter()
+            .filter_map(|(column, new_type)| match column_type(existing, column) {
+                Some(old_type) if &old_type != new_type => {
+                    Some((column.clone(), old_type, new_type.clone()))
+                }
+                _ => None,
+            })
+            .collect(),
+    }
+}
+
+fn column_type(columns: &[(String, String)], name: &str) -> Option<String> {
+    columns
+        .iter()
+        .find(|(column, _)| column == name)
+        .map(|(_, data_type)| data_type.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns(columns: &[(&str, &str)]) -> Vec<(String, String)> {
+        columns
+            .iter()
+            .map(|(name, data_type)| (name.to_string(), data_type.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn finds_added_removed_and_retyped_columns() {
+        let existing = columns(&[("id", "integer"), ("name", "text"), ("area", "integer")]);
+        let incoming = columns(&[
+            ("id", "integer"),
+            ("area", "double precision"),
+            ("Zone", "text"),
+        ]);
+        assert_eq!(
+            schema_changes(&existing, &incoming),
+            SchemaChanges {
+                added: vec!["Zone".to_string()],
+                removed: vec!["name".to_string()],
+                retyped: vec![(
+                    "area".to_string(),
+                    "integer".to_string(),
+                    "double precision".to_string()
+                )],
+            }
+        );
+    }
+
+    #[test]
+    fn matching_schemas_have_no_changes() {
+        let existing = columns(&[("id", "integer"), ("geom", "geometry(Point,4326)")]);
+        let incoming = columns(&[("geom", "geometry(Point,4326)"), ("id", "integer")]);
+        let changes = schema_changes(&existing, &incoming);
+        assert!(changes.added.is_empty() && changes.removed.is_empty());
+        assert!(changes.retyped.is_empty());
+
+        // Names are compared exactly, as Postgres stores them
+        let changes = schema_changes(
+            &columns(&[("id", "integer")]),
+            &columns(&[("ID", "integer")]),
+        );
+        assert_eq!(changes.added, ["ID"]);
+        assert_eq!(changes.removed, ["id"]);
+    }
+}
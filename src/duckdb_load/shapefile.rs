@@ -0,0 +1,140 @@
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use super::logging::log_warn;
+use super::DuckDBFileProcessor;
+
+impl DuckDBFileProcessor {
+    // Attribute encoding for a shapefile - the encoding option, else the .cpg, else the DBF LDID
+    pub(super) fn shapefile_encoding(&self) -> Option<String> {
+        if let Some(encoding) = &self.options.encoding {
+            return Some(encoding.clone());
+        }
+        let path = Path::new(&self.file_path);
+        sidecar(path, "cpg")
+            .and_then(|cpg| fs::read_to_string(cpg).ok())
+            .and_then(|cpg| cpg_encoding(&cpg))
+            .or_else(|| ldid_encoding(&sidecar(path, "dbf")?))
+    }
+
+    // WKT of the .prj, which GDAL can't always match to an authority code itself
+    pub(super) fn prj_wkt(&self) -> Option<String> {
+        let prj = sidecar(Path::new(&self.file_path), "prj")?;
+        fs::read_to_string(prj).ok()
+    }
+}
+
+// A directory is loaded from the one .shp inside it, any other path is used as given
+pub(super) fn resolve_shapefile_path(path: &str) -> Result<String, Box<dyn Error>> {
+    if !Path::new(path).is_dir() {
+        return Ok(path.to_string());
+    }
+    let mut shapefiles = fs::read_dir(path)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|entry| has_extension(entry, "shp"))
+        .collect::<Vec<_>>();
+    match shapefiles.len() {
+        0 => Err(format!("No .shp file found in directory {}", path).into()),
+        1 => Ok(shapefiles.remove(0).to_string_lossy().into_owned()),
+        _ => {
+            shapefiles.sort();
+            let names = shapefiles
+                .iter()
+                .filter_map(|shapefile| shapefile.file_name())
+                .map(|name| name.to_string_lossy().into_owned())
+                .collect::<Vec<_>>();
+            Err(format!(
+                "Directory {} holds several shapefiles, pass one of {:?}",
+                path, names
+            )
+            .into())
+        }
+    }
+}
+
+// The .shx index is needed to read the geometry, while a missing .dbf only loses the attributes
+pub(super) fn check_sidecars(shp_path: &str) -> Result<(), Box<dyn Error>> {
+    let path = Path::new(shp_path);
+    if sidecar(path, "shx").is_none() {
+        return Err(format!("Shapefile {} is missing its .shx index file", shp_path).into());
+    }
+    if sidecar(path, "dbf").is_none() {
+        log_warn!(
+            "Shapefile {} has no .dbf file - loading geometry only",
+            shp_path
+        );
+    }
+    Ok(())
+}
+
+// Sibling file with the same stem, matching the extension case insensitively (ROADS.DBF)
+fn sidecar(shp_path: &Path, extension: &str) -> Option<PathBuf> {
+    let stem = shp_path.file_stem()?;
+    let directory = match shp_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    fs::read_dir(directory)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .find(|entry| entry.file_stem() == Some(stem) && has_extension(entry, extension))
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .is_some_and(|found| found.eq_ignore_ascii_case(extension))
+}
+
+// Map a .cpg codepage, e.g. "1252", "ANSI 1252" or "88591", to a GDAL ENCODING name
+fn cpg_encoding(cpg: &str) -> Option<String> {
+    let cpg = cpg.trim().to_uppercase();
+    let cpg = cpg.strip_prefix("ANSI").unwrap_or(&cpg).trim();
+    // The name is interpolated into SQL so only allow encoding name characters
+    if cpg.is_empty()
+        || !cpg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return None;
+    }
+    let encoding = match cpg {
+        "UTF8" | "UTF-8" | "65001" => "UTF-8".to_string(),
+        // ESRI writes ISO-8859 parts as 8859<part>, e.g. 88591
+        digits if digits.starts_with("8859") && digits.chars().all(|c| c.is_ascii_digit()) => {
+            format!("ISO-8859-{}", &digits[4..])
+        }
+        digits if digits.chars().all(|c| c.is_ascii_digit()) => format!("CP{}", digits),
+        name => name.to_string(),
+    };
+    Some(encoding)
+}
+
+// Codepage from the language driver ID at byte 29 of the DBF header
+fn ldid_encoding(dbf_path: &Path) -> Option<String> {
+    let mut header = [0u8; 30];
+    File::open(dbf_path).ok()?.read_exact(&mut header).ok()?;
+    let codepage = match header[29] {
+        0x01 => "CP437",
+        0x02 => "CP850",
+        0x03 | 0x57 | 0x58 | 0x59 => "CP1252",
+        0x13 | 0x7B => "CP932",
+        0x26 | 0x65 => "CP866",
+        0x4D | 0x7A => "CP936",
+        0x4E | 0x79 => "CP949",
+        0x4F | 0x78 => "CP950",
+        0x64 => "CP852",
+        0x66 => "CP865",
+        0x7C => "CP874",
+        0x7D => "CP1255",
+        0x7E => "CP1256",
+        0xC8 => "CP1250",
+        0xC9 => "CP1251",
+        0xCA => "CP1254",
+        0xCB => "CP1253",
+        // 0 means no codepage was recorded
+        _ => return None,
+    };
+    Some(codepage.to_string())
+}
@@ -0,0 +1,170 @@
+use std::error::Error;
+use std::io;
+
+use duckdb::Connection;
+
+use super::logging::log_warn;
+use super::{
+    credentials, extensions, naming, run_processor, DuckDBFileProcessor, LoadReport, MixedGeometry,
+    ProcessorOptions,
+};
+
+// Extensions every load needs
+const REQUIRED_EXTENSIONS: [&str; 2] = ["spatial", "postgres"];
+
+// Load a file through the caller's DuckDB database rather than a fresh in-memory one, so it
+// reuses the extensions and any global settings the application has configured. The load runs
+// on a clone of conn and drops the tables, views and attachments it created afterwards,
+// leaving what was already in the database alone. spatial and postgres are loaded when the
+// database doesn't have them yet
+pub fn launch_process_file_with_connection(
+    conn: &Connection,
+    file_path: &str,
+    table_name: &str,
+    postgis_uri: &str,
+    schema_name: &str,
+    options: ProcessorOptions,
+) -> Result<LoadReport, io::Error> {
+    // Lockdown would freeze the caller's database, and separate tables need a database each
+    if options.locked_down || options.mixed_geometry == MixedGeometry::SeparateTables {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Shared connections don't support locked-down mode or separate geometry tables",
+        ));
+    }
+
+    // Temporary tables belong to a connection, so the clone's go when the load finishes
+    let shared = conn.try_clone().map_err(io::Error::other)?;
+    let existing = drop_statements(conn)
+        .and_then(|existing| {
+            load_missing_extensions(&shared)?;
+            Ok(existing)
+        })
+        .map_err(|e| {
+            io::Error::other(format!(
+                "Error preparing the shared DuckDB connection: {}",
+                e
+            ))
+        })?;
+
+    let result = DuckDBFileProcessor::new_with_connection(
+        Some(shared),
+        file_path,
+        table_name,
+        postgis_uri,
+        schema_name,
+        options,
+    )
+    .map_err(|e| {
+        io::Error::other(format!(
+            "Error creating FileProcessor for '{}': {}",
+            file_path,
+            credentials::redact(&e.to_string(), postgis_uri)
+        ))
+    })
+    .and_then(|processor| run_processor(processor, file_path, None));
+
+    // Clean up whether or not the load succeeded, so the next load starts clean
+    let cleanup = drop_statements(conn).and_then(|statements| {
+        let created = statements
+            .into_iter()
+            .filter(|statement| !existing.contains(statement))
+            .collect::<Vec<_>>();
+        conn.execute_batch(&created.join("\n"))?;
+        Ok(())
+    });
+    if let Err(e) = cleanup {
+        log_warn!("Failed to clean up the shared DuckDB connection: {}", e);
+    }
+    result
+}
+
+fn load_missing_extensions(conn: &Connection) -> Result<(), Box<dyn Error>> {
+    for name in REQUIRED_EXTENSIONS {
+        let loaded: bool = conn.query_row(
+            "SELECT count(*) > 0 FROM duckdb_extensions() WHERE extension_name = ? AND loaded;",
+            [name],
+            |row| row.get(0),
+        )?;
+        if !loaded {
+            extensions::load_extension(conn, name)?;
+        }
+    }
+    Ok(())
+}
+
+// Statements dropping every table and view in the database, and detaching every database
+// attached to it
+pub(super) fn drop_statements(conn: &Connection) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut statements = Vec::new();
+    let relations = [
+        ("TABLE", "duckdb_tables()", "table_name"),
+        ("VIEW", "duckdb_views()", "view_name"),
+    ];
+    for (kind, catalog, name_column) in relations {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT database_name, schema_name, {} FROM {}
+            WHERE NOT internal AND database_name IN (current_database(), 'temp')",
+            name_column, catalog
+        ))?;
+        let names = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        for (database, schema, name) in names {
+            statements.push(format!(
+                "DROP {} {}.{}.{};",
+                kind,
+                naming::quote_identifier(&database),
+                naming::quote_identifier(&schema),
+                naming::quote_identifier(&name)
+            ));
+        }
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT database_name FROM duckdb_databases()
+        WHERE NOT internal AND database_name NOT IN (current_database(), 'temp')",
+    )?;
+    let databases = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    for database in databases {
+        statements.push(detach_statement(&database));
+    }
+    Ok(statements)
+}
+
+pub(super) fn detach_statement(database: &str) -> String {
+    format!("DETACH DATABASE {};", naming::quote_identifier(database))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_relations_with_quotes_in_their_names() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE \"we\"\"ird\" (id INTEGER);
+            CREATE VIEW \"a\"\"view\" AS SELECT 1;
+            CREATE TEMP TABLE \"temp\"\"rows\" (id INTEGER);",
+        )
+        .unwrap();
+        let statements = drop_statements(&conn).unwrap();
+        assert_eq!(statements.len(), 3);
+        conn.execute_batch(&statements.join("\n")).unwrap();
+        assert!(drop_statements(&conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn quotes_detached_databases() {
+        assert_eq!(detach_statement("pg\"1"), "DETACH DATABASE \"pg\"\"1\";");
+    }
+}
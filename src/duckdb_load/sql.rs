@@ -0,0 +1,92 @@
+// SQL sent to Postgres through the DuckDB postgres extension. Statements reach Postgres as a
+// DuckDB string literal passed to postgres_execute or postgres_query, so they are dollar quoted
+// rather than escaped, and the values written into them are quoted as Postgres literals
+
+// Postgres string literal for a value. A backslash makes it an escape string, so the value
+// reads back the same whatever standard_conforming_strings is set to
+pub(crate) fn literal(value: &str) -> String {
+    let quoted = value.replace('\'', "''");
+    if value.contains('\\') {
+        format!("E'{}'", quoted.replace('\\', "\\\\"))
+    } else {
+        format!("'{}'", quoted)
+    }
+}
+
+// Dollar-quoted DuckDB string literal holding text exactly as given - quotes, backslashes,
+// semicolons and newlines included - with a tag chosen so the text can't close it early
+pub(crate) fn dollar_quote(text: &str) -> String {
+    let mut tag = "$sql$".to_string();
+    let mut suffix = 0;
+    // The closing tag is the first match after the opening one, which the end of the text
+    // could start
+    while format!("{}{}", text, tag).find(&tag) != Some(text.len()) {
+        suffix += 1;
+        tag = format!("$sql{}$", suffix);
+    }
+    format!("{}{}{}", tag, text, tag)
+}
+
+// DuckDB string literal for a value such as a file path. DuckDB takes backslashes as they are,
+// so only quotes are doubled
+pub(crate) fn string_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+// CALL running statements directly in the Postgres database attached as alias
+pub(crate) fn postgres_execute(alias: &str, sql: &str) -> String {
+    format!(
+        "CALL postgres_execute('{}', {});",
+        alias.replace('\'', "''"),
+        dollar_quote(sql)
+    )
+}
+
+// Table function reading the rows of a query run directly in the Postgres database attached
+// as alias
+pub(crate) fn postgres_query(alias: &str, sql: &str) -> String {
+    format!(
+        "postgres_query('{}', {})",
+        alias.replace('\'', "''"),
+        dollar_quote(sql)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_literals() {
+        assert_eq!(literal("roads"), "'roads'");
+        assert_eq!(literal("O'Brien"), "'O''Brien'");
+        assert_eq!(literal(r"C:\data\o'k"), r"E'C:\\data\\o''k'");
+    }
+
+    #[test]
+    fn quotes_duckdb_string_literals() {
+        assert_eq!(string_literal("roads.gpkg"), "'roads.gpkg'");
+        assert_eq!(string_literal(r"C:\data\o'k.shp"), r"'C:\data\o''k.shp'");
+    }
+
+    #[test]
+    fn dollar_quotes_text_that_holds_the_tag() {
+        assert_eq!(dollar_quote("SELECT 1;"), "$sql$SELECT 1;$sql$");
+        assert_eq!(dollar_quote("a $sql$ b"), "$sql1$a $sql$ b$sql1$");
+        // Text ending in part of the tag would close it early
+        assert_eq!(dollar_quote("x$sql"), "$sql1$x$sql$sql1$");
+        assert_eq!(dollar_quote("$sql$ $sql1$"), "$sql2$$sql$ $sql1$$sql2$");
+    }
+
+    #[test]
+    fn wraps_statements_for_the_attached_database() {
+        assert_eq!(
+            postgres_execute("pg'1", "SELECT 'a';"),
+            "CALL postgres_execute('pg''1', $sql$SELECT 'a';$sql$);"
+        );
+        assert_eq!(
+            postgres_query("pg", "SELECT 1"),
+            "postgres_query('pg', $sql$SELECT 1$sql$)"
+        );
+    }
+}
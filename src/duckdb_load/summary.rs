@@ -0,0 +1,102 @@
+use std::error::Error;
+
+use super::logging::{log_debug, log_info};
+use super::{naming, sql, DuckDBFileProcessor, Extent};
+
+impl DuckDBFileProcessor {
+    // Record the WGS84 extent of the loaded layer, so clients can zoom to it without a scan
+    pub(super) fn record_layer_extent(
+        &self,
+        geom_columns: &[String],
+    ) -> Result<(), Box<dyn Error>> {
+        let geom_column = match geom_columns.first() {
+            Some(column) => column,
+            None => return Ok(()),
+        };
+        let extent_sql = format!(
+            "SELECT ST_XMin(e)::float8, ST_YMin(e)::float8, ST_XMax(e)::float8, ST_YMax(e)::float8
+            FROM (SELECT ST_Extent(ST_Transform({}, 4326)) AS e FROM {}) AS layer",
+            naming::quote_identifier(geom_column),
+            naming::qualified_name(&self.schema_name, &self.table_name)
+        );
+        let bounds: (Option<f64>, Option<f64>, Option<f64>, Option<f64>) = self.conn.query_row(
+            &format!(
+                "SELECT * FROM {};",
+                sql::postgres_query(&self.attach_alias, &extent_sql)
+            ),
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )?;
+        // An empty layer has no extent
+        if let (Some(min_x), Some(min_y), Some(max_x), Some(max_y)) = bounds {
+            log_debug!("Layer extent: {}, {} -> {}, {}", min_x, min_y, max_x, max_y);
+            self.report.borrow_mut().extent = Some(Extent {
+                min_x,
+                min_y,
+                max_x,
+                max_y,
+            });
+        }
+        Ok(())
+    }
+
+    // Write <table>_summary with a feature count and bbox per category value
+    pub(super) fn write_category_summary(
+        &self,
+        geom_columns: &[String],
+    ) -> Result<(), Box<dyn Error>> {
+        let category_column = match &self.options.summary_category_column {
+            Some(column) => column,
+            None => return Ok(()),
+        };
+        let geom_column = match geom_columns.first() {
+            Some(column) => column,
+            None => return Ok(()),
+        };
+        // Column names are matched like unquoted SQL identifiers, and the loaded name is used
+        let category_column = match self
+            .get_column_names("transformed_data")?
+            .into_iter()
+            .find(|column| column.eq_ignore_ascii_case(category_column))
+        {
+            Some(column) => column,
+            None => {
+                return Err(format!(
+                    "Summary category column '{}' not found in loaded data",
+                    category_column
+                )
+                .into())
+            }
+        };
+
+        let summary_table = naming::qualified_name(
+            &self.schema_name,
+            &naming::derived_name(&self.table_name, "_summary"),
+        );
+        let summary_sql = format!(
+            "DROP TABLE IF EXISTS {summary};
+            CREATE TABLE {summary} AS
+            SELECT category, feature_count,
+                jsonb_build_array(ST_XMin(extent), ST_YMin(extent), ST_XMax(extent), ST_YMax(extent)) AS bbox,
+                ST_SetSRID(extent::geometry, {srid}) AS bbox_geom
+            FROM (
+                SELECT {category}::text AS category, count(*) AS feature_count, ST_Extent({geom}) AS extent
+                FROM {target}
+                GROUP BY {category}
+            ) AS grouped
+            ORDER BY category;",
+            summary = summary_table,
+            category = naming::quote_identifier(&category_column),
+            geom = naming::quote_identifier(geom_column),
+            srid = self.geometry_srid(geom_column),
+            target = naming::qualified_name(&self.schema_name, &self.table_name),
+        );
+        self.postgres_execute("write category summary", &summary_sql)?;
+        log_info!(
+            "Summary table {} written for category column {}",
+            summary_table,
+            category_column
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+// Limits on the Postgres write rate
+// Clones share the same limits, so a handle kept by the caller can adjust a running load
+#[derive(Debug, Clone, Default)]
+pub struct WriteThrottle {
+    limits: Arc<ThrottleLimits>,
+}
+
+// Zero means unlimited
+#[derive(Debug, Default)]
+struct ThrottleLimits {
+    max_rows_per_second: AtomicU64,
+    max_bytes_per_second: AtomicU64,
+}
+
+impl WriteThrottle {
+    pub fn new(max_rows_per_second: Option<u64>, max_bytes_per_second: Option<u64>) -> Self {
+        let throttle = Self::default();
+        throttle.set_max_rows_per_second(max_rows_per_second);
+        throttle.set_max_bytes_per_second(max_bytes_per_second);
+        throttle
+    }
+
+    pub fn max_rows_per_second(&self) -> Option<u64> {
+        Self::load(&self.limits.max_rows_per_second)
+    }
+
+    pub fn max_bytes_per_second(&self) -> Option<u64> {
+        Self::load(&self.limits.max_bytes_per_second)
+    }
+
+    pub fn set_max_rows_per_second(&self, limit: Option<u64>) {
+        self.limits
+            .max_rows_per_second
+            .store(limit.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    pub fn set_max_bytes_per_second(&self, limit: Option<u64>) {
+        self.limits
+            .max_bytes_per_second
+            .store(limit.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    // Effective row rate given the average row size, using the tighter of the two limits
+    pub(crate) fn rows_per_second(&self, average_row_bytes: f64) -> Option<u64> {
+        let from_bytes = self
+            .max_bytes_per_second()
+            .map(|bytes| ((bytes as f64 / average_row_bytes.max(1.0)) as u64).max(1));
+        match (self.max_rows_per_second(), from_bytes) {
+            (Some(rows), Some(bytes)) => Some(rows.min(bytes)),
+            (rows, bytes) => rows.or(bytes),
+        }
+    }
+
+    fn load(limit: &AtomicU64) -> Option<u64> {
+        match limit.load(Ordering::Relaxed) {
+            0 => None,
+            value => Some(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uses_the_tighter_limit() {
+        let throttle = WriteThrottle::new(Some(1000), Some(50_000));
+        assert_eq!(throttle.rows_per_second(100.0), Some(500));
+        assert_eq!(throttle.rows_per_second(10.0), Some(1000));
+
+        let throttle = WriteThrottle::new(None, Some(100));
+        assert_eq!(throttle.rows_per_second(1000.0), Some(1));
+        assert_eq!(throttle.rows_per_second(0.0), Some(100));
+        assert_eq!(WriteThrottle::default().rows_per_second(100.0), None);
+    }
+
+    #[test]
+    fn clones_share_their_limits() {
+        let throttle = WriteThrottle::new(Some(10), None);
+        let handle = throttle.clone();
+        handle.set_max_rows_per_second(Some(20));
+        handle.set_max_bytes_per_second(Some(4096));
+        assert_eq!(throttle.max_rows_per_second(), Some(20));
+        assert_eq!(throttle.max_bytes_per_second(), Some(4096));
+
+        handle.set_max_rows_per_second(None);
+        assert_eq!(throttle.max_rows_per_second(), None);
+        assert_eq!(
+            WriteThrottle::new(Some(0), None).max_rows_per_second(),
+            None
+        );
+    }
+}
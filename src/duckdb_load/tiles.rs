@@ -0,0 +1,90 @@
+use std::error::Error;
+
+use super::logging::log_info;
+use super::{naming, sql, DuckDBFileProcessor};
+
+// Half the width of the Web Mercator world in metres
+const MERCATOR_HALF_WIDTH: f64 = 20037508.342789244;
+
+impl DuckDBFileProcessor {
+    // Write <table>_tiles with a Mapbox Vector Tile per (z, x, y) covering the loaded features
+    pub(super) fn write_vector_tiles(&self, geom_columns: &[String]) -> Result<(), Box<dyn Error>> {
+        let tiles = match &self.options.vector_tiles {
+            Some(tiles) => tiles,
+            None => return Ok(()),
+        };
+        let geom_column = match geom_columns.first() {
+            Some(column) => column,
+            None => return Ok(()),
+        };
+
+        // MVT attributes are every loaded column other than geometry
+        let geometry_wkt = geom_columns
+            .iter()
+            .map(|column| format!("{}_wkt", column))
+            .collect::<Vec<_>>();
+        let attributes = self
+            .get_column_names("transformed_data")?
+            .into_iter()
+            .filter(|column| !geometry_wkt.contains(column))
+            .map(|column| format!(", src.{}", naming::quote_identifier(&column)))
+            .collect::<String>();
+
+        let tiles_table = naming::qualified_name(
+            &self.schema_name,
+            &naming::derived_name(&self.table_name, "_tiles"),
+        );
+        // Only tiles over the layer extent are generated, and tiles without features are skipped
+        let tiles_sql = format!(
+            "BEGIN;
+            DROP TABLE IF EXISTS {tiles};
+            CREATE TABLE {tiles} (z integer, x integer, y integer, tile bytea NOT NULL, PRIMARY KEY (z, x, y));
+            INSERT INTO {tiles} (z, x, y, tile)
+            WITH extent AS (
+                SELECT ST_Transform(ST_MakeEnvelope(
+                    GREATEST(ST_XMin(e), -180), GREATEST(ST_YMin(e), -85.0511),
+                    LEAST(ST_XMax(e), 180), LEAST(ST_YMax(e), 85.0511), 4326), 3857) AS e
+                FROM (SELECT ST_Extent(ST_Transform({geom}, 4326)) AS e FROM {target}) AS layer
+                WHERE e IS NOT NULL
+            ),
+            grid AS (
+                SELECT z, x, y
+                FROM extent,
+                    generate_series({min_zoom}, {max_zoom}) AS z,
+                    LATERAL (SELECT {half} * 2 / (2 ^ z) AS size, (2 ^ z)::integer - 1 AS last) AS zoom,
+                    generate_series(
+                        GREATEST(floor((ST_XMin(e) + {half}) / size)::integer, 0),
+                        LEAST(floor((ST_XMax(e) + {half}) / size)::integer, last)) AS x,
+                    generate_series(
+                        GREATEST(floor(({half} - ST_YMax(e)) / size)::integer, 0),
+                        LEAST(floor(({half} - ST_YMin(e)) / size)::integer, last)) AS y
+            )
+            SELECT grid.z, grid.x, grid.y, ST_AsMVT(features, {layer}, 4096, 'geom')
+            FROM grid
+            CROSS JOIN LATERAL (
+                SELECT ST_AsMVTGeom(ST_Transform(src.{geom}, 3857), ST_TileEnvelope(grid.z, grid.x, grid.y)) AS geom{attributes}
+                FROM {target} AS src
+                WHERE src.{geom} && ST_Transform(ST_TileEnvelope(grid.z, grid.x, grid.y), {srid})
+            ) AS features
+            GROUP BY grid.z, grid.x, grid.y;
+            COMMIT;",
+            tiles = tiles_table,
+            geom = naming::quote_identifier(geom_column),
+            srid = self.geometry_srid(geom_column),
+            target = naming::qualified_name(&self.schema_name, &self.table_name),
+            min_zoom = tiles.min_zoom,
+            max_zoom = tiles.max_zoom,
+            half = MERCATOR_HALF_WIDTH,
+            layer = sql::literal(&self.table_name),
+            attributes = attributes,
+        );
+        self.postgres_execute("write vector tiles", &tiles_sql)?;
+        log_info!(
+            "Vector tiles for zooms {}-{} written to {}",
+            tiles.min_zoom,
+            tiles.max_zoom,
+            tiles_table
+        );
+        Ok(())
+    }
+}
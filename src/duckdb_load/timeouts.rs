@@ -0,0 +1,295 @@
+use std::error::Error;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use postgres::error::SqlState;
+
+use super::logging::{self, Event};
+use super::{DuckDBFileProcessor, LoadStage, Timeouts, Verbosity};
+
+// Error a load stops with when a stage or the whole load runs out of time
+#[derive(Debug)]
+pub(super) struct LoadTimeout {
+    stage: &'static str,
+    limit: Duration,
+}
+
+impl fmt::Display for LoadTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} timed out after {:?}", self.stage, self.limit)
+    }
+}
+
+impl Error for LoadTimeout {}
+
+// Whether an error is a load timeout, including Postgres cancelling a statement that ran past
+// the write timeout (SQLSTATE 57014). Statements cancelled inside DuckDB's Postgres attach only
+// surface as text, so those are caught by is_out_of_time instead
+pub(super) fn is_timeout(error: &(dyn Error + 'static)) -> bool {
+    error.downcast_ref::<LoadTimeout>().is_some()
+        || error
+            .downcast_ref::<postgres::Error>()
+            .and_then(postgres::Error::code)
+            .is_some_and(|code| *code == SqlState::QUERY_CANCELED)
+}
+
+// Whether a connection string is a URI rather than libpq key/value pairs. The scheme is case
+// insensitive, as libpq treats it
+pub(super) fn is_uri(connection: &str) -> bool {
+    let scheme = connection.split("://").next().unwrap_or_default();
+    connection.contains("://")
+        && (scheme.eq_ignore_ascii_case("postgres") || scheme.eq_ignore_ascii_case("postgresql"))
+}
+
+// Connection string with the write timeout applied as the Postgres statement_timeout and the
+// connect timeout, so a hung server can't hold a single statement forever. Options already in
+// the string are kept, with the statement_timeout added after them
+pub(super) fn with_write_timeout(connection: &str, timeouts: &Timeouts) -> String {
+    let Some(write) = timeouts.write.filter(|_| !connection.is_empty()) else {
+        return connection.to_string();
+    };
+    let connect_seconds = write.as_secs().max(1);
+    let statement_ms = write.as_millis().max(1);
+    let mut connection = connection.to_string();
+    if is_uri(&connection) {
+        if !connection.contains("connect_timeout=") {
+            add_uri_parameter(
+                &mut connection,
+                &format!("connect_timeout={}", connect_seconds),
+            );
+        }
+        let timeout = format!("-c%20statement_timeout%3D{}", statement_ms);
+        if !append_uri_option(&mut connection, &timeout) {
+            add_uri_parameter(&mut connection, &format!("options={}", timeout));
+        }
+    } else {
+        if !connection.contains("connect_timeout") {
+            connection.push_str(&format!(" connect_timeout={}", connect_seconds));
+        }
+        let timeout = format!("-c statement_timeout={}", statement_ms);
+        if !append_key_value_option(&mut connection, &timeout) {
+            connection.push_str(&format!(" options='{}'", timeout));
+        }
+    }
+    connection
+}
+
+fn add_uri_parameter(connection: &mut String, parameter: &str) {
+    connection.push(if connection.contains('?') { '&' } else { '?' });
+    connection.push_str(parameter);
+}
+
+// Add an option to the end of an options parameter already in a URI's query, returning whether
+// there was one
+fn append_uri_option(connection: &mut String, option: &str) -> bool {
+    let Some(query_start) = connection.find('?') else {
+        return false;
+    };
+    let mut offset = query_start + 1;
+    for parameter in connection[query_start + 1..].split('&') {
+        if parameter.starts_with("options=") {
+            let end = offset + parameter.len();
+            connection.insert_str(end, &format!("%20{}", option));
+            return true;
+        }
+        offset += parameter.len() + 1;
+    }
+    false
+}
+
+// Add an option to the end of an options value already in key/value pairs, returning whether
+// there was one. The value may be quoted, with quotes inside escaped by backslashes
+fn append_key_value_option(connection: &mut String, option: &str) -> bool {
+    let bytes = connection.as_bytes();
+    let mut search = 0;
+    while let Some(found) = connection[search..].find("options") {
+        let start = search + found;
+        search = start + "options".len();
+        if start > 0 && !bytes[start - 1].is_ascii_whitespace() {
+            continue;
+        }
+        let rest = connection[search..].trim_start();
+        let Some(value) = rest.strip_prefix('=') else {
+            continue;
+        };
+        let value_start = connection.len() - value.trim_start().len();
+        if bytes.get(value_start) == Some(&b'\'') {
+            let mut index = value_start + 1;
+            while index < bytes.len() && bytes[index] != b'\'' {
+                index += if bytes[index] == b'\\' { 2 } else { 1 };
+            }
+            let end = index.min(bytes.len());
+            connection.insert_str(end, &format!(" {}", option));
+        } else {
+            let end = connection[value_start..]
+                .find(char::is_whitespace)
+                .map_or(connection.len(), |end| value_start + end);
+            let value = connection[value_start..end].to_string();
+            connection.replace_range(value_start..end, &format!("'{} {}'", value, option));
+        }
+        return true;
+    }
+    false
+}
+
+// Name and time limit of a load stage
+fn stage_limit(timeouts: &Timeouts, stage: LoadStage) -> (&'static str, Option<Duration>) {
+    match stage {
+        LoadStage::Detection => ("Detection", timeouts.detection),
+        LoadStage::Transform => ("Transform", timeouts.transform),
+        LoadStage::Write => ("Postgres write", timeouts.write),
+    }
+}
+
+// When a stage started at stage_started, or the whole load, runs out of time - whichever is
+// first
+pub(super) fn deadline(
+    timeouts: &Timeouts,
+    started: Instant,
+    stage: LoadStage,
+    stage_started: Instant,
+) -> Option<Instant> {
+    let total = timeouts.total.map(|total| started + total);
+    let stage = stage_limit(timeouts, stage)
+        .1
+        .map(|limit| stage_started + limit);
+    match (total, stage) {
+        (Some(total), Some(stage)) => Some(total.min(stage)),
+        (total, stage) => total.or(stage),
+    }
+}
+
+impl DuckDBFileProcessor {
+    pub(super) fn begin_stage(&self, stage: LoadStage) {
+        self.record_stage_time();
+        let stage_started = Instant::now();
+        self.stage.set((stage, stage_started));
+        *self.deadline.lock().unwrap_or_else(|e| e.into_inner()) =
+            deadline(&self.options.timeouts, self.started, stage, stage_started);
+        self.report_stage(stage);
+        logging::emit(
+            Verbosity::Info,
+            Event::Stage(stage.name()),
+            format_args!("Starting {} stage of {}", stage.name(), self.table_name),
+        );
+    }
+
+    // Stop here if the load was cancelled or has run out of time
+    pub(super) fn checkpoint(&self) -> Result<(), Box<dyn Error>> {
+        self.check_cancelled()?;
+
+        let timeouts = &self.options.timeouts;
+        if let Some(total) = timeouts.total {
+            if self.started.elapsed() > total {
+                return Err(LoadTimeout {
+                    stage: "Load",
+                    limit: total,
+                }
+                .into());
+            }
+        }
+        let (stage, stage_started) = self.stage.get();
+        match stage_limit(timeouts, stage) {
+            (name, Some(limit)) if stage_started.elapsed() > limit => {
+                Err(LoadTimeout { stage: name, limit }.into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Whether the load has run out of time, so a statement the watchdog cancelled is reported
+    // as a timeout
+    pub(super) fn is_out_of_time(&self) -> bool {
+        self.deadline
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_some_and(|deadline| Instant::now() > deadline)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_timeout(seconds: u64) -> Timeouts {
+        Timeouts {
+            write: Some(Duration::from_secs(seconds)),
+            ..Timeouts::default()
+        }
+    }
+
+    #[test]
+    fn adds_the_write_timeout_to_uris() {
+        let timeouts = write_timeout(30);
+        assert_eq!(
+            with_write_timeout("postgres://user@host/db", &timeouts),
+            "postgres://user@host/db?connect_timeout=30&options=-c%20statement_timeout%3D30000"
+        );
+        assert_eq!(
+            with_write_timeout("POSTGRESQL://host/db?sslmode=require", &timeouts),
+            "POSTGRESQL://host/db?sslmode=require&connect_timeout=30\
+            &options=-c%20statement_timeout%3D30000"
+        );
+        assert_eq!(
+            with_write_timeout(
+                "postgres://host/db?options=-c%20search_path%3Dgis",
+                &timeouts
+            ),
+            "postgres://host/db?options=-c%20search_path%3Dgis%20-c%20statement_timeout%3D30000\
+            &connect_timeout=30"
+        );
+        assert_eq!(
+            with_write_timeout("postgres://host/db?connect_timeout=5", &timeouts),
+            "postgres://host/db?connect_timeout=5&options=-c%20statement_timeout%3D30000"
+        );
+    }
+
+    #[test]
+    fn adds_the_write_timeout_to_key_value_pairs() {
+        let timeouts = write_timeout(2);
+        assert_eq!(
+            with_write_timeout("host=db dbname=gis", &timeouts),
+            "host=db dbname=gis connect_timeout=2 options='-c statement_timeout=2000'"
+        );
+        assert_eq!(
+            with_write_timeout("host=db options='-c search_path=gis' dbname=gis", &timeouts),
+            "host=db options='-c search_path=gis -c statement_timeout=2000' dbname=gis \
+            connect_timeout=2"
+        );
+        assert_eq!(
+            with_write_timeout("host=db options = -csearch_path=gis", &timeouts),
+            "host=db options = '-csearch_path=gis -c statement_timeout=2000' connect_timeout=2"
+        );
+        assert_eq!(
+            with_write_timeout("host=db application_options=x", &timeouts),
+            "host=db application_options=x connect_timeout=2 \
+            options='-c statement_timeout=2000'"
+        );
+    }
+
+    #[test]
+    fn leaves_connections_without_a_write_timeout() {
+        let timeouts = Timeouts::default();
+        assert_eq!(with_write_timeout("host=db", &timeouts), "host=db");
+        assert_eq!(with_write_timeout("", &write_timeout(1)), "");
+    }
+
+    #[test]
+    fn tells_uris_from_key_value_pairs() {
+        assert!(is_uri("postgres://host/db"));
+        assert!(is_uri("PostgreSQL://host/db"));
+        assert!(!is_uri("host=db password=a://b"));
+        assert!(!is_uri("mysql://host/db"));
+    }
+
+    #[test]
+    fn only_load_timeouts_are_timeouts() {
+        let timeout = LoadTimeout {
+            stage: "Load",
+            limit: Duration::from_secs(1),
+        };
+        assert!(is_timeout(&timeout));
+        let other: Box<dyn Error> = "canceling statement due to statement timeout".into();
+        assert!(!is_timeout(other.as_ref()));
+    }
+}
@@ -0,0 +1,174 @@
+use std::error::Error;
+use std::io;
+
+use duckdb::Connection;
+
+use super::logging::log_info;
+use super::{
+    connection, credentials, naming, open_connection, sql, unique_attach_alias, DuckDBFileProcessor,
+};
+
+// Version tables are named <table>__v<YYYYMMDDHHMMSS>
+const VERSION_MARKER: &str = "__v";
+// Length of the timestamp ending a version table's name
+const STAMP_LENGTH: usize = 14;
+
+impl DuckDBFileProcessor {
+    // Rename staging to a new version, point the table's view at it and drop the oldest versions
+    pub(super) fn swap_in_version(
+        &self,
+        staging_table: &str,
+        keep_versions: usize,
+    ) -> Result<(), Box<dyn Error>> {
+        let stamp: String = self
+            .query_postgres("SELECT to_char(clock_timestamp(), 'YYYYMMDDHH24MISS')")?
+            .ok_or("Could not read the Postgres clock")?;
+        let version = format!("{}{}", version_prefix(&self.table_name), stamp);
+
+        let view = naming::qualified_name(&self.schema_name, &self.table_name);
+        // A table loaded before versioning was enabled is kept as the oldest version
+        let replace_current = match self.target_relkind()?.as_deref() {
+            Some("r") => format!(
+                "ALTER TABLE {} RENAME TO {};",
+                view,
+                naming::quote_identifier(&format!(
+                    "{}{}",
+                    version_prefix(&self.table_name),
+                    "0".repeat(STAMP_LENGTH)
+                ))
+            ),
+            Some(_) => format!("DROP VIEW {};", view),
+            None => String::new(),
+        };
+        let swap_sql = format!(
+            "BEGIN;
+            {}
+            ALTER TABLE {} RENAME TO {};
+            CREATE VIEW {} AS SELECT * FROM {};
+            COMMIT;",
+            replace_current,
+            staging_table,
+            naming::quote_identifier(&version),
+            view,
+            naming::qualified_name(&self.schema_name, &version)
+        );
+        self.postgres_execute("swap in table version", &swap_sql)?;
+        self.report.borrow_mut().version_table = Some(version);
+
+        let expired: Vec<String> = list_versions(
+            &self.conn,
+            &self.attach_alias,
+            &self.schema_name,
+            &self.table_name,
+        )?
+        .into_iter()
+        .skip(keep_versions)
+        .map(|version| naming::qualified_name(&self.schema_name, &version))
+        .collect();
+        if !expired.is_empty() {
+            self.postgres_execute(
+                "drop expired table versions",
+                &format!("DROP TABLE {};", expired.join(", ")),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Roll a versioned table back to its previous version, dropping the latest one.
+// Returns the name of the version the table now points at
+pub fn rollback_table_version(
+    postgis_uri: &str,
+    schema_name: &str,
+    table_name: &str,
+) -> Result<String, io::Error> {
+    let connection = credentials::resolve_target_connection(postgis_uri);
+    let schema_name = &credentials::resolve_schema(schema_name);
+    rollback_version(&connection, schema_name, table_name).map_err(|e| {
+        io::Error::other(format!(
+            "Error rolling back table '{}.{}': {}",
+            schema_name,
+            table_name,
+            credentials::redact(&e.to_string(), &connection)
+        ))
+    })
+}
+
+fn rollback_version(
+    postgis_uri: &str,
+    schema_name: &str,
+    table_name: &str,
+) -> Result<String, Box<dyn Error>> {
+    connection::validate_connection(postgis_uri)?;
+    let conn = open_connection()?;
+    let attach_alias = unique_attach_alias();
+    conn.execute(
+        &format!(
+            "ATTACH '{}' AS {} (TYPE POSTGRES)",
+            postgis_uri.replace('\'', "''"),
+            attach_alias
+        ),
+        [],
+    )?;
+
+    let versions = list_versions(&conn, &attach_alias, schema_name, table_name)?;
+    let (latest, previous) = match versions.as_slice() {
+        [latest, previous, ..] => (latest, previous),
+        _ => return Err("There is no previous version to roll back to".into()),
+    };
+    let rollback_sql = format!(
+        "BEGIN;
+        DROP VIEW {view};
+        CREATE VIEW {view} AS SELECT * FROM {previous};
+        DROP TABLE {latest};
+        COMMIT;",
+        view = naming::qualified_name(schema_name, table_name),
+        previous = naming::qualified_name(schema_name, previous),
+        latest = naming::qualified_name(schema_name, latest)
+    );
+    conn.execute(&sql::postgres_execute(&attach_alias, &rollback_sql), [])?;
+    log_info!("Rolled {} back from {} to {}", table_name, latest, previous);
+    Ok(previous.clone())
+}
+
+// Version tables of a table, newest first
+fn list_versions(
+    conn: &Connection,
+    attach_alias: &str,
+    schema_name: &str,
+    table_name: &str,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let prefix = version_prefix(table_name);
+    let versions_sql = format!(
+        "SELECT c.relname::text FROM pg_class c
+        JOIN pg_namespace n ON n.oid = c.relnamespace
+        WHERE n.nspname = {} AND c.relkind = 'r' AND starts_with(c.relname, {})
+        ORDER BY c.relname DESC",
+        sql::literal(schema_name),
+        sql::literal(&prefix)
+    );
+    let mut stmt = conn.prepare(&format!(
+        "SELECT * FROM {};",
+        sql::postgres_query(attach_alias, &versions_sql)
+    ))?;
+    let names = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(names
+        .into_iter()
+        .filter(|name| {
+            let stamp = &name[prefix.len()..];
+            stamp.len() == STAMP_LENGTH && stamp.bytes().all(|b| b.is_ascii_digit())
+        })
+        .collect())
+}
+
+// Start of a table's version names, up to the timestamp - shortened with a hash when a full
+// name wouldn't fit, so every version of the table still shares it and sorts by time
+fn version_prefix(table_name: &str) -> String {
+    let name = naming::derived_name(
+        table_name,
+        &format!("{}{}", VERSION_MARKER, "0".repeat(STAMP_LENGTH)),
+    );
+    name[..name.len() - STAMP_LENGTH].to_string()
+}
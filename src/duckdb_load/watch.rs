@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use notify::{EventKind, RecursiveMode, Watcher};
+
+use super::logging::{log_info, log_warn};
+use super::{determine_file_type, launch_process_file_with_options, naming, ProcessorOptions};
+
+// A file is loaded once its size has not changed for this long, so partly copied files are left
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+
+// Shapefile parts loaded with their .shp and moved alongside it
+const SHAPEFILE_SIDECARS: &[&str] = &["dbf", "shx", "prj", "cpg", "qix", "sbn", "sbx"];
+
+// Sidecars a shapefile can't be read without, so it isn't loaded until they've arrived
+const REQUIRED_SHAPEFILE_SIDECARS: &[&str] = &["dbf", "shx"];
+
+// Watch a directory and load each file dropped into it, into a table named from the file name.
+// Files are moved to processed/ or failed/ under the directory afterwards, so a file changed or
+// dropped again is loaded again. Files already in the directory are loaded first. Runs until the
+// watcher fails
+pub fn watch_folder(
+    dir: &str,
+    postgis_uri: &str,
+    schema_name: &str,
+    options: ProcessorOptions,
+) -> Result<(), io::Error> {
+    let dir = Path::new(dir);
+    let processed = dir.join("processed");
+    let failed = dir.join("failed");
+    fs::create_dir_all(&processed)?;
+    fs::create_dir_all(&failed)?;
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).map_err(io::Error::other)?;
+    watcher
+        .watch(dir, RecursiveMode::NonRecursive)
+        .map_err(io::Error::other)?;
+    log_info!("Watching {} for files to load", dir.display());
+
+    // Candidate files with their last seen size and when it last changed
+    let mut pending: HashMap<PathBuf, (u64, Instant)> = HashMap::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if is_candidate(&path) {
+            pending.insert(path, (0, Instant::now()));
+        }
+    }
+
+    loop {
+        match receiver.recv_timeout(SETTLE_TIME / 2) {
+            Ok(event) => {
+                let event = event.map_err(io::Error::other)?;
+                if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                    for path in event.paths.into_iter().filter(|path| is_candidate(path)) {
+                        pending.insert(path, (0, Instant::now()));
+                    }
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(io::Error::other("Folder watcher stopped"));
+            }
+        }
+
+        let mut settled = Vec::new();
+        pending.retain(|path, (size, changed)| {
+            let Some(current) = total_size(path) else {
+                // Removed or moved away before it settled
+                return false;
+            };
+            if current != *size {
+                *size = current;
+                *changed = Instant::now();
+            } else if changed.elapsed() >= SETTLE_TIME && has_required_sidecars(path) {
+                settled.push(path.clone());
+                return false;
+            }
+            true
+        });
+        for path in settled {
+            let destination = match load_file(&path, postgis_uri, schema_name, &options) {
+                Ok(()) => &processed,
+                Err(e) => {
+                    log_warn!("Failed to load {}: {}", path.display(), e);
+                    &failed
+                }
+            };
+            // Left where it is rather than stopping the watch, e.g. when it's been removed
+            if let Err(e) = move_file(&path, destination) {
+                log_warn!(
+                    "Failed to move {} to {}: {}",
+                    path.display(),
+                    destination.display(),
+                    e
+                );
+            }
+        }
+    }
+}
+
+// Regular files other than hidden files and shapefile sidecars
+fn is_candidate(path: &Path) -> bool {
+    let hidden = path
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+    let sidecar = path.extension().is_some_and(|extension| {
+        SHAPEFILE_SIDECARS.contains(&extension.to_string_lossy().to_lowercase().as_str())
+    });
+    path.is_file() && !hidden && !sidecar
+}
+
+fn is_shapefile(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("shp"))
+}
+
+// Sidecar of a shapefile with the given extension, in either case
+fn sidecar(path: &Path, extension: &str) -> Option<PathBuf> {
+    [extension.to_string(), extension.to_uppercase()]
+        .into_iter()
+        .map(|extension| path.with_extension(extension))
+        .find(|sidecar| sidecar.is_file())
+}
+
+// Size of a file with its shapefile sidecars, so a sidecar still being copied holds the load
+fn total_size(path: &Path) -> Option<u64> {
+    let mut size = fs::metadata(path).ok()?.len();
+    if is_shapefile(path) {
+        for sidecar in SHAPEFILE_SIDECARS.iter().filter_map(|e| sidecar(path, e)) {
+            size += fs::metadata(sidecar).map_or(0, |metadata| metadata.len());
+        }
+    }
+    Some(size)
+}
+
+fn has_required_sidecars(path: &Path) -> bool {
+    !is_shapefile(path)
+        || REQUIRED_SHAPEFILE_SIDECARS
+            .iter()
+            .all(|extension| sidecar(path, extension).is_some())
+}
+
+fn load_file(
+    path: &Path,
+    postgis_uri: &str,
+    schema_name: &str,
+    options: &ProcessorOptions,
+) -> Result<(), io::Error> {
+    let file_path = path.to_string_lossy();
+    let file_type = determine_file_type(&file_path)?;
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let table_name = naming::sanitize_identifier(&stem);
+    log_info!(
+        "Loading {} ({:?}) into {}.{}",
+        path.display(),
+        file_type,
+        schema_name,
+        table_name
+    );
+    let report = launch_process_file_with_options(
+        &file_path,
+        &table_name,
+        postgis_uri,
+        schema_name,
+        options.clone(),
+    )?;
+    log_info!("Loaded {} rows into {}", report.rows_loaded, table_name);
+    Ok(())
+}
+
+// Move a file into dir, with its shapefile sidecars
+fn move_file(path: &Path, dir: &Path) -> Result<(), io::Error> {
+    let mut paths = vec![path.to_path_buf()];
+    if is_shapefile(path) {
+        paths.extend(SHAPEFILE_SIDECARS.iter().filter_map(|e| sidecar(path, e)));
+    }
+    for path in paths {
+        if let Some(name) = path.file_name() {
+            fs::rename(&path, dir.join(name))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_hidden_files_and_sidecars() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in [
+            "roads.geojson",
+            ".roads.geojson.part",
+            "roads.dbf",
+            "roads.PRJ",
+        ] {
+            fs::write(dir.path().join(name), "x").unwrap();
+        }
+        assert!(is_candidate(&dir.path().join("roads.geojson")));
+        assert!(!is_candidate(&dir.path().join(".roads.geojson.part")));
+        assert!(!is_candidate(&dir.path().join("roads.dbf")));
+        assert!(!is_candidate(&dir.path().join("roads.PRJ")));
+        assert!(!is_candidate(&dir.path().join("missing.csv")));
+        assert!(!is_candidate(dir.path()));
+    }
+
+    #[test]
+    fn shapefiles_wait_for_their_required_sidecars() {
+        let dir = tempfile::tempdir().unwrap();
+        let shp = dir.path().join("parcels.shp");
+        fs::write(&shp, "shp").unwrap();
+        assert!(!has_required_sidecars(&shp));
+        assert_eq!(total_size(&shp), Some(3));
+
+        fs::write(dir.path().join("parcels.DBF"), "dbf").unwrap();
+        assert!(!has_required_sidecars(&shp));
+        fs::write(dir.path().join("parcels.shx"), "shx").unwrap();
+        assert!(has_required_sidecars(&shp));
+        assert_eq!(total_size(&shp), Some(9));
+
+        let csv = dir.path().join("parcels.csv");
+        fs::write(&csv, "a,b").unwrap();
+        assert!(has_required_sidecars(&csv));
+        assert_eq!(total_size(&csv), Some(3));
+        assert_eq!(total_size(&dir.path().join("gone.csv")), None);
+    }
+
+    #[test]
+    fn moves_shapefiles_with_their_sidecars() {
+        let dir = tempfile::tempdir().unwrap();
+        let processed = dir.path().join("processed");
+        fs::create_dir(&processed).unwrap();
+        for name in [
+            "parcels.shp",
+            "parcels.dbf",
+            "parcels.SHX",
+            "parcels.prj",
+            "other.dbf",
+        ] {
+            fs::write(dir.path().join(name), "x").unwrap();
+        }
+        move_file(&dir.path().join("parcels.shp"), &processed).unwrap();
+
+        let mut moved = fs::read_dir(&processed)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        moved.sort();
+        assert_eq!(
+            moved,
+            ["parcels.SHX", "parcels.dbf", "parcels.prj", "parcels.shp"]
+        );
+        assert!(dir.path().join("other.dbf").is_file());
+
+        // A file that's already gone is an error for the caller to log
+        assert!(move_file(&dir.path().join("parcels.shp"), &processed).is_err());
+    }
+}
@@ -0,0 +1,225 @@
+use std::error::Error;
+
+use super::inspect::{infer_crs, Extent};
+use super::logging::{log_debug, log_info, log_warn};
+use super::{naming, DuckDBFileProcessor, FileType};
+
+// Start of a WKT or EWKT value - ST_GeomFromText checks the rest of it
+const WKT_PATTERN: &str = concat!(
+    r"^\s*(SRID=\d+;\s*)?",
+    r"(POINT|LINESTRING|POLYGON|MULTIPOINT|MULTILINESTRING|MULTIPOLYGON|GEOMETRYCOLLECTION)",
+    r"\s*(ZM|Z|M)?\s*(\(|EMPTY\s*$)"
+);
+// The SRID= prefix of an EWKT value, as written by PostGIS
+const SRID_PATTERN: &str = r"^\s*SRID=(\d+);";
+
+impl DuckDBFileProcessor {
+    // Parse text columns of a CSV or spreadsheet whose values are all WKT into geometry, so
+    // they're found, transformed and written like the geometry of any other source. Columns
+    // named in column_types are left as they are
+    pub(super) fn apply_wkt_geometry(&self) -> Result<(), Box<dyn Error>> {
+        if !matches!(
+            self.file_type,
+            FileType::Csv | FileType::Excel | FileType::Ods
+        ) {
+            return Ok(());
+        }
+        let mut stmt = self.conn.prepare(
+            "SELECT column_name FROM information_schema.columns
+            WHERE table_name = 'data' AND data_type = 'VARCHAR'",
+        )?;
+        let columns = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for column in columns.iter().filter(|column| {
+            !self
+                .options
+                .column_types
+                .keys()
+                .any(|typed| typed.eq_ignore_ascii_case(column))
+        }) {
+            let Some(srids) = self.wkt_srids(column)? else {
+                continue;
+            };
+            if srids.len() > 1 {
+                log_warn!(
+                    "Column {} holds WKT with SRIDs {} - it's kept as text, as one column \
+                    can only have one CRS",
+                    column,
+                    srids.join(", ")
+                );
+                continue;
+            }
+
+            let quoted = naming::quote_identifier(column);
+            let parsed = self.conn.execute(
+                &format!(
+                    "ALTER TABLE data ALTER COLUMN {column} TYPE GEOMETRY USING ST_GeomFromText(
+                        NULLIF(trim(regexp_replace({column}, '{srid}', '', 'i')), ''));",
+                    column = quoted,
+                    srid = SRID_PATTERN
+                ),
+                [],
+            );
+            if let Err(e) = parsed {
+                log_warn!(
+                    "Column {} looks like WKT but not all of it parses, so it's kept as text: {}",
+                    column,
+                    e
+                );
+                continue;
+            }
+            log_info!("Column {} parsed as WKT geometry", column);
+            self.wkt_columns
+                .borrow_mut()
+                .insert(column.clone(), srids.into_iter().next());
+            self.report.borrow_mut().wkt_columns.push(column.clone());
+        }
+        Ok(())
+    }
+
+    // The distinct SRIDs given by a column's EWKT prefixes when every non-blank value in it
+    // starts like WKT, or None when it isn't a WKT column
+    fn wkt_srids(&self, column: &str) -> Result<Option<Vec<String>>, Box<dyn Error>> {
+        let quoted = naming::quote_identifier(column);
+        let (values, wkt_values, srids): (i64, i64, Option<String>) = self.conn.query_row(
+            &format!(
+                "SELECT count(*) FILTER (WHERE trim({column}) <> ''),
+                    count(*) FILTER (WHERE regexp_matches({column}, '{wkt}', 'i')),
+                    string_agg(DISTINCT regexp_extract({column}, '{srid}', 1, 'i'), ', ')
+                        FILTER (WHERE regexp_matches({column}, '{srid}', 'i'))
+                FROM data;",
+                column = quoted,
+                wkt = WKT_PATTERN,
+                srid = SRID_PATTERN
+            ),
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        log_debug!(
+            "Column {} has {} values, {} of them WKT",
+            column,
+            values,
+            wkt_values
+        );
+        let srids = srids
+            .map(|srids| srids.split(", ").map(str::to_string).collect())
+            .unwrap_or_default();
+        Ok((values > 0 && wkt_values == values).then_some(srids))
+    }
+
+    // CRS of a geometry column parsed from WKT - its SRID= prefix, else the assumed CRS, else
+    // guessed from its coordinates. None for columns that weren't WKT text
+    pub(super) fn wkt_column_crs(
+        &self,
+        geom_column: &str,
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        let Some(srid) = self.wkt_columns.borrow().get(geom_column).cloned() else {
+            return Ok(None);
+        };
+        if let Some(crs_number) = srid.or_else(|| self.options.assumed_crs.clone()) {
+            return Ok(Some(crs_number));
+        }
+
+        let quoted = naming::quote_identifier(geom_column);
+        let extent = self.conn.query_row(
+            &format!(
+                "SELECT min(ST_XMin({column})), min(ST_YMin({column})),
+                    max(ST_XMax({column})), max(ST_YMax({column}))
+                FROM data WHERE NOT ST_IsEmpty({column});",
+                column = quoted
+            ),
+            [],
+            |row| {
+                Ok(match (row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?) {
+                    (Some(min_x), Some(min_y), Some(max_x), Some(max_y)) => Some(Extent {
+                        min_x,
+                        min_y,
+                        max_x,
+                        max_y,
+                    }),
+                    _ => None,
+                })
+            },
+        )?;
+        match extent.and_then(infer_crs) {
+            Some(crs) => {
+                log_warn!(
+                    "WKT column {} declares no CRS, so EPSG:{} is assumed from its coordinates \
+                    - set assumed_crs if that's wrong",
+                    geom_column,
+                    crs.code
+                );
+                Ok(Some(crs.code))
+            }
+            None => Err(format!(
+                "CRS not found for WKT column {} - give its values an SRID=...; prefix or set \
+                assumed_crs",
+                geom_column
+            )
+            .into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use duckdb::Connection;
+
+    // Whether DuckDB's regular expressions match a value as apply_wkt_geometry does
+    fn matches(pattern: &str, value: &str) -> bool {
+        Connection::open_in_memory()
+            .unwrap()
+            .query_row(
+                "SELECT regexp_matches(?, ?, 'i');",
+                [value, pattern],
+                |row| row.get(0),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn matches_wkt_and_ewkt() {
+        for value in [
+            "POINT (1 2)",
+            "  point(1 2)",
+            "SRID=27700;POINT Z (1 2 3)",
+            "MULTIPOLYGON (((0 0, 1 0, 1 1, 0 0)))",
+            "LINESTRING ZM (0 0 0 0, 1 1 1 1)",
+            "GEOMETRYCOLLECTION EMPTY",
+            "POLYGON EMPTY ",
+        ] {
+            assert!(matches(WKT_PATTERN, value), "{}", value);
+        }
+    }
+
+    #[test]
+    fn rejects_text_that_isnt_wkt() {
+        for value in [
+            "",
+            "Point of interest",
+            "POINTS (1 2)",
+            "POINT EMPTY or not",
+            "SRID=abc;POINT (1 2)",
+            "{\"type\": \"Point\"}",
+        ] {
+            assert!(!matches(WKT_PATTERN, value), "{}", value);
+        }
+    }
+
+    #[test]
+    fn extracts_ewkt_srids() {
+        let srid: String = Connection::open_in_memory()
+            .unwrap()
+            .query_row(
+                "SELECT regexp_extract(?, ?, 1, 'i');",
+                ["srid=4326; POINT (1 2)", SRID_PATTERN],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(srid, "4326");
+        assert!(!matches(SRID_PATTERN, "POINT (1 2)"));
+    }
+}
@@ -0,0 +1,720 @@
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::Read;
+
+// Legacy .xls workbooks, which GDAL in the spatial extension has no driver for. Only the cell
+// values of BIFF8 (Excel 97-2003) workbooks are read - cell formats aren't, so dates come
+// through as serial numbers for date_columns to convert
+
+// Compound file signature, shared by every legacy Office format
+const CFB_SIGNATURE: [u8; 8] = [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+const END_OF_CHAIN: u32 = 0xFFFF_FFFE;
+const DIRECTORY_ENTRY_SIZE: usize = 128;
+const STREAM_ENTRY: u8 = 2;
+
+// BIFF8 record types
+const BOF: u16 = 0x0809;
+const EOF: u16 = 0x000A;
+const BOUNDSHEET: u16 = 0x0085;
+const DATEMODE: u16 = 0x0022;
+const FILEPASS: u16 = 0x002F;
+const SST: u16 = 0x00FC;
+const CONTINUE: u16 = 0x003C;
+const LABEL_SST: u16 = 0x00FD;
+const LABEL: u16 = 0x0204;
+const RSTRING: u16 = 0x00D6;
+const NUMBER: u16 = 0x0203;
+const RK: u16 = 0x027E;
+const MULRK: u16 = 0x00BD;
+const BOOLERR: u16 = 0x0205;
+const FORMULA: u16 = 0x0006;
+const STRING: u16 = 0x0207;
+const BIFF8: u16 = 0x0600;
+
+pub(super) struct XlsWorkbook {
+    // The Workbook stream of the compound file, holding every sheet
+    stream: Vec<u8>,
+    // Name and stream offset of each worksheet, in workbook order
+    sheets: Vec<(String, usize)>,
+    // Shared strings, which most text cells refer to by index
+    strings: Vec<String>,
+    date_1904: bool,
+}
+
+// Whether a file is a compound file, and so a legacy .xls rather than an .xlsx workbook
+pub(super) fn is_xls(path: &str) -> bool {
+    let mut signature = [0u8; 8];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut signature))
+        .is_ok_and(|_| signature == CFB_SIGNATURE)
+}
+
+impl XlsWorkbook {
+    pub(super) fn open(path: &str) -> Result<Self, Box<dyn Error>> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    fn from_bytes(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let stream = workbook_stream(data)?;
+        let mut workbook = Self {
+            stream,
+            sheets: Vec::new(),
+            strings: Vec::new(),
+            date_1904: false,
+        };
+        let records = Records::new(&workbook.stream, 0).collect::<Vec<_>>();
+        match records.first() {
+            Some((BOF, data)) if u16_at(data, 0) == Some(BIFF8) => {}
+            _ => {
+                return Err(
+                    "Only Excel 97-2003 .xls workbooks can be read - save older \
+                    workbooks as .xlsx"
+                        .into(),
+                )
+            }
+        }
+        for (index, (kind, data)) in records.iter().enumerate() {
+            match *kind {
+                FILEPASS => return Err("The Excel workbook is password protected".into()),
+                // Sheets of other types, such as charts, have no cells
+                BOUNDSHEET if data.get(5) == Some(&0) => {
+                    let offset = u32_at(data, 0).ok_or("Excel sheet record is truncated")?;
+                    let name = data
+                        .get(6..)
+                        .and_then(short_string)
+                        .ok_or("Excel sheet name is truncated")?;
+                    workbook.sheets.push((name, offset as usize));
+                }
+                DATEMODE => workbook.date_1904 = u16_at(data, 0) == Some(1),
+                SST => {
+                    // Strings run on into the CONTINUE records after the table
+                    let chunks = records[index..]
+                        .iter()
+                        .enumerate()
+                        .take_while(|(offset, (kind, _))| *offset == 0 || *kind == CONTINUE)
+                        .map(|(_, (_, data))| *data)
+                        .collect();
+                    workbook.strings = shared_strings(chunks);
+                }
+                EOF => break,
+                _ => {}
+            }
+        }
+        Ok(workbook)
+    }
+
+    // Whether serial dates count from 1904 rather than 1900
+    pub(super) fn date_1904(&self) -> bool {
+        self.date_1904
+    }
+
+    // Cell text of a worksheet, by row then column from A1, with None for blank cells. The
+    // first worksheet is read when no name is given
+    pub(super) fn sheet_rows(
+        &self,
+        sheet: Option<&str>,
+    ) -> Result<Vec<Vec<Option<String>>>, Box<dyn Error>> {
+        let offset = match sheet {
+            Some(sheet) => self
+                .sheets
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(sheet))
+                .map(|(_, offset)| *offset)
+                .ok_or_else(|| format!("The Excel workbook has no sheet named '{}'", sheet))?,
+            None => self
+                .sheets
+                .first()
+                .map(|(_, offset)| *offset)
+                .ok_or("The Excel workbook has no worksheets")?,
+        };
+
+        let mut rows = Vec::new();
+        let mut string_formula = None;
+        for (kind, data) in Records::new(&self.stream, offset).skip(1) {
+            match kind {
+                EOF => break,
+                // A formula returning text has its value in the STRING record after it
+                STRING => {
+                    if let (Some(cell), Some(text)) = (string_formula.take(), unicode_string(data))
+                    {
+                        set_cell(&mut rows, cell, text);
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+            let (Some(row), Some(column)) = (u16_at(data, 0), u16_at(data, 2)) else {
+                continue;
+            };
+            let cell = (row as usize, column as usize);
+            match kind {
+                LABEL_SST => {
+                    let index = u32_at(data, 6).ok_or("Excel cell record is truncated")?;
+                    if let Some(text) = self.strings.get(index as usize) {
+                        set_cell(&mut rows, cell, text.clone());
+                    }
+                }
+                LABEL | RSTRING => {
+                    if let Some(text) = data.get(6..).and_then(unicode_string) {
+                        set_cell(&mut rows, cell, text);
+                    }
+                }
+                NUMBER => {
+                    if let Some(value) = f64_at(data, 6) {
+                        set_cell(&mut rows, cell, value.to_string());
+                    }
+                }
+                RK => {
+                    if let Some(rk) = u32_at(data, 6) {
+                        set_cell(&mut rows, cell, rk_value(rk).to_string());
+                    }
+                }
+                MULRK => {
+                    // Each value is an XF index and an RK number, with the last column after them
+                    let values = data
+                        .get(4..data.len().saturating_sub(2))
+                        .unwrap_or_default();
+                    for (index, value) in values.chunks_exact(6).enumerate() {
+                        if let Some(rk) = u32_at(value, 2) {
+                            let cell = (cell.0, cell.1 + index);
+                            set_cell(&mut rows, cell, rk_value(rk).to_string());
+                        }
+                    }
+                }
+                // Errors such as #N/A are left blank
+                BOOLERR if data.get(7) == Some(&0) => {
+                    let value = data.get(6).is_some_and(|value| *value != 0);
+                    set_cell(&mut rows, cell, value.to_string());
+                }
+                FORMULA => match data.get(6..14) {
+                    Some(result) if result[6..8] == [0xFF, 0xFF] => match result[0] {
+                        0 => string_formula = Some(cell),
+                        1 => set_cell(&mut rows, cell, (result[2] != 0).to_string()),
+                        _ => {}
+                    },
+                    Some(_) => {
+                        if let Some(value) = f64_at(data, 6) {
+                            set_cell(&mut rows, cell, value.to_string());
+                        }
+                    }
+                    None => {}
+                },
+                _ => {}
+            }
+        }
+        Ok(rows)
+    }
+}
+
+fn set_cell(rows: &mut Vec<Vec<Option<String>>>, (row, column): (usize, usize), text: String) {
+    if rows.len() <= row {
+        rows.resize(row + 1, Vec::new());
+    }
+    let cells = &mut rows[row];
+    if cells.len() <= column {
+        cells.resize(column + 1, None);
+    }
+    cells[column] = Some(text);
+}
+
+// BIFF records of a stream from offset - type and data - up to the end or a truncated record
+struct Records<'a> {
+    stream: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Records<'a> {
+    fn new(stream: &'a [u8], offset: usize) -> Self {
+        Self { stream, offset }
+    }
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = (u16, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let kind = u16_at(self.stream, self.offset)?;
+        let length = u16_at(self.stream, self.offset + 2)? as usize;
+        let start = self.offset + 4;
+        let data = self.stream.get(start..start + length)?;
+        self.offset = start + length;
+        Some((kind, data))
+    }
+}
+
+// The Workbook stream of a compound file, following its sector chains
+fn workbook_stream(data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    if data.len() < 512 || data[..8] != CFB_SIGNATURE {
+        return Err("The file isn't an Excel workbook".into());
+    }
+    let header = |offset| u32_at(data, offset).unwrap_or(END_OF_CHAIN);
+    let sector_size = match u16_at(data, 0x1E) {
+        Some(shift @ (9 | 12)) => 1usize << shift,
+        _ => return Err("The Excel workbook has an unsupported sector size".into()),
+    };
+    let sector = |id: u32| {
+        let start = (id as usize + 1) * sector_size;
+        data.get(start..start + sector_size)
+    };
+
+    // The sectors of the allocation table are listed in the header, then in a chain of their own
+    let mut fat_sectors = (0..109)
+        .map(|index| header(0x4C + index * 4))
+        .collect::<Vec<_>>();
+    let mut difat = header(0x44);
+    for _ in 0..header(0x48) {
+        let Some(list) = sector(difat) else {
+            break;
+        };
+        fat_sectors.extend(u32_values(&list[..sector_size - 4]));
+        difat = u32_at(list, sector_size - 4).unwrap_or(END_OF_CHAIN);
+    }
+    let fat = fat_sectors
+        .into_iter()
+        .take(header(0x2C) as usize)
+        .map(|id| sector(id).ok_or("The Excel workbook is truncated"))
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .flat_map(u32_values)
+        .collect::<Vec<_>>();
+
+    let directory = read_chain(&fat, header(0x30), sector)?;
+    let entries = directory
+        .chunks_exact(DIRECTORY_ENTRY_SIZE)
+        .collect::<Vec<_>>();
+    let workbook = entries
+        .iter()
+        .find(|entry| {
+            let name_length = (u16_at(entry, 0x40).unwrap_or(0) as usize).clamp(2, 64);
+            let name = entry[..name_length - 2]
+                .chunks_exact(2)
+                .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+                .collect::<Vec<_>>();
+            entry[0x42] == STREAM_ENTRY && String::from_utf16_lossy(&name) == "Workbook"
+        })
+        .ok_or("The file has no Excel workbook in it")?;
+    let start = u32_at(workbook, 0x74).unwrap_or(END_OF_CHAIN);
+    // Version 3 files, with 512-byte sectors, may have junk in the high half of the size
+    let size = u32_at(workbook, 0x78).unwrap_or(0) as usize;
+
+    let mut stream = if (size as u32) < header(0x38) {
+        // Small streams are kept in 64-byte sectors of a mini stream held by the root entry
+        let root = entries
+            .first()
+            .ok_or("The Excel workbook has no root entry")?;
+        let mini_stream = read_chain(&fat, u32_at(root, 0x74).unwrap_or(END_OF_CHAIN), sector)?;
+        let mini_fat = u32_values(&read_chain(&fat, header(0x3C), sector)?).collect::<Vec<_>>();
+        read_chain(&mini_fat, start, |id| {
+            let start = id as usize * 64;
+            mini_stream.get(start..start + 64)
+        })?
+    } else {
+        read_chain(&fat, start, sector)?
+    };
+    stream.truncate(size);
+    Ok(stream)
+}
+
+// The sectors of a chain starting at start, joined
+fn read_chain<'a>(
+    table: &[u32],
+    start: u32,
+    sector: impl Fn(u32) -> Option<&'a [u8]>,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut stream = Vec::new();
+    let mut id = start;
+    // A chain can't be longer than the table, so a longer one loops
+    for _ in 0..=table.len() {
+        if id == END_OF_CHAIN {
+            return Ok(stream);
+        }
+        stream.extend_from_slice(sector(id).ok_or("The Excel workbook is truncated")?);
+        id = *table
+            .get(id as usize)
+            .ok_or("The Excel workbook has a broken sector chain")?;
+    }
+    Err("The Excel workbook has a looping sector chain".into())
+}
+
+// The shared string table, from the SST record and the CONTINUE records after it
+fn shared_strings(chunks: Vec<&[u8]>) -> Vec<String> {
+    let mut data = ContinuedData {
+        chunks,
+        chunk: 0,
+        position: 8,
+    };
+    let count = data
+        .chunks
+        .first()
+        .and_then(|sst| u32_at(sst, 4))
+        .unwrap_or(0);
+    let mut strings = Vec::new();
+    for _ in 0..count {
+        let Some(text) = data.rich_string() else {
+            break;
+        };
+        strings.push(text);
+    }
+    strings
+}
+
+// Data split across a record and its CONTINUE records
+struct ContinuedData<'a> {
+    chunks: Vec<&'a [u8]>,
+    chunk: usize,
+    position: usize,
+}
+
+impl ContinuedData<'_> {
+    fn byte(&mut self) -> Option<u8> {
+        loop {
+            let chunk = self.chunks.get(self.chunk)?;
+            if let Some(byte) = chunk.get(self.position) {
+                self.position += 1;
+                return Some(*byte);
+            }
+            self.chunk += 1;
+            self.position = 0;
+        }
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_le_bytes([self.byte()?, self.byte()?]))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes([
+            self.byte()?,
+            self.byte()?,
+            self.byte()?,
+            self.byte()?,
+        ]))
+    }
+
+    // A string with optional formatting runs and phonetic text, which are skipped
+    fn rich_string(&mut self) -> Option<String> {
+        let length = self.u16()? as usize;
+        let flags = self.byte()?;
+        let runs = if flags & 0x08 != 0 { self.u16()? } else { 0 };
+        let extended = if flags & 0x04 != 0 { self.u32()? } else { 0 };
+        let mut units = Vec::with_capacity(length);
+        let mut high_byte = flags & 0x01 != 0;
+        while units.len() < length {
+            // Characters split across records restart with a flags byte of their own
+            if self.position >= self.chunks.get(self.chunk)?.len() {
+                self.chunk += 1;
+                self.position = 0;
+                high_byte = self.byte()? & 0x01 != 0;
+                continue;
+            }
+            units.push(if high_byte {
+                self.u16()?
+            } else {
+                self.byte()? as u16
+            });
+        }
+        for _ in 0..runs as usize * 4 + extended as usize {
+            self.byte()?;
+        }
+        Some(String::from_utf16_lossy(&units))
+    }
+}
+
+// A string with a 16-bit length, as in LABEL and STRING records
+fn unicode_string(data: &[u8]) -> Option<String> {
+    let length = u16_at(data, 0)? as usize;
+    decode_characters(data.get(3..)?, length, *data.get(2)? & 0x01 != 0)
+}
+
+// A string with an 8-bit length, as in sheet names
+fn short_string(data: &[u8]) -> Option<String> {
+    let length = *data.first()? as usize;
+    decode_characters(data.get(2..)?, length, *data.get(1)? & 0x01 != 0)
+}
+
+// Characters stored as UTF-16, or with their high byte dropped when it is zero for them all
+fn decode_characters(data: &[u8], length: usize, high_byte: bool) -> Option<String> {
+    if high_byte {
+        let units = data
+            .get(..length * 2)?
+            .chunks_exact(2)
+            .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+            .collect::<Vec<_>>();
+        Some(String::from_utf16_lossy(&units))
+    } else {
+        Some(
+            data.get(..length)?
+                .iter()
+                .map(|byte| *byte as char)
+                .collect(),
+        )
+    }
+}
+
+// A number packed into 30 bits, either an integer or the high bits of a double, and
+// optionally multiplied by 100
+fn rk_value(rk: u32) -> f64 {
+    let value = if rk & 0x02 != 0 {
+        ((rk as i32) >> 2) as f64
+    } else {
+        f64::from_bits(((rk & 0xFFFF_FFFC) as u64) << 32)
+    };
+    if rk & 0x01 != 0 {
+        value / 100.0
+    } else {
+        value
+    }
+}
+
+fn u32_values(bytes: &[u8]) -> impl Iterator<Item = u32> + '_ {
+    bytes
+        .chunks_exact(4)
+        .map(|value| u32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        bytes.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        bytes.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn f64_at(bytes: &[u8], offset: usize) -> Option<f64> {
+    Some(f64::from_le_bytes(
+        bytes.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FREE: u32 = 0xFFFF_FFFF;
+    const FAT_SECTOR: u32 = 0xFFFF_FFFD;
+    const SECTOR_SIZE: usize = 512;
+
+    fn record(kind: u16, data: &[u8]) -> Vec<u8> {
+        let mut record = kind.to_le_bytes().to_vec();
+        record.extend((data.len() as u16).to_le_bytes());
+        record.extend(data);
+        record
+    }
+
+    fn cell(kind: u16, row: u16, column: u16, value: &[u8]) -> Vec<u8> {
+        let mut data = row.to_le_bytes().to_vec();
+        data.extend(column.to_le_bytes());
+        // XF index
+        data.extend(0u16.to_le_bytes());
+        data.extend(value);
+        record(kind, &data)
+    }
+
+    fn bof() -> Vec<u8> {
+        let mut data = BIFF8.to_le_bytes().to_vec();
+        data.resize(16, 0);
+        record(BOF, &data)
+    }
+
+    // Workbook stream with the given global records and one worksheet named Data
+    fn biff_stream(globals: &[Vec<u8>], cells: &[Vec<u8>]) -> Vec<u8> {
+        let boundsheet = |offset: u32| {
+            let mut data = offset.to_le_bytes().to_vec();
+            data.extend([0, 0, 4, 0]);
+            data.extend(b"Data");
+            record(BOUNDSHEET, &data)
+        };
+        let mut stream = bof();
+        stream.extend(globals.concat());
+        let sheet_offset = stream.len() + boundsheet(0).len() + record(EOF, &[]).len();
+        stream.extend(boundsheet(sheet_offset as u32));
+        stream.extend(record(EOF, &[]));
+        stream.extend(bof());
+        stream.extend(cells.concat());
+        stream.extend(record(EOF, &[]));
+        stream
+    }
+
+    fn directory_entry(name: &str, kind: u8, start: u32, size: usize) -> Vec<u8> {
+        let mut entry = name
+            .encode_utf16()
+            .chain([0])
+            .flat_map(u16::to_le_bytes)
+            .collect::<Vec<_>>();
+        let name_length = entry.len() as u16;
+        entry.resize(DIRECTORY_ENTRY_SIZE, 0);
+        entry[0x40..0x42].copy_from_slice(&name_length.to_le_bytes());
+        entry[0x42] = kind;
+        entry[0x44..0x50].copy_from_slice(&[0xFF; 12]);
+        entry[0x74..0x78].copy_from_slice(&start.to_le_bytes());
+        entry[0x78..0x7C].copy_from_slice(&(size as u32).to_le_bytes());
+        entry
+    }
+
+    // Version 3 compound file holding a Workbook stream, which goes in the mini stream when
+    // it's under 4096 bytes as Excel writes it
+    fn compound_file(stream: &[u8]) -> Vec<u8> {
+        // Sector 0 holds the allocation table, and every other sector is added as a chain
+        let mut sectors = vec![Vec::new()];
+        let mut fat = vec![FAT_SECTOR];
+        let mut add_chain = |data: &[u8]| {
+            let start = sectors.len() as u32;
+            let chunks = data.chunks(SECTOR_SIZE).collect::<Vec<_>>();
+            for (index, chunk) in chunks.iter().enumerate() {
+                let mut sector = chunk.to_vec();
+                sector.resize(SECTOR_SIZE, 0);
+                sectors.push(sector);
+                let id = start + index as u32;
+                fat.push(if index + 1 == chunks.len() {
+                    END_OF_CHAIN
+                } else {
+                    id + 1
+                });
+            }
+            start
+        };
+
+        let (root, workbook, mini_fat_start) = if stream.len() < 4096 {
+            let sector_count = stream.len().div_ceil(64);
+            let mini_fat = (0..sector_count as u32)
+                .map(|id| {
+                    if id + 1 == sector_count as u32 {
+                        END_OF_CHAIN
+                    } else {
+                        id + 1
+                    }
+                })
+                .flat_map(u32::to_le_bytes)
+                .collect::<Vec<_>>();
+            let mini_fat_start = add_chain(&mini_fat);
+            let mut mini_stream = stream.to_vec();
+            mini_stream.resize(sector_count * 64, 0);
+            let root = directory_entry("Root Entry", 5, add_chain(&mini_stream), mini_stream.len());
+            (
+                root,
+                directory_entry("Workbook", STREAM_ENTRY, 0, stream.len()),
+                mini_fat_start,
+            )
+        } else {
+            let root = directory_entry("Root Entry", 5, END_OF_CHAIN, 0);
+            let workbook =
+                directory_entry("Workbook", STREAM_ENTRY, add_chain(stream), stream.len());
+            (root, workbook, END_OF_CHAIN)
+        };
+        let directory_start = add_chain(&[root, workbook].concat());
+
+        let mut header = CFB_SIGNATURE.to_vec();
+        header.resize(SECTOR_SIZE, 0);
+        let mut set = |offset: usize, value: u32| {
+            header[offset..offset + 4].copy_from_slice(&value.to_le_bytes())
+        };
+        set(0x18, 0x0003_003E);
+        set(0x1C, 0x0009_FFFE);
+        set(0x20, 6);
+        set(0x2C, 1);
+        set(0x30, directory_start);
+        set(0x38, 4096);
+        set(0x3C, mini_fat_start);
+        set(0x40, (mini_fat_start != END_OF_CHAIN) as u32);
+        set(0x44, END_OF_CHAIN);
+        set(0x4C, 0);
+        for index in 1..109 {
+            set(0x4C + index * 4, FREE);
+        }
+
+        fat.resize(SECTOR_SIZE / 4, FREE);
+        sectors[0] = fat.into_iter().flat_map(u32::to_le_bytes).collect();
+        [header, sectors.concat()].concat()
+    }
+
+    fn sheet(workbook: &XlsWorkbook) -> Vec<Vec<Option<String>>> {
+        workbook.sheet_rows(Some("data")).unwrap()
+    }
+
+    fn text(value: &str) -> Option<String> {
+        Some(value.to_string())
+    }
+
+    #[test]
+    fn reads_shared_strings_continued_across_records() {
+        // The second string starts in the SST record with UTF-16 characters and ends in a
+        // CONTINUE record with compressed ones, and the third starts in the next CONTINUE
+        let mut sst = 3u32.to_le_bytes().to_vec();
+        sst.extend(3u32.to_le_bytes());
+        sst.extend([5, 0, 0]);
+        sst.extend(b"alpha");
+        sst.extend([4, 0, 1]);
+        sst.extend("βe".encode_utf16().flat_map(u16::to_le_bytes));
+        let mut continued = vec![0];
+        continued.extend(b"ta");
+        let mut third = vec![5, 0, 0];
+        third.extend(b"gamma");
+        let globals = [sst, continued, third]
+            .iter()
+            .zip([SST, CONTINUE, CONTINUE])
+            .map(|(data, kind)| record(kind, data))
+            .collect::<Vec<_>>();
+        let cells = (0..3u32)
+            .map(|index| cell(LABEL_SST, index as u16, 0, &index.to_le_bytes()))
+            .collect::<Vec<_>>();
+
+        let workbook = XlsWorkbook::from_bytes(&compound_file(&biff_stream(&globals, &cells)));
+        assert_eq!(
+            sheet(&workbook.unwrap()),
+            vec![vec![text("alpha")], vec![text("βeta")], vec![text("gamma")]]
+        );
+    }
+
+    #[test]
+    fn reads_mulrk_values_into_consecutive_columns() {
+        let mut values = Vec::new();
+        // The integer 5, 1.5 as the high bits of a double, and 1234 divided by 100
+        for rk in [(5 << 2) | 0x02, 0x3FF8_0000, (1234 << 2) | 0x03] {
+            values.extend(0u16.to_le_bytes());
+            values.extend((rk as u32).to_le_bytes());
+        }
+        let mut data = 1u16.to_le_bytes().to_vec();
+        data.extend(1u16.to_le_bytes());
+        data.extend(values);
+        data.extend(3u16.to_le_bytes());
+
+        let stream = biff_stream(&[], &[record(MULRK, &data)]);
+        let workbook = XlsWorkbook::from_bytes(&compound_file(&stream)).unwrap();
+        assert_eq!(
+            sheet(&workbook),
+            vec![vec![], vec![None, text("5"), text("1.5"), text("12.34")]]
+        );
+    }
+
+    #[test]
+    fn reads_workbook_streams_in_and_out_of_the_mini_stream() {
+        let small = biff_stream(&[], &[cell(NUMBER, 0, 0, &2.5f64.to_le_bytes())]);
+        // Enough cells to push the stream past the mini stream cutoff
+        let cells = (0..400u16)
+            .map(|row| cell(NUMBER, row, 0, &(row as f64).to_le_bytes()))
+            .collect::<Vec<_>>();
+        let large = biff_stream(&[], &cells);
+        assert!(small.len() < 4096 && large.len() >= 4096);
+
+        let workbook = XlsWorkbook::from_bytes(&compound_file(&small)).unwrap();
+        assert_eq!(sheet(&workbook), vec![vec![text("2.5")]]);
+        let workbook = XlsWorkbook::from_bytes(&compound_file(&large)).unwrap();
+        let rows = sheet(&workbook);
+        assert_eq!(rows.len(), 400);
+        assert_eq!(rows[399], vec![text("399")]);
+    }
+
+    #[test]
+    fn reads_the_1904_date_system() {
+        let date_1904 = biff_stream(&[record(DATEMODE, &1u16.to_le_bytes())], &[]);
+        let workbook = XlsWorkbook::from_bytes(&compound_file(&date_1904)).unwrap();
+        assert!(workbook.date_1904());
+        let date_1900 = biff_stream(&[record(DATEMODE, &0u16.to_le_bytes())], &[]);
+        let workbook = XlsWorkbook::from_bytes(&compound_file(&date_1900)).unwrap();
+        assert!(!workbook.date_1904());
+    }
+}
@@ -0,0 +1,114 @@
+// HTTP upload daemon, enabled with the http feature. A multipart upload is loaded on a blocking
+// thread and answered with its load report as JSON
+use std::io::{self, Cursor};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::extract::{DefaultBodyLimit, Multipart, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde_json::json;
+
+use crate::duckdb_load::{
+    launch_process_reader, render_metrics, validate_identifier, LoadReport, ProcessorOptions,
+};
+
+// Error returned to the caller as {"error": message} with the given status
+struct HttpError(StatusCode, String);
+
+impl IntoResponse for HttpError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+fn bad_request(e: impl ToString) -> HttpError {
+    HttpError(StatusCode::BAD_REQUEST, e.to_string())
+}
+
+// Connection uploads are loaded into, and whether a client may give its own
+struct Target {
+    default_uri: String,
+    allow_client_uri: bool,
+}
+
+// Serve POST /load, and GET /metrics for Prometheus, on addr until the process is stopped
+// Uploads larger than max_upload_bytes are refused before they are read. Clients can only
+// name their own connection with allow_client_uri, as the server otherwise connects wherever
+// it is told
+pub fn serve(
+    addr: SocketAddr,
+    default_uri: &str,
+    max_upload_bytes: usize,
+    allow_client_uri: bool,
+) -> Result<(), io::Error> {
+    let app = Router::new()
+        .route("/load", post(load))
+        .route("/metrics", get(|| async { render_metrics() }))
+        .layer(DefaultBodyLimit::max(max_upload_bytes))
+        .with_state(Arc::new(Target {
+            default_uri: default_uri.to_string(),
+            allow_client_uri,
+        }));
+    tokio::runtime::Runtime::new()?.block_on(async {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        axum::serve(listener, app).await
+    })
+}
+
+// Multipart fields: file (required), table (required), schema (defaults to public) and uri
+// (defaults to the connection the server was started with, and only taken when the server
+// allows client connections)
+async fn load(
+    State(target): State<Arc<Target>>,
+    mut multipart: Multipart,
+) -> Result<Json<LoadReport>, HttpError> {
+    let mut upload = None;
+    let mut table_name = String::new();
+    let mut schema_name = "public".to_string();
+    let mut postgis_uri = target.default_uri.clone();
+    while let Some(field) = multipart.next_field().await.map_err(bad_request)? {
+        match field.name().unwrap_or_default() {
+            "file" => upload = Some(field.bytes().await.map_err(bad_request)?),
+            "table" => table_name = field.text().await.map_err(bad_request)?,
+            "schema" => schema_name = field.text().await.map_err(bad_request)?,
+            "uri" if target.allow_client_uri => {
+                postgis_uri = field.text().await.map_err(bad_request)?
+            }
+            "uri" => {
+                return Err(HttpError(
+                    StatusCode::FORBIDDEN,
+                    "This server doesn't accept connection URIs from clients".to_string(),
+                ))
+            }
+            name => return Err(bad_request(format!("Unknown field '{}'", name))),
+        }
+    }
+    let upload = upload.ok_or_else(|| bad_request("Missing field 'file'"))?;
+    if table_name.is_empty() {
+        return Err(bad_request("Missing field 'table'"));
+    }
+    // Checked before the upload is spooled to disk, as the load would only check them after
+    validate_identifier("Table", &table_name).map_err(bad_request)?;
+    validate_identifier("Schema", &schema_name).map_err(bad_request)?;
+
+    let report = tokio::task::spawn_blocking(move || {
+        launch_process_reader(
+            Cursor::new(upload),
+            &table_name,
+            &postgis_uri,
+            &schema_name,
+            // Uploads come from anyone who can reach the server
+            ProcessorOptions {
+                locked_down: true,
+                ..ProcessorOptions::default()
+            },
+        )
+    })
+    .await
+    .map_err(|e| HttpError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .map_err(|e| HttpError(StatusCode::UNPROCESSABLE_ENTITY, e.to_string()))?;
+    Ok(Json(report))
+}
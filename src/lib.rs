@@ -1 +1,10 @@
 pub mod duckdb_load;
+
+#[cfg(feature = "napi")]
+pub mod node;
+
+#[cfg(feature = "server")]
+pub mod server;
+
+#[cfg(feature = "http")]
+pub mod http;
@@ -0,0 +1,123 @@
+// Node.js bindings, enabled with the napi feature. Loads run on the libuv thread pool and
+// resolve promises, so they don't block the event loop
+use napi::bindgen_prelude::AsyncTask;
+use napi::{Env, Error, Result, Task};
+use napi_derive::napi;
+
+use crate::duckdb_load::{inspect_file, launch_process_file_with_options, ProcessorOptions};
+
+// What a load did, as returned to JavaScript
+#[napi(object)]
+pub struct LoadSummary {
+    pub rows_loaded: i64,
+    pub source_crs: Option<String>,
+    pub geometry_tables: Vec<String>,
+    pub rejected_rows: i64,
+    pub source_sha256: Option<String>,
+}
+
+#[napi(object)]
+pub struct ColumnSummary {
+    pub name: String,
+    pub data_type: String,
+}
+
+// What inspect found in a file, as returned to JavaScript
+#[napi(object)]
+pub struct FileSummary {
+    pub file_type: String,
+    pub layers: Vec<String>,
+    pub columns: Vec<ColumnSummary>,
+    pub crs: Option<String>,
+    pub feature_count: i64,
+    pub suggested_table_name: String,
+}
+
+pub struct ProcessFileTask {
+    file_path: String,
+    table_name: String,
+    postgis_uri: String,
+    schema_name: String,
+}
+
+impl Task for ProcessFileTask {
+    type Output = LoadSummary;
+    type JsValue = LoadSummary;
+
+    fn compute(&mut self) -> Result<LoadSummary> {
+        let report = launch_process_file_with_options(
+            &self.file_path,
+            &self.table_name,
+            &self.postgis_uri,
+            &self.schema_name,
+            ProcessorOptions::default(),
+        )
+        .map_err(|e| Error::from_reason(e.to_string()))?;
+        Ok(LoadSummary {
+            rows_loaded: report.rows_loaded as i64,
+            source_crs: report.source_crs,
+            geometry_tables: report.geometry_tables,
+            rejected_rows: report.rejected_rows as i64,
+            source_sha256: report.source_sha256,
+        })
+    }
+
+    fn resolve(&mut self, _env: Env, output: LoadSummary) -> Result<LoadSummary> {
+        Ok(output)
+    }
+}
+
+pub struct InspectTask {
+    file_path: String,
+}
+
+impl Task for InspectTask {
+    type Output = FileSummary;
+    type JsValue = FileSummary;
+
+    fn compute(&mut self) -> Result<FileSummary> {
+        let inspection =
+            inspect_file(&self.file_path).map_err(|e| Error::from_reason(e.to_string()))?;
+        Ok(FileSummary {
+            file_type: inspection.file_type,
+            layers: inspection.layers.into_iter().map(|l| l.name).collect(),
+            columns: inspection
+                .columns
+                .into_iter()
+                .map(|c| ColumnSummary {
+                    name: c.name,
+                    data_type: c.data_type,
+                })
+                .collect(),
+            crs: inspection.crs.map(|crs| crs.code),
+            feature_count: inspection.feature_count as i64,
+            suggested_table_name: inspection.suggested_table_name,
+        })
+    }
+
+    fn resolve(&mut self, _env: Env, output: FileSummary) -> Result<FileSummary> {
+        Ok(output)
+    }
+}
+
+// processFile(filePath, tableName, postgisUri, schemaName): Promise<LoadSummary>
+#[napi]
+pub fn process_file(
+    file_path: String,
+    table_name: String,
+    postgis_uri: String,
+    schema_name: String,
+) -> AsyncTask<ProcessFileTask> {
+    AsyncTask::new(ProcessFileTask {
+        file_path,
+        table_name,
+        postgis_uri,
+        schema_name,
+    })
+}
+
+// inspect(filePath): Promise<FileSummary>
+#[napi]
+pub fn inspect(file_path: String) -> AsyncTask<InspectTask> {
+    AsyncTask::new(InspectTask { file_path })
+}
@@ -0,0 +1,272 @@
+// gRPC ingestion service, enabled with the server feature. Loads run on blocking threads and
+// stream their status back to the caller, and can be polled by job id
+use std::collections::BTreeMap;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use tokio::sync::{mpsc, Semaphore};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+
+use crate::duckdb_load::{
+    check_local_source, inspect_file, launch_process_file_with_options, validate_identifier,
+    LoadProgress, LoadReport, LoadStage, ProcessorOptions,
+};
+
+mod proto {
+    tonic::include_proto!("duckdb_postgis.v1");
+}
+
+use proto::ingest_server::{Ingest, IngestServer};
+use proto::job_status::State;
+use proto::{
+    Column, GetJobStatusRequest, InspectRequest, InspectResponse, JobStatus, LoadFileRequest,
+    LoadSummary,
+};
+
+// How often a running load's status is checked for the LoadFile stream
+const STATUS_INTERVAL: Duration = Duration::from_millis(500);
+// Finished jobs kept for GetJobStatus - the oldest are forgotten beyond this
+const MAX_FINISHED_JOBS: usize = 1000;
+
+// A load started through the service
+struct Job {
+    progress: LoadProgress,
+    outcome: Option<Result<LoadReport, String>>,
+}
+
+type Jobs = Arc<Mutex<BTreeMap<u64, Job>>>;
+
+// A load that panicked leaves the jobs as they were, so a poisoned lock is still used
+fn lock(jobs: &Jobs) -> MutexGuard<'_, BTreeMap<u64, Job>> {
+    jobs.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+struct IngestService {
+    jobs: Jobs,
+    next_job: AtomicU64,
+    // Used for loads that don't give a connection
+    default_uri: String,
+    // Whether a load may name its own connection
+    allow_client_uri: bool,
+    // Bounds the loads and inspections running at once - the rest wait for a permit
+    permits: Arc<Semaphore>,
+}
+
+// Serve the Ingest service on addr until the process is stopped, running at most max_concurrent
+// loads and inspections at a time. Clients can only name their own connection with
+// allow_client_uri, as the server otherwise connects wherever it is told
+pub fn serve(
+    addr: SocketAddr,
+    default_uri: &str,
+    allow_client_uri: bool,
+    max_concurrent: usize,
+) -> Result<(), io::Error> {
+    let service = IngestService {
+        jobs: Jobs::default(),
+        next_job: AtomicU64::new(1),
+        default_uri: default_uri.to_string(),
+        allow_client_uri,
+        permits: Arc::new(Semaphore::new(max_concurrent.max(1))),
+    };
+    tokio::runtime::Runtime::new()?
+        .block_on(
+            Server::builder()
+                .add_service(IngestServer::new(service))
+                .serve(addr),
+        )
+        .map_err(|e| io::Error::other(format!("gRPC server on {} failed: {}", addr, e)))
+}
+
+fn invalid_argument(e: impl ToString) -> Status {
+    Status::invalid_argument(e.to_string())
+}
+
+fn job_status(id: u64, job: &Job) -> JobStatus {
+    let mut status = JobStatus {
+        job_id: id.to_string(),
+        stage: job
+            .progress
+            .stage()
+            .map(LoadStage::name)
+            .unwrap_or_default()
+            .to_string(),
+        rows_written: job.progress.rows_written(),
+        rows_total: job.progress.rows_total(),
+        ..Default::default()
+    };
+    match &job.outcome {
+        None => status.set_state(State::Running),
+        Some(Ok(report)) => {
+            status.set_state(State::Succeeded);
+            status.report = Some(LoadSummary {
+                rows_loaded: report.rows_loaded,
+                source_crs: report.source_crs.clone().unwrap_or_default(),
+                geometry_tables: report.geometry_tables.clone(),
+                rejected_rows: report.rejected_rows,
+                source_sha256: report.source_sha256.clone().unwrap_or_default(),
+            });
+        }
+        Some(Err(e)) => {
+            status.set_state(State::Failed);
+            status.error = e.clone();
+        }
+    }
+    status
+}
+
+fn finish_job(jobs: &Jobs, id: u64, outcome: Result<LoadReport, String>) {
+    let mut jobs = lock(jobs);
+    if let Some(job) = jobs.get_mut(&id) {
+        job.outcome = Some(outcome);
+    }
+    let finished = jobs
+        .iter()
+        .filter(|(_, job)| job.outcome.is_some())
+        .map(|(id, _)| *id)
+        .collect::<Vec<_>>();
+    for id in finished
+        .iter()
+        .take(finished.len().saturating_sub(MAX_FINISHED_JOBS))
+    {
+        jobs.remove(id);
+    }
+}
+
+#[tonic::async_trait]
+impl Ingest for IngestService {
+    type LoadFileStream = ReceiverStream<Result<JobStatus, Status>>;
+
+    async fn load_file(
+        &self,
+        request: Request<LoadFileRequest>,
+    ) -> Result<Response<Self::LoadFileStream>, Status> {
+        let request = request.into_inner();
+        let postgis_uri = if request.postgis_uri.is_empty() {
+            self.default_uri.clone()
+        } else if self.allow_client_uri {
+            request.postgis_uri
+        } else {
+            return Err(Status::permission_denied(
+                "This server doesn't accept connection URIs from clients",
+            ));
+        };
+        // Checked up front, so a refused load never takes a job id
+        validate_identifier("Table", &request.table_name).map_err(invalid_argument)?;
+        validate_identifier("Schema", &request.schema_name).map_err(invalid_argument)?;
+        check_local_source(&request.file_path).map_err(invalid_argument)?;
+
+        let id = self.next_job.fetch_add(1, Ordering::Relaxed);
+        let progress = LoadProgress::new();
+        lock(&self.jobs).insert(
+            id,
+            Job {
+                progress: progress.clone(),
+                outcome: None,
+            },
+        );
+
+        let jobs = self.jobs.clone();
+        let permits = self.permits.clone();
+        tokio::spawn(async move {
+            // The semaphore is never closed
+            let Ok(permit) = permits.acquire_owned().await else {
+                return;
+            };
+            tokio::task::spawn_blocking(move || {
+                let options = ProcessorOptions {
+                    progress: Some(progress),
+                    // Requests come from anyone who can reach the server
+                    locked_down: true,
+                    ..ProcessorOptions::default()
+                };
+                let outcome = launch_process_file_with_options(
+                    &request.file_path,
+                    &request.table_name,
+                    &postgis_uri,
+                    &request.schema_name,
+                    options,
+                )
+                .map_err(|e| e.to_string());
+                finish_job(&jobs, id, outcome);
+                drop(permit);
+            });
+        });
+
+        // Send the status whenever it changes, until the load finishes
+        // A caller that disconnects stops the stream but not the load
+        let (sender, receiver) = mpsc::channel(4);
+        let jobs = self.jobs.clone();
+        tokio::spawn(async move {
+            let mut last_sent = None;
+            loop {
+                let status = match lock(&jobs).get(&id) {
+                    Some(job) => job_status(id, job),
+                    None => break,
+                };
+                let running = status.state() == State::Running;
+                if last_sent.as_ref() != Some(&status) {
+                    if sender.send(Ok(status.clone())).await.is_err() {
+                        break;
+                    }
+                    last_sent = Some(status);
+                }
+                if !running {
+                    break;
+                }
+                tokio::time::sleep(STATUS_INTERVAL).await;
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+
+    async fn inspect(
+        &self,
+        request: Request<InspectRequest>,
+    ) -> Result<Response<InspectResponse>, Status> {
+        let file_path = request.into_inner().file_path;
+        check_local_source(&file_path).map_err(invalid_argument)?;
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        let inspection = tokio::task::spawn_blocking(move || inspect_file(&file_path))
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        Ok(Response::new(InspectResponse {
+            file_type: inspection.file_type,
+            layers: inspection.layers.into_iter().map(|l| l.name).collect(),
+            columns: inspection
+                .columns
+                .into_iter()
+                .map(|c| Column {
+                    name: c.name,
+                    data_type: c.data_type,
+                })
+                .collect(),
+            crs: inspection.crs.map(|crs| crs.code).unwrap_or_default(),
+            feature_count: inspection.feature_count,
+            suggested_table_name: inspection.suggested_table_name,
+        }))
+    }
+
+    async fn get_job_status(
+        &self,
+        request: Request<GetJobStatusRequest>,
+    ) -> Result<Response<JobStatus>, Status> {
+        let job_id = request.into_inner().job_id;
+        let jobs = lock(&self.jobs);
+        job_id
+            .parse()
+            .ok()
+            .and_then(|id| jobs.get(&id).map(|job| job_status(id, job)))
+            .map(Response::new)
+            .ok_or_else(|| Status::not_found(format!("No job '{}'", job_id)))
+    }
+}